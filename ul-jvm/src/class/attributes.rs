@@ -1,6 +1,6 @@
 use std::collections::HashMap;
 
-use anyhow::Result;
+use anyhow::{Result, bail};
 use serde::Serialize;
 
 use crate::types::JvmTypeDescriptor;
@@ -10,7 +10,7 @@ use super::{
         ConstantClass, ConstantJvmUtf8, ConstantMethodHandle, ConstantMethodref,
         LoadableJvmConstant,
     },
-    get_class,
+    get_class, get_loadable_constant,
     jvm_unit::JvmVisibility,
     parser,
 };
@@ -125,6 +125,32 @@ pub struct BootstrapMethodsEntry {
     pub bootstrap_arguments: Vec<LoadableJvmConstant>,
 }
 
+impl BootstrapMethodsEntry {
+    pub fn try_from_parser(
+        loadable_constant_pool: &HashMap<u16, LoadableJvmConstant>,
+        parser: parser::attributes::BootstrapMethodsEntry,
+    ) -> Result<Self> {
+        let Some(LoadableJvmConstant::MethodHandle(bootstrap_method_ref)) = loadable_constant_pool
+            .get(&parser.bootstrap_method_ref)
+            .cloned()
+        else {
+            bail!(
+                "no MethodHandle in constant pool at {} for a bootstrap method",
+                parser.bootstrap_method_ref
+            );
+        };
+
+        Ok(Self {
+            bootstrap_method_ref,
+            bootstrap_arguments: parser
+                .bootstrap_arguments
+                .iter()
+                .map(|idx| get_loadable_constant(loadable_constant_pool, idx))
+                .collect::<Result<Vec<_>>>()?,
+        })
+    }
+}
+
 #[derive(Debug, Clone, Serialize)]
 pub struct NestHost {
     pub host_class_index: ConstantClass,
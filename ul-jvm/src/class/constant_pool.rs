@@ -39,7 +39,7 @@ pub struct ConstantInterfaceMethodref {
 
 #[derive(Debug, Clone, Serialize)]
 pub struct DynamicInvoke {
-    pub bootstrap_method_attr_index: u16, // TODO: bootstrap methods
+    pub bootstrap_method_attr_index: u16,
     pub name: ConstantJvmUtf8,
     pub ty: JvmMethodDescriptor,
 }
@@ -0,0 +1,1644 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Display,
+    io::Cursor,
+};
+
+use binrw::BinRead;
+use log::debug;
+
+use super::parser::{
+    AttributeInfo, ClassAccessFlags, ClassFile, ConstantPoolInfo, FieldAccessFlags, FieldInfo,
+    MethodAccessFlags, MethodInfo, MethodKind,
+    attributes::{
+        BootstrapMethods, Code, ConstantValue, Exceptions, LineNumberTable, LocalVariableTable,
+        LocalVariableTypeTable, MethodParameters, Record, Signature, StackMapFrame, StackMapTable,
+        VerificationTypeInfo,
+    },
+};
+
+/// A class file rejected while parsing it or by the format checks (JVMS §4.8), thrown as a
+/// ClassFormatError
+#[derive(Debug, Clone)]
+pub struct ClassFormatError {
+    /// The binary name of the class (java/lang/Object)
+    pub class_name: String,
+    /// The field or method at fault: the name of a field, the name and descriptor of a method
+    pub member: Option<String>,
+    /// The offset in the class file of the structure at fault
+    pub offset: u64,
+    pub reason: String,
+}
+
+impl Display for ClassFormatError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.class_name.replace('/', "."))?;
+
+        if let Some(member) = &self.member {
+            write!(f, ".{member}")?;
+        }
+
+        write!(
+            f,
+            ": {} (at offset {} of the class file)",
+            self.reason, self.offset
+        )
+    }
+}
+
+impl std::error::Error for ClassFormatError {}
+
+/// Parses a class file, failing with a ClassFormatError if it is truncated, has extra bytes at
+/// its end, or has unknown tags or malformed strings
+pub fn parse_class_file(class_name: &str, bytes: &[u8]) -> Result<ClassFile, ClassFormatError> {
+    let mut reader = Cursor::new(bytes);
+    let end = bytes.len() as u64;
+
+    let error = |offset, reason| ClassFormatError {
+        class_name: class_name.to_string(),
+        member: None,
+        offset,
+        reason,
+    };
+
+    let class_file = ClassFile::read(&mut reader).map_err(|err| {
+        let (offset, reason) = describe_parse_error(&err, end);
+
+        error(offset, reason)
+    })?;
+
+    if reader.position() != end {
+        return Err(error(
+            reader.position(),
+            "extra bytes at the end of the class file".to_string(),
+        ));
+    }
+
+    Ok(class_file)
+}
+
+/// The offset and the reason of an error of the parser
+fn describe_parse_error(err: &binrw::Error, end: u64) -> (u64, String) {
+    if err.is_eof() {
+        return (end, "truncated class file".to_string());
+    }
+
+    match err.root_cause() {
+        binrw::Error::BadMagic { pos: 0, found } => {
+            (0, format!("incompatible magic value {found:#x?}"))
+        }
+        binrw::Error::BadMagic { pos, .. } | binrw::Error::NoVariantMatch { pos } => {
+            (*pos, "unknown tag".to_string())
+        }
+        binrw::Error::AssertFail { pos, message } => (*pos, message.clone()),
+        // Enums are parsed by trying each variant: the interesting error is the one of the
+        // variant whose tag matched, if any
+        binrw::Error::EnumErrors {
+            pos,
+            variant_errors,
+        } => variant_errors
+            .iter()
+            .map(|(_, err)| err)
+            .find(|err| !matches!(err.root_cause(), binrw::Error::BadMagic { .. }))
+            .map_or_else(
+                || (*pos, "unknown tag".to_string()),
+                |err| describe_parse_error(err, end),
+            ),
+        err => (0, err.to_string()),
+    }
+}
+
+/// Checks a parsed class file against the format checks of JVMS §4.8: the references to the
+/// constant pool, the names and descriptors, the access flags, and the attributes read by the
+/// JVM. The checks of the code itself are left to the verifier.
+pub fn check_format(class_file: &ClassFile) -> Result<(), ClassFormatError> {
+    let strings = class_file
+        .constant_pool
+        .iter()
+        .enumerate()
+        .filter_map(|(index, constant)| match constant {
+            ConstantPoolInfo::Utf8 { bytes, .. } => Some((index as u16, bytes.convert_to_string())),
+            _ => None,
+        })
+        .collect();
+
+    FormatChecker::new(class_file, &strings).check()
+}
+
+struct FormatChecker<'a> {
+    class_file: &'a ClassFile,
+    /// The content of the Utf8 constants, by index
+    strings: &'a HashMap<u16, String>,
+    /// The offset of each constant pool entry in the class file
+    offsets: Vec<u64>,
+    /// The offset of the access flags, right after the constant pool
+    constant_pool_end: u64,
+    class_name: String,
+    is_interface: bool,
+    /// The field or method being checked, for the errors
+    member: Option<String>,
+}
+
+/// The attributes which may appear at most once in a class, field, method or Code attribute
+const UNIQUE_ATTRIBUTES: &[&str] = &[
+    "BootstrapMethods",
+    "Code",
+    "ConstantValue",
+    "Exceptions",
+    "MethodParameters",
+    "Record",
+    "Signature",
+    "StackMapTable",
+];
+
+impl<'a> FormatChecker<'a> {
+    fn new(class_file: &'a ClassFile, strings: &'a HashMap<u16, String>) -> Self {
+        // The constant pool starts after the magic, the version and the constant pool count
+        let mut offset = 10;
+        let mut offsets = Vec::with_capacity(class_file.constant_pool.len());
+
+        for constant in class_file.constant_pool.iter() {
+            offsets.push(offset);
+            offset += constant.size();
+        }
+
+        // The name of the class is only known once this_class is checked
+        let class_name = match class_file.constant_pool.get(class_file.this_class as usize) {
+            Some(ConstantPoolInfo::Class { name_index }) => strings.get(name_index).cloned(),
+            _ => None,
+        };
+
+        Self {
+            class_file,
+            strings,
+            offsets,
+            constant_pool_end: offset,
+            class_name: class_name.unwrap_or_else(|| "<unknown class>".to_string()),
+            is_interface: class_file
+                .access_flags
+                .contains(&ClassAccessFlags::Interface),
+            member: None,
+        }
+    }
+
+    fn check(mut self) -> Result<(), ClassFormatError> {
+        debug!("checking the format of {}", self.class_name);
+
+        self.check_constant_pool()?;
+
+        let mut offset = self.check_class()?;
+
+        let mut fields = HashSet::new();
+
+        for field in self.class_file.fields.iter() {
+            offset = self.check_field(field, offset, &mut fields)?;
+        }
+
+        // The methods count
+        offset += 2;
+
+        let mut methods = HashSet::new();
+
+        for method in self.class_file.methods.iter() {
+            offset = self.check_method(method, offset, &mut methods)?;
+        }
+
+        self.member = None;
+
+        self.check_class_attributes(offset + 2)
+    }
+
+    fn error<T>(&self, offset: u64, reason: impl Into<String>) -> Result<T, ClassFormatError> {
+        Err(ClassFormatError {
+            class_name: self.class_name.clone(),
+            member: self.member.clone(),
+            offset,
+            reason: reason.into(),
+        })
+    }
+
+    fn entry(&self, index: u16) -> Option<&'a ConstantPoolInfo> {
+        match self.class_file.constant_pool.get(index as usize) {
+            None | Some(ConstantPoolInfo::Ignored) => None,
+            v => v,
+        }
+    }
+
+    /// The content of a Utf8 constant, referenced by the structure at the given offset
+    fn utf8(&self, offset: u64, index: u16, what: &str) -> Result<&'a str, ClassFormatError> {
+        match self.strings.get(&index) {
+            Some(v) => Ok(v),
+            None => self.error(
+                offset,
+                format!("invalid constant pool index {index} for {what} (Utf8 expected)"),
+            ),
+        }
+    }
+
+    /// The name of a Class constant, referenced by the structure at the given offset
+    fn class(&self, offset: u64, index: u16, what: &str) -> Result<&'a str, ClassFormatError> {
+        match self.entry(index) {
+            Some(ConstantPoolInfo::Class { name_index }) => {
+                self.utf8(self.offsets[index as usize], *name_index, "a class name")
+            }
+            _ => self.error(
+                offset,
+                format!("invalid constant pool index {index} for {what} (Class expected)"),
+            ),
+        }
+    }
+
+    /// The name and descriptor of a NameAndType constant, referenced by the structure at the
+    /// given offset
+    fn name_and_type(
+        &self,
+        offset: u64,
+        index: u16,
+    ) -> Result<(&'a str, &'a str), ClassFormatError> {
+        match self.entry(index) {
+            Some(ConstantPoolInfo::NameAndType {
+                name_index,
+                descriptor_index,
+            }) => {
+                let offset = self.offsets[index as usize];
+
+                Ok((
+                    self.utf8(offset, *name_index, "a name")?,
+                    self.utf8(offset, *descriptor_index, "a descriptor")?,
+                ))
+            }
+            _ => self.error(
+                offset,
+                format!(
+                    "invalid constant pool index {index} for a name and type (NameAndType expected)"
+                ),
+            ),
+        }
+    }
+
+    /// The name of the member referenced by a Fieldref, Methodref or InterfaceMethodref, if valid
+    fn member_name(&self, index: u16) -> Option<&'a str> {
+        let name_and_type_index = match self.entry(index)? {
+            ConstantPoolInfo::Fieldref {
+                name_and_type_index,
+                ..
+            }
+            | ConstantPoolInfo::Methodref {
+                name_and_type_index,
+                ..
+            }
+            | ConstantPoolInfo::InterfaceMethodref {
+                name_and_type_index,
+                ..
+            } => *name_and_type_index,
+            _ => return None,
+        };
+
+        match self.entry(name_and_type_index)? {
+            ConstantPoolInfo::NameAndType { name_index, .. } => {
+                self.strings.get(name_index).map(String::as_str)
+            }
+            _ => None,
+        }
+    }
+
+    fn check_constant_pool(&self) -> Result<(), ClassFormatError> {
+        let major_version = self.class_file.major_version;
+
+        for (index, constant) in self.class_file.constant_pool.iter().enumerate() {
+            let offset = self.offsets[index];
+
+            match constant {
+                ConstantPoolInfo::Class { name_index } => {
+                    let name = self.utf8(offset, *name_index, "a class name")?;
+
+                    let is_valid = if name.starts_with('[') {
+                        is_field_descriptor(name)
+                    } else {
+                        is_binary_name(name)
+                    };
+
+                    if !is_valid {
+                        return self.error(offset, format!("invalid class name {name}"));
+                    }
+                }
+                ConstantPoolInfo::String { string_index } => {
+                    self.utf8(offset, *string_index, "a string")?;
+                }
+                ConstantPoolInfo::Fieldref {
+                    class_index,
+                    name_and_type_index,
+                } => {
+                    self.class(offset, *class_index, "the class of a field")?;
+
+                    let (name, descriptor) = self.name_and_type(offset, *name_and_type_index)?;
+
+                    if !is_unqualified_name(name) {
+                        return self.error(offset, format!("invalid field name {name}"));
+                    }
+
+                    if !is_field_descriptor(descriptor) {
+                        return self
+                            .error(offset, format!("invalid field descriptor {descriptor}"));
+                    }
+                }
+                ConstantPoolInfo::Methodref {
+                    class_index,
+                    name_and_type_index,
+                }
+                | ConstantPoolInfo::InterfaceMethodref {
+                    class_index,
+                    name_and_type_index,
+                } => {
+                    self.class(offset, *class_index, "the class of a method")?;
+
+                    let (name, descriptor) = self.name_and_type(offset, *name_and_type_index)?;
+
+                    if name != "<init>" && !is_method_name(name) {
+                        return self.error(offset, format!("invalid method name {name}"));
+                    }
+
+                    if !is_method_descriptor(descriptor)
+                        || (name == "<init>" && !descriptor.ends_with(")V"))
+                    {
+                        return self
+                            .error(offset, format!("invalid method descriptor {descriptor}"));
+                    }
+                }
+                ConstantPoolInfo::MethodHandle {
+                    reference_kind,
+                    reference_index,
+                } => self.check_method_handle(offset, reference_kind, *reference_index)?,
+                ConstantPoolInfo::MethodType { descriptor_index } => {
+                    let descriptor = self.utf8(offset, *descriptor_index, "a method type")?;
+
+                    if !is_method_descriptor(descriptor) {
+                        return self
+                            .error(offset, format!("invalid method descriptor {descriptor}"));
+                    }
+                }
+                ConstantPoolInfo::Dynamic {
+                    name_and_type_index,
+                    ..
+                } => {
+                    let (name, descriptor) = self.name_and_type(offset, *name_and_type_index)?;
+
+                    if !is_unqualified_name(name) {
+                        return self.error(offset, format!("invalid dynamic constant name {name}"));
+                    }
+
+                    if !is_field_descriptor(descriptor) {
+                        return self
+                            .error(offset, format!("invalid field descriptor {descriptor}"));
+                    }
+                }
+                ConstantPoolInfo::DynamicInvoke {
+                    name_and_type_index,
+                    ..
+                } => {
+                    let (name, descriptor) = self.name_and_type(offset, *name_and_type_index)?;
+
+                    if !is_method_name(name) {
+                        return self.error(offset, format!("invalid method name {name}"));
+                    }
+
+                    if !is_method_descriptor(descriptor) {
+                        return self
+                            .error(offset, format!("invalid method descriptor {descriptor}"));
+                    }
+                }
+                ConstantPoolInfo::NameAndType {
+                    name_index,
+                    descriptor_index,
+                } => {
+                    self.utf8(offset, *name_index, "a name")?;
+                    self.utf8(offset, *descriptor_index, "a descriptor")?;
+                }
+                ConstantPoolInfo::Module { name_index }
+                | ConstantPoolInfo::Package { name_index } => {
+                    self.utf8(offset, *name_index, "a module or package name")?;
+                }
+                ConstantPoolInfo::Ignored
+                | ConstantPoolInfo::Integer { .. }
+                | ConstantPoolInfo::Float { .. }
+                | ConstantPoolInfo::Long { .. }
+                | ConstantPoolInfo::Double { .. }
+                | ConstantPoolInfo::Utf8 { .. } => (),
+            }
+        }
+
+        // Interface methods are only allowed in method handles since version 52
+        if major_version < 52 {
+            for (index, constant) in self.class_file.constant_pool.iter().enumerate() {
+                if let ConstantPoolInfo::MethodHandle {
+                    reference_index, ..
+                } = constant
+                    && let Some(ConstantPoolInfo::InterfaceMethodref { .. }) =
+                        self.entry(*reference_index)
+                {
+                    return self.error(
+                        self.offsets[index],
+                        "method handle to an interface method in a class file older than version 52",
+                    );
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn check_method_handle(
+        &self,
+        offset: u64,
+        reference_kind: &MethodKind,
+        reference_index: u16,
+    ) -> Result<(), ClassFormatError> {
+        let reference = self.entry(reference_index);
+
+        let is_valid = match reference_kind {
+            MethodKind::GetField
+            | MethodKind::GetStatic
+            | MethodKind::PutField
+            | MethodKind::PutStatic => matches!(reference, Some(ConstantPoolInfo::Fieldref { .. })),
+            MethodKind::InvokeVirtual | MethodKind::NewInvokeSpecial => {
+                matches!(reference, Some(ConstantPoolInfo::Methodref { .. }))
+            }
+            MethodKind::InvokeStatic | MethodKind::InvokeSpecial => matches!(
+                reference,
+                Some(
+                    ConstantPoolInfo::Methodref { .. }
+                        | ConstantPoolInfo::InterfaceMethodref { .. }
+                )
+            ),
+            MethodKind::InvokeInterface => {
+                matches!(reference, Some(ConstantPoolInfo::InterfaceMethodref { .. }))
+            }
+        };
+
+        if !is_valid {
+            return self.error(
+                offset,
+                format!(
+                    "invalid constant pool index {reference_index} for a method handle of kind {reference_kind:?}"
+                ),
+            );
+        }
+
+        let name = self.member_name(reference_index);
+
+        let is_valid_name = match reference_kind {
+            MethodKind::NewInvokeSpecial => name.is_none_or(|v| v == "<init>"),
+            MethodKind::InvokeVirtual
+            | MethodKind::InvokeStatic
+            | MethodKind::InvokeSpecial
+            | MethodKind::InvokeInterface => name.is_none_or(|v| v != "<init>" && v != "<clinit>"),
+            _ => true,
+        };
+
+        if !is_valid_name {
+            return self.error(
+                offset,
+                format!(
+                    "invalid method {} for a method handle of kind {reference_kind:?}",
+                    name.unwrap_or_default()
+                ),
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Checks the access flags, this class, the superclass and the interfaces, returning the
+    /// offset of the fields
+    fn check_class(&self) -> Result<u64, ClassFormatError> {
+        let class_file = self.class_file;
+        let offset = self.constant_pool_end;
+        let flags = &class_file.access_flags;
+        let has = |flag| flags.contains(&flag);
+        let is_at_least_49 = class_file.major_version >= 49;
+
+        // The ACC_ABSTRACT flag of interfaces is implicit before version 50
+        let is_illegal = (self.is_interface
+            && !has(ClassAccessFlags::Abstract)
+            && class_file.major_version >= 50)
+            || (self.is_interface
+                && is_at_least_49
+                && (has(ClassAccessFlags::Super) || has(ClassAccessFlags::Enum)))
+            || (!self.is_interface && is_at_least_49 && has(ClassAccessFlags::Annotation))
+            || (has(ClassAccessFlags::Final) && has(ClassAccessFlags::Abstract));
+
+        if is_illegal {
+            return self.error(offset, format!("illegal class modifiers {flags:?}"));
+        }
+
+        let this_class = self.class(offset + 2, class_file.this_class, "this_class")?;
+
+        if this_class.starts_with('[') {
+            return self.error(offset + 2, format!("invalid this_class {this_class}"));
+        }
+
+        if class_file.super_class == 0 {
+            // Modules (module-info) have no superclass either
+            if this_class != "java/lang/Object" && !flags.contains(&ClassAccessFlags::Module) {
+                return self.error(offset + 4, "no superclass");
+            }
+        } else {
+            let super_class = self.class(offset + 4, class_file.super_class, "the superclass")?;
+
+            if this_class == "java/lang/Object" {
+                return self.error(offset + 4, "superclass for java.lang.Object");
+            }
+
+            if super_class.starts_with('[')
+                || (self.is_interface && super_class != "java/lang/Object")
+            {
+                return self.error(offset + 4, format!("invalid superclass {super_class}"));
+            }
+        }
+
+        let mut offset = offset + 8;
+
+        for interface in class_file.interfaces.iter() {
+            let name = self.class(offset, *interface, "an interface")?;
+
+            if name.starts_with('[') {
+                return self.error(offset, format!("invalid interface {name}"));
+            }
+
+            offset += 2;
+        }
+
+        // The fields count
+        Ok(offset + 2)
+    }
+
+    /// Checks a field, returning the offset of the next one
+    fn check_field(
+        &mut self,
+        field: &FieldInfo,
+        offset: u64,
+        fields: &mut HashSet<(&'a str, &'a str)>,
+    ) -> Result<u64, ClassFormatError> {
+        self.member = None;
+
+        let name = self.utf8(offset + 2, field.name_index, "a field name")?;
+        let descriptor = self.utf8(offset + 4, field.descriptor_index, "a field descriptor")?;
+
+        self.member = Some(name.to_string());
+
+        if !is_unqualified_name(name) {
+            return self.error(offset + 2, format!("invalid field name {name}"));
+        }
+
+        if !is_field_descriptor(descriptor) {
+            return self.error(offset + 4, format!("invalid field descriptor {descriptor}"));
+        }
+
+        let flags = &field.access_flags;
+        let has = |flag| flags.contains(&flag);
+
+        let visibilities = [
+            FieldAccessFlags::Public,
+            FieldAccessFlags::Private,
+            FieldAccessFlags::Protected,
+        ]
+        .into_iter()
+        .filter(|v| has(*v))
+        .count();
+
+        let is_illegal = if self.is_interface {
+            !has(FieldAccessFlags::Public)
+                || !has(FieldAccessFlags::Static)
+                || !has(FieldAccessFlags::Final)
+                || visibilities > 1
+                || has(FieldAccessFlags::Volatile)
+                || has(FieldAccessFlags::Transient)
+                || (self.class_file.major_version >= 49 && has(FieldAccessFlags::Enum))
+        } else {
+            visibilities > 1 || (has(FieldAccessFlags::Final) && has(FieldAccessFlags::Volatile))
+        };
+
+        if is_illegal {
+            return self.error(offset, format!("illegal field modifiers {flags:?}"));
+        }
+
+        if !fields.insert((name, descriptor)) {
+            return self.error(offset, "duplicate field name and descriptor");
+        }
+
+        let mut unique = HashSet::new();
+        let mut offset = offset + 8;
+
+        for attribute in field.attributes.iter() {
+            match self.attribute_name(attribute, offset, &mut unique)? {
+                "ConstantValue" => {
+                    let value: ConstantValue =
+                        self.parse_attribute(attribute, offset, "ConstantValue")?;
+                    let index = value.constantvalue_index;
+
+                    let is_valid = matches!(
+                        (descriptor, self.entry(index)),
+                        (
+                            "B" | "C" | "I" | "S" | "Z",
+                            Some(ConstantPoolInfo::Integer { .. })
+                        ) | ("J", Some(ConstantPoolInfo::Long { .. }))
+                            | ("F", Some(ConstantPoolInfo::Float { .. }))
+                            | ("D", Some(ConstantPoolInfo::Double { .. }))
+                            | ("Ljava/lang/String;", Some(ConstantPoolInfo::String { .. }))
+                    );
+
+                    if !is_valid {
+                        return self.error(
+                            offset,
+                            format!(
+                                "invalid constant pool index {index} for the constant value of a field of type {descriptor}"
+                            ),
+                        );
+                    }
+                }
+                "Signature" => self.check_signature(attribute, offset)?,
+                _ => (),
+            }
+
+            offset += attribute.size();
+        }
+
+        Ok(offset)
+    }
+
+    /// Checks a method, returning the offset of the next one
+    fn check_method(
+        &mut self,
+        method: &MethodInfo,
+        offset: u64,
+        methods: &mut HashSet<(&'a str, &'a str)>,
+    ) -> Result<u64, ClassFormatError> {
+        self.member = None;
+
+        let name = self.utf8(offset + 2, method.name_index, "a method name")?;
+        let descriptor = self.utf8(offset + 4, method.descriptor_index, "a method descriptor")?;
+
+        self.member = Some(format!("{name}{descriptor}"));
+
+        let flags = &method.access_flags;
+        let has = |flag| flags.contains(&flag);
+        let is_static = has(MethodAccessFlags::Static);
+        let is_abstract = has(MethodAccessFlags::Abstract);
+        let is_native = has(MethodAccessFlags::Native);
+        let major_version = self.class_file.major_version;
+
+        let is_init = name == "<init>";
+        let is_clinit = name == "<clinit>";
+
+        if !is_init && !is_clinit && !is_method_name(name) {
+            return self.error(offset + 2, format!("invalid method name {name}"));
+        }
+
+        let Some(mut parameter_slots) = method_descriptor_slots(descriptor) else {
+            return self.error(
+                offset + 4,
+                format!("invalid method descriptor {descriptor}"),
+            );
+        };
+
+        if (is_init || is_clinit) && !descriptor.ends_with(")V") {
+            return self.error(
+                offset + 4,
+                format!("invalid method descriptor {descriptor}"),
+            );
+        }
+
+        if !is_static {
+            parameter_slots += 1;
+        }
+
+        if parameter_slots > 255 {
+            return self.error(offset + 4, "too many parameters");
+        }
+
+        let visibilities = [
+            MethodAccessFlags::Public,
+            MethodAccessFlags::Private,
+            MethodAccessFlags::Protected,
+        ]
+        .into_iter()
+        .filter(|v| has(*v))
+        .count();
+
+        // The strictfp modifier only had a meaning from version 46 to 60
+        let is_strict = has(MethodAccessFlags::Strict) && (46..=60).contains(&major_version);
+
+        let abstract_conflicts = has(MethodAccessFlags::Private)
+            || is_static
+            || has(MethodAccessFlags::Final)
+            || has(MethodAccessFlags::Synchronized)
+            || is_native
+            || is_strict;
+
+        let is_illegal = if is_clinit {
+            // The other flags of class initialization methods are ignored
+            major_version >= 51 && !is_static
+        } else if self.is_interface && is_init {
+            true
+        } else if self.is_interface && major_version < 52 {
+            !has(MethodAccessFlags::Public)
+                || !is_abstract
+                || visibilities > 1
+                || is_static
+                || has(MethodAccessFlags::Final)
+                || has(MethodAccessFlags::Synchronized)
+                || is_native
+                || is_strict
+        } else if self.is_interface {
+            visibilities != 1
+                || has(MethodAccessFlags::Protected)
+                || has(MethodAccessFlags::Final)
+                || has(MethodAccessFlags::Synchronized)
+                || is_native
+                || (is_abstract && abstract_conflicts)
+        } else if is_init {
+            visibilities > 1
+                || is_static
+                || has(MethodAccessFlags::Final)
+                || has(MethodAccessFlags::Synchronized)
+                || is_native
+                || is_abstract
+                || has(MethodAccessFlags::Bridge)
+        } else {
+            visibilities > 1 || (is_abstract && abstract_conflicts)
+        };
+
+        if is_illegal {
+            return self.error(offset, format!("illegal method modifiers {flags:?}"));
+        }
+
+        if !methods.insert((name, descriptor)) {
+            return self.error(offset, "duplicate method name and descriptor");
+        }
+
+        let mut unique = HashSet::new();
+        let mut has_code = false;
+        let method_offset = offset;
+        let mut offset = offset + 8;
+
+        for attribute in method.attributes.iter() {
+            match self.attribute_name(attribute, offset, &mut unique)? {
+                "Code" => {
+                    if is_native || is_abstract {
+                        return self.error(offset, "Code attribute in a native or abstract method");
+                    }
+
+                    self.check_code(attribute, offset, parameter_slots)?;
+                    has_code = true;
+                }
+                "Exceptions" => {
+                    let exceptions: Exceptions =
+                        self.parse_attribute(attribute, offset, "Exceptions")?;
+
+                    for index in exceptions.exception_index_table {
+                        self.class(offset, index, "an exception")?;
+                    }
+                }
+                "MethodParameters" => {
+                    let parameters: MethodParameters =
+                        self.parse_attribute(attribute, offset, "MethodParameters")?;
+
+                    for parameter in parameters.parameters {
+                        if parameter.name_index != 0 {
+                            self.utf8(offset, parameter.name_index, "a parameter name")?;
+                        }
+                    }
+                }
+                "Signature" => self.check_signature(attribute, offset)?,
+                _ => (),
+            }
+
+            offset += attribute.size();
+        }
+
+        if !has_code && !is_native && !is_abstract {
+            return self.error(
+                method_offset,
+                "no Code attribute in a method neither native nor abstract",
+            );
+        }
+
+        Ok(offset)
+    }
+
+    /// Checks a Code attribute: its lengths, its exception table and its own attributes
+    fn check_code(
+        &self,
+        attribute: &AttributeInfo,
+        offset: u64,
+        parameter_slots: usize,
+    ) -> Result<(), ClassFormatError> {
+        let code: Code = self.parse_attribute(attribute, offset, "Code")?;
+        let code_length = code.code_length;
+
+        // After the attribute header, max_stack, max_locals and code_length
+        let code_offset = offset + 14;
+
+        if code_length == 0 || code_length > u16::MAX as u32 {
+            return self.error(
+                code_offset - 4,
+                format!("invalid code length {code_length}"),
+            );
+        }
+
+        if (code.max_locals as usize) < parameter_slots {
+            return self.error(
+                code_offset - 6,
+                format!(
+                    "the {parameter_slots} slots of the parameters do not fit in {} locals",
+                    code.max_locals
+                ),
+            );
+        }
+
+        let mut offset = code_offset + code_length as u64 + 2;
+
+        for entry in code.exception_table.iter() {
+            let start_pc = entry.start_pc as u32;
+            let end_pc = entry.end_pc as u32;
+
+            if start_pc >= end_pc || end_pc > code_length || entry.handler_pc as u32 >= code_length
+            {
+                return self.error(
+                    offset,
+                    format!(
+                        "invalid exception table entry [{start_pc}, {end_pc}) -> {} for a code length of {code_length}",
+                        entry.handler_pc
+                    ),
+                );
+            }
+
+            if entry.catch_type != 0 {
+                self.class(offset + 6, entry.catch_type, "a catch type")?;
+            }
+
+            offset += 8;
+        }
+
+        let mut unique = HashSet::new();
+
+        // The attributes count
+        offset += 2;
+
+        for attribute in code.attributes.iter() {
+            match self.attribute_name(attribute, offset, &mut unique)? {
+                "StackMapTable" => {
+                    let table: StackMapTable =
+                        self.parse_attribute(attribute, offset, "StackMapTable")?;
+
+                    for frame in table.entries.iter() {
+                        let types: &[VerificationTypeInfo] = match frame {
+                            StackMapFrame::Same { .. }
+                            | StackMapFrame::ChopFrame { .. }
+                            | StackMapFrame::SameFrameExtended { .. } => &[],
+                            StackMapFrame::SameLocals1StackItemFrame { stack, .. }
+                            | StackMapFrame::SameLocals1StackItemFrameExtended { stack, .. } => {
+                                stack
+                            }
+                            StackMapFrame::AppendFrame { locals, .. } => locals,
+                            StackMapFrame::FullFrame { locals, stack, .. } => {
+                                for ty in locals {
+                                    self.check_verification_type(ty, offset)?;
+                                }
+
+                                stack
+                            }
+                        };
+
+                        for ty in types {
+                            self.check_verification_type(ty, offset)?;
+                        }
+                    }
+                }
+                "LocalVariableTable" => {
+                    let table: LocalVariableTable =
+                        self.parse_attribute(attribute, offset, "LocalVariableTable")?;
+
+                    for entry in table.local_variable_table {
+                        self.utf8(offset, entry.name_index, "a local variable name")?;
+
+                        let descriptor = self.utf8(
+                            offset,
+                            entry.descriptor_index,
+                            "a local variable descriptor",
+                        )?;
+
+                        if !is_field_descriptor(descriptor) {
+                            return self.error(
+                                offset,
+                                format!("invalid local variable descriptor {descriptor}"),
+                            );
+                        }
+                    }
+                }
+                "LocalVariableTypeTable" => {
+                    let table: LocalVariableTypeTable =
+                        self.parse_attribute(attribute, offset, "LocalVariableTypeTable")?;
+
+                    for entry in table.local_variable_type_table {
+                        self.utf8(offset, entry.name_index, "a local variable name")?;
+                        self.utf8(offset, entry.signature_index, "a local variable signature")?;
+                    }
+                }
+                "LineNumberTable" => {
+                    self.parse_attribute::<LineNumberTable>(attribute, offset, "LineNumberTable")?;
+                }
+                _ => (),
+            }
+
+            offset += attribute.size();
+        }
+
+        Ok(())
+    }
+
+    fn check_verification_type(
+        &self,
+        ty: &VerificationTypeInfo,
+        offset: u64,
+    ) -> Result<(), ClassFormatError> {
+        if let VerificationTypeInfo::Object { cpool_index } = ty {
+            self.class(offset, *cpool_index, "a verification type")?;
+        }
+
+        Ok(())
+    }
+
+    fn check_signature(
+        &self,
+        attribute: &AttributeInfo,
+        offset: u64,
+    ) -> Result<(), ClassFormatError> {
+        let signature: Signature = self.parse_attribute(attribute, offset, "Signature")?;
+
+        self.utf8(offset, signature.signature_index, "a signature")?;
+
+        Ok(())
+    }
+
+    /// Checks the attributes of the class, starting at the given offset
+    fn check_class_attributes(&self, mut offset: u64) -> Result<(), ClassFormatError> {
+        let mut unique = HashSet::new();
+        let mut bootstrap_method_count = 0;
+
+        for attribute in self.class_file.attributes.iter() {
+            match self.attribute_name(attribute, offset, &mut unique)? {
+                "BootstrapMethods" => {
+                    let bootstrap_methods: BootstrapMethods =
+                        self.parse_attribute(attribute, offset, "BootstrapMethods")?;
+
+                    for method in bootstrap_methods.bootstrap_methods.iter() {
+                        let index = method.bootstrap_method_ref;
+
+                        if !matches!(
+                            self.entry(index),
+                            Some(ConstantPoolInfo::MethodHandle { .. })
+                        ) {
+                            return self.error(
+                                offset,
+                                format!("invalid constant pool index {index} for a bootstrap method (MethodHandle expected)"),
+                            );
+                        }
+
+                        for index in method.bootstrap_arguments.iter().copied() {
+                            let is_loadable = matches!(
+                                self.entry(index),
+                                Some(
+                                    ConstantPoolInfo::Integer { .. }
+                                        | ConstantPoolInfo::Float { .. }
+                                        | ConstantPoolInfo::Long { .. }
+                                        | ConstantPoolInfo::Double { .. }
+                                        | ConstantPoolInfo::Class { .. }
+                                        | ConstantPoolInfo::String { .. }
+                                        | ConstantPoolInfo::MethodHandle { .. }
+                                        | ConstantPoolInfo::MethodType { .. }
+                                        | ConstantPoolInfo::Dynamic { .. }
+                                )
+                            );
+
+                            if !is_loadable {
+                                return self.error(
+                                    offset,
+                                    format!("invalid constant pool index {index} for a bootstrap argument (loadable constant expected)"),
+                                );
+                            }
+                        }
+                    }
+
+                    bootstrap_method_count = bootstrap_methods.bootstrap_methods.len();
+                }
+                "Record" => {
+                    let record: Record = self.parse_attribute(attribute, offset, "Record")?;
+
+                    for component in record.components.iter() {
+                        let name =
+                            self.utf8(offset, component.name_index, "a record component name")?;
+                        let descriptor = self.utf8(
+                            offset,
+                            component.descriptor_index,
+                            "a record component descriptor",
+                        )?;
+
+                        if !is_unqualified_name(name) || !is_field_descriptor(descriptor) {
+                            return self.error(
+                                offset,
+                                format!("invalid record component {name} of type {descriptor}"),
+                            );
+                        }
+
+                        for attribute in component.attributes.iter() {
+                            self.utf8(offset, attribute.attribute_name_index, "an attribute name")?;
+                        }
+                    }
+                }
+                "Signature" => self.check_signature(attribute, offset)?,
+                _ => (),
+            }
+
+            offset += attribute.size();
+        }
+
+        for (index, constant) in self.class_file.constant_pool.iter().enumerate() {
+            if let ConstantPoolInfo::Dynamic {
+                bootstrap_method_attr_index,
+                ..
+            }
+            | ConstantPoolInfo::DynamicInvoke {
+                bootstrap_method_attr_index,
+                ..
+            } = constant
+                && *bootstrap_method_attr_index as usize >= bootstrap_method_count
+            {
+                return self.error(
+                    self.offsets[index],
+                    format!(
+                        "invalid bootstrap method index {bootstrap_method_attr_index} ({bootstrap_method_count} bootstrap methods)"
+                    ),
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The name of an attribute, checking that the attributes which must be unique are
+    fn attribute_name(
+        &self,
+        attribute: &AttributeInfo,
+        offset: u64,
+        unique: &mut HashSet<&'a str>,
+    ) -> Result<&'a str, ClassFormatError> {
+        let name = self.utf8(offset, attribute.attribute_name_index, "an attribute name")?;
+
+        if UNIQUE_ATTRIBUTES.contains(&name) && !unique.insert(name) {
+            return self.error(offset, format!("multiple {name} attributes"));
+        }
+
+        Ok(name)
+    }
+
+    /// Parses a predefined attribute, which must take exactly its length
+    fn parse_attribute<T>(
+        &self,
+        attribute: &AttributeInfo,
+        offset: u64,
+        name: &str,
+    ) -> Result<T, ClassFormatError>
+    where
+        T: for<'b> BinRead<Args<'b> = ()>,
+    {
+        let mut reader = Cursor::new(&attribute.info);
+
+        match T::read_be(&mut reader) {
+            Ok(v) if reader.position() == attribute.info.len() as u64 => Ok(v),
+            Ok(_) => self.error(
+                offset,
+                format!(
+                    "{name} attribute shorter than its length of {}",
+                    attribute.info.len()
+                ),
+            ),
+            Err(_) => self.error(offset, format!("truncated or malformed {name} attribute")),
+        }
+    }
+}
+
+/// Whether a name is a valid unqualified name (JVMS §4.2.2)
+fn is_unqualified_name(name: &str) -> bool {
+    !name.is_empty() && !name.contains(['.', ';', '[', '/'])
+}
+
+/// Whether a name is a valid binary name of a class or interface, in its internal form
+/// (JVMS §4.2.1)
+fn is_binary_name(name: &str) -> bool {
+    name.split('/').all(is_unqualified_name)
+}
+
+/// Whether a name is a valid name for a method other than the initialization methods
+/// (JVMS §4.2.2)
+fn is_method_name(name: &str) -> bool {
+    is_unqualified_name(name) && !name.contains(['<', '>'])
+}
+
+/// The length of the field descriptor (JVMS §4.3.2) at the start of a string, if valid
+fn field_descriptor_length(s: &str) -> Option<usize> {
+    let dimensions = s.bytes().take_while(|b| *b == b'[').count();
+
+    if dimensions > 255 {
+        return None;
+    }
+
+    let element_type = &s[dimensions..];
+
+    let length = match element_type.bytes().next()? {
+        b'B' | b'C' | b'D' | b'F' | b'I' | b'J' | b'S' | b'Z' => 1,
+        b'L' => {
+            let end = element_type.find(';')?;
+
+            if !is_binary_name(&element_type[1..end]) {
+                return None;
+            }
+
+            end + 1
+        }
+        _ => return None,
+    };
+
+    Some(dimensions + length)
+}
+
+fn is_field_descriptor(s: &str) -> bool {
+    field_descriptor_length(s) == Some(s.len())
+}
+
+/// The number of local variable slots taken by the parameters of a method descriptor
+/// (JVMS §4.3.3), if valid
+fn method_descriptor_slots(s: &str) -> Option<usize> {
+    let mut rest = s.strip_prefix('(')?;
+    let mut slots = 0;
+
+    while !rest.starts_with(')') {
+        let length = field_descriptor_length(rest)?;
+
+        slots += if matches!(&rest[..length], "J" | "D") {
+            2
+        } else {
+            1
+        };
+        rest = &rest[length..];
+    }
+
+    let return_type = &rest[1..];
+
+    (return_type == "V" || is_field_descriptor(return_type)).then_some(slots)
+}
+
+fn is_method_descriptor(s: &str) -> bool {
+    method_descriptor_slots(s).is_some()
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// The Code attribute of a method assembled by hand
+    pub(crate) struct CodeBytes<'a> {
+        pub max_stack: u16,
+        pub max_locals: u16,
+        pub code: &'a [u8],
+        /// The entries of the exception table: start_pc, end_pc, handler_pc and catch_type
+        pub exception_table: &'a [[u16; 4]],
+        /// The entries of the StackMapTable, which is left out if there are none
+        pub stack_map: &'a [&'a [u8]],
+    }
+
+    /// A class file assembled by hand, whose superclass is java/lang/Object unless changed with
+    /// `extends` (none for java/lang/Object itself)
+    pub(crate) struct ClassBytes {
+        pub major_version: u16,
+        pub access_flags: u16,
+        constant_pool: Vec<u8>,
+        constant_pool_count: u16,
+        utf8s: HashMap<String, u16>,
+        this_class: u16,
+        super_class: u16,
+        fields: Vec<u8>,
+        fields_count: u16,
+        methods: Vec<u8>,
+        methods_count: u16,
+        /// The entries of the BootstrapMethods attribute, which is left out if there are none
+        bootstrap_methods: Vec<u8>,
+        bootstrap_methods_count: u16,
+    }
+
+    impl ClassBytes {
+        pub(crate) fn new(name: &str, major_version: u16) -> Self {
+            let mut class = Self {
+                major_version,
+                // ACC_PUBLIC and ACC_SUPER
+                access_flags: 0x0021,
+                constant_pool: vec![],
+                constant_pool_count: 1,
+                utf8s: HashMap::new(),
+                this_class: 0,
+                super_class: 0,
+                fields: vec![],
+                fields_count: 0,
+                methods: vec![],
+                methods_count: 0,
+                bootstrap_methods: vec![],
+                bootstrap_methods_count: 0,
+            };
+
+            class.this_class = class.class(name);
+
+            if name != "java/lang/Object" {
+                class.super_class = class.class("java/lang/Object");
+            }
+
+            class
+        }
+
+        /// Appends a constant pool entry, returning its index
+        pub(crate) fn constant(&mut self, bytes: &[u8]) -> u16 {
+            self.constant_pool.extend_from_slice(bytes);
+            self.constant_pool_count += 1;
+            self.constant_pool_count - 1
+        }
+
+        pub(crate) fn utf8(&mut self, value: &str) -> u16 {
+            if let Some(index) = self.utf8s.get(value) {
+                return *index;
+            }
+
+            let mut bytes = vec![1];
+            bytes.extend_from_slice(&(value.len() as u16).to_be_bytes());
+            bytes.extend_from_slice(value.as_bytes());
+
+            let index = self.constant(&bytes);
+            self.utf8s.insert(value.to_string(), index);
+            index
+        }
+
+        pub(crate) fn class(&mut self, name: &str) -> u16 {
+            let [high, low] = self.utf8(name).to_be_bytes();
+
+            self.constant(&[7, high, low])
+        }
+
+        /// A Fieldref to a field of the given class
+        pub(crate) fn field_ref(&mut self, class: &str, name: &str, descriptor: &str) -> u16 {
+            self.member_ref(9, class, name, descriptor)
+        }
+
+        fn member_ref(&mut self, tag: u8, class: &str, name: &str, descriptor: &str) -> u16 {
+            let [class_high, class_low] = self.class(class).to_be_bytes();
+            let [name_high, name_low] = self.utf8(name).to_be_bytes();
+            let [descriptor_high, descriptor_low] = self.utf8(descriptor).to_be_bytes();
+            let [nat_high, nat_low] = self
+                .constant(&[12, name_high, name_low, descriptor_high, descriptor_low])
+                .to_be_bytes();
+
+            self.constant(&[tag, class_high, class_low, nat_high, nat_low])
+        }
+
+        /// A Methodref to a method of the given class
+        pub(crate) fn method_ref(&mut self, class: &str, name: &str, descriptor: &str) -> u16 {
+            self.member_ref(10, class, name, descriptor)
+        }
+
+        /// A MethodHandle of the given kind (like 6 for REF_invokeStatic) to a member reference
+        pub(crate) fn method_handle(&mut self, kind: u8, reference: u16) -> u16 {
+            let [high, low] = reference.to_be_bytes();
+
+            self.constant(&[15, kind, high, low])
+        }
+
+        pub(crate) fn method_type(&mut self, descriptor: &str) -> u16 {
+            let [high, low] = self.utf8(descriptor).to_be_bytes();
+
+            self.constant(&[16, high, low])
+        }
+
+        /// An InvokeDynamic call site, bootstrapped by the given method handle with the given
+        /// static arguments
+        pub(crate) fn invoke_dynamic(
+            &mut self,
+            bootstrap_method: u16,
+            arguments: &[u16],
+            name: &str,
+            descriptor: &str,
+        ) -> u16 {
+            for v in [bootstrap_method, arguments.len() as u16]
+                .iter()
+                .chain(arguments)
+            {
+                self.bootstrap_methods.extend_from_slice(&v.to_be_bytes());
+            }
+
+            self.utf8("BootstrapMethods");
+            self.bootstrap_methods_count += 1;
+
+            let [index_high, index_low] = (self.bootstrap_methods_count - 1).to_be_bytes();
+            let [name_high, name_low] = self.utf8(name).to_be_bytes();
+            let [descriptor_high, descriptor_low] = self.utf8(descriptor).to_be_bytes();
+            let [nat_high, nat_low] = self
+                .constant(&[12, name_high, name_low, descriptor_high, descriptor_low])
+                .to_be_bytes();
+
+            self.constant(&[18, index_high, index_low, nat_high, nat_low])
+        }
+
+        /// An InterfaceMethodref to a method of the given interface
+        pub(crate) fn interface_method_ref(
+            &mut self,
+            interface: &str,
+            name: &str,
+            descriptor: &str,
+        ) -> u16 {
+            self.member_ref(11, interface, name, descriptor)
+        }
+
+        /// Makes the class extend the given one instead of java/lang/Object
+        pub(crate) fn extends(&mut self, super_class: &str) {
+            self.super_class = self.class(super_class);
+        }
+
+        /// The offset of the access flags, right after the constant pool
+        pub(crate) fn constant_pool_end(&self) -> u64 {
+            10 + self.constant_pool.len() as u64
+        }
+
+        pub(crate) fn field(&mut self, access_flags: u16, name: &str, descriptor: &str) {
+            let (name, descriptor) = (self.utf8(name), self.utf8(descriptor));
+
+            for v in [access_flags, name, descriptor, 0] {
+                self.fields.extend_from_slice(&v.to_be_bytes());
+            }
+
+            self.fields_count += 1;
+        }
+
+        pub(crate) fn method(
+            &mut self,
+            access_flags: u16,
+            name: &str,
+            descriptor: &str,
+            code: Option<CodeBytes>,
+        ) {
+            let (name, descriptor) = (self.utf8(name), self.utf8(descriptor));
+
+            for v in [access_flags, name, descriptor, code.is_some() as u16] {
+                self.methods.extend_from_slice(&v.to_be_bytes());
+            }
+
+            if let Some(code) = code {
+                let mut info = vec![];
+
+                info.extend_from_slice(&code.max_stack.to_be_bytes());
+                info.extend_from_slice(&code.max_locals.to_be_bytes());
+                info.extend_from_slice(&(code.code.len() as u32).to_be_bytes());
+                info.extend_from_slice(code.code);
+                info.extend_from_slice(&(code.exception_table.len() as u16).to_be_bytes());
+
+                for v in code.exception_table.iter().flatten() {
+                    info.extend_from_slice(&v.to_be_bytes());
+                }
+
+                if code.stack_map.is_empty() {
+                    info.extend_from_slice(&0u16.to_be_bytes());
+                } else {
+                    let mut table = (code.stack_map.len() as u16).to_be_bytes().to_vec();
+                    table.extend(code.stack_map.concat());
+
+                    info.extend_from_slice(&1u16.to_be_bytes());
+                    self.attribute(&mut info, "StackMapTable", &table);
+                }
+
+                let mut methods = std::mem::take(&mut self.methods);
+                self.attribute(&mut methods, "Code", &info);
+                self.methods = methods;
+            }
+
+            self.methods_count += 1;
+        }
+
+        fn attribute(&mut self, bytes: &mut Vec<u8>, name: &str, info: &[u8]) {
+            bytes.extend_from_slice(&self.utf8(name).to_be_bytes());
+            bytes.extend_from_slice(&(info.len() as u32).to_be_bytes());
+            bytes.extend_from_slice(info);
+        }
+
+        pub(crate) fn bytes(&self) -> Vec<u8> {
+            let mut bytes = vec![0xca, 0xfe, 0xba, 0xbe, 0, 0];
+
+            bytes.extend_from_slice(&self.major_version.to_be_bytes());
+            bytes.extend_from_slice(&self.constant_pool_count.to_be_bytes());
+            bytes.extend_from_slice(&self.constant_pool);
+
+            // No interfaces
+            for v in [self.access_flags, self.this_class, self.super_class, 0] {
+                bytes.extend_from_slice(&v.to_be_bytes());
+            }
+
+            bytes.extend_from_slice(&self.fields_count.to_be_bytes());
+            bytes.extend_from_slice(&self.fields);
+            bytes.extend_from_slice(&self.methods_count.to_be_bytes());
+            bytes.extend_from_slice(&self.methods);
+
+            if self.bootstrap_methods_count == 0 {
+                bytes.extend_from_slice(&0u16.to_be_bytes());
+            } else {
+                let length = self.bootstrap_methods.len() as u32 + 2;
+
+                bytes.extend_from_slice(&1u16.to_be_bytes());
+                bytes.extend_from_slice(&self.utf8s["BootstrapMethods"].to_be_bytes());
+                bytes.extend_from_slice(&length.to_be_bytes());
+                bytes.extend_from_slice(&self.bootstrap_methods_count.to_be_bytes());
+                bytes.extend_from_slice(&self.bootstrap_methods);
+            }
+
+            bytes
+        }
+    }
+
+    fn check(class: &ClassBytes) -> Result<(), ClassFormatError> {
+        check_format(&parse_class_file("Test", &class.bytes())?)
+    }
+
+    /// A static method returning 0
+    fn return_zero() -> Option<CodeBytes<'static>> {
+        Some(CodeBytes {
+            max_stack: 1,
+            max_locals: 0,
+            // iconst_0, ireturn
+            code: &[0x03, 0xac],
+            exception_table: &[],
+            stack_map: &[],
+        })
+    }
+
+    #[test]
+    fn valid_class_is_accepted() {
+        let mut class = ClassBytes::new("Test", 61);
+
+        class.field(0x0002, "x", "I");
+        class.field(0x0002, "x", "J");
+        class.method(0x0009, "zero", "()I", return_zero());
+        class.method(0x0401, "abstractMethod", "()V", None);
+
+        check(&class).unwrap();
+        check(&ClassBytes::new("java/lang/Object", 61)).unwrap();
+    }
+
+    #[test]
+    fn unknown_constant_pool_tag_is_rejected() {
+        let mut class = ClassBytes::new("Test", 61);
+        let offset = class.constant_pool_end();
+
+        // Tag 2 is not used
+        class.constant(&[2, 0, 0]);
+
+        let err = check(&class).unwrap_err();
+
+        assert_eq!(err.offset, offset);
+        assert_eq!(err.reason, "unknown tag");
+    }
+
+    #[test]
+    fn constant_pool_index_of_the_wrong_tag_is_rejected() {
+        let mut class = ClassBytes::new("Test", 61);
+        let value = class.utf8("value");
+        let [high, low] = value.to_be_bytes();
+        class.constant(&[8, high, low]);
+
+        check(&class).unwrap();
+
+        let offset = class.constant_pool_end();
+        let [high, low] = class.this_class.to_be_bytes();
+        class.constant(&[8, high, low]);
+
+        let err = check(&class).unwrap_err();
+
+        assert_eq!(err.offset, offset);
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "Test: invalid constant pool index {} for a string (Utf8 expected) \
+                 (at offset {offset} of the class file)",
+                class.this_class
+            )
+        );
+
+        let mut class = ClassBytes::new("Test", 61);
+        class.constant(&[8, 0, 99]);
+
+        assert_eq!(
+            check(&class).unwrap_err().reason,
+            "invalid constant pool index 99 for a string (Utf8 expected)"
+        );
+    }
+
+    #[test]
+    fn duplicate_field_is_rejected() {
+        let mut class = ClassBytes::new("com/example/Test", 61);
+
+        class.field(0x0002, "x", "I");
+        class.field(0x0002, "x", "I");
+
+        // After the flags, this_class, super_class, interfaces, the fields count and the first
+        // field
+        let offset = class.constant_pool_end() + 8 + 2 + 8;
+
+        assert_eq!(
+            check(&class).unwrap_err().to_string(),
+            format!(
+                "com.example.Test.x: duplicate field name and descriptor \
+                 (at offset {offset} of the class file)"
+            )
+        );
+    }
+
+    #[test]
+    fn duplicate_method_is_rejected() {
+        let mut class = ClassBytes::new("Test", 61);
+
+        class.method(0x0009, "zero", "()I", return_zero());
+        class.method(0x0109, "zero", "()J", None);
+        class.method(0x0009, "zero", "()I", return_zero());
+
+        let err = check(&class).unwrap_err();
+
+        assert_eq!(err.member.as_deref(), Some("zero()I"));
+        assert_eq!(err.reason, "duplicate method name and descriptor");
+    }
+
+    #[test]
+    fn illegal_class_modifiers_are_rejected() {
+        let mut class = ClassBytes::new("Test", 61);
+
+        // ACC_PUBLIC, ACC_FINAL and ACC_SUPER
+        class.access_flags = 0x0031;
+        check(&class).unwrap();
+
+        // ACC_FINAL and ACC_ABSTRACT
+        class.access_flags = 0x0431;
+        let err = check(&class).unwrap_err();
+
+        assert_eq!(err.member, None);
+        assert_eq!(err.offset, class.constant_pool_end());
+        assert!(err.reason.starts_with("illegal class modifiers"));
+    }
+
+    #[test]
+    fn illegal_member_modifiers_are_rejected() {
+        let mut class = ClassBytes::new("Test", 61);
+        // ACC_PUBLIC and ACC_PRIVATE
+        class.field(0x0003, "x", "I");
+
+        let err = check(&class).unwrap_err();
+
+        assert_eq!(err.member.as_deref(), Some("x"));
+        assert!(err.reason.starts_with("illegal field modifiers"));
+
+        let mut class = ClassBytes::new("Test", 61);
+        // ACC_PRIVATE and ACC_ABSTRACT
+        class.method(0x0402, "run", "()V", None);
+
+        let err = check(&class).unwrap_err();
+
+        assert_eq!(err.member.as_deref(), Some("run()V"));
+        assert!(err.reason.starts_with("illegal method modifiers"));
+    }
+
+    #[test]
+    fn method_without_code_is_rejected() {
+        let mut class = ClassBytes::new("Test", 61);
+        class.method(0x0009, "zero", "()I", None);
+
+        assert_eq!(
+            check(&class).unwrap_err().reason,
+            "no Code attribute in a method neither native nor abstract"
+        );
+    }
+
+    #[test]
+    fn truncated_class_file_is_rejected() {
+        let bytes = ClassBytes::new("Test", 61).bytes();
+        let err = parse_class_file("Test", &bytes[..bytes.len() - 1]).unwrap_err();
+
+        assert_eq!(err.offset, bytes.len() as u64 - 1);
+        assert_eq!(err.reason, "truncated class file");
+
+        let mut bytes = bytes;
+        bytes.push(0);
+        let err = parse_class_file("Test", &bytes).unwrap_err();
+
+        assert_eq!(err.reason, "extra bytes at the end of the class file");
+    }
+}
@@ -9,12 +9,13 @@ use either::Either;
 use log::{debug, trace, warn};
 use serde::Serialize;
 
+use super::attributes::BootstrapMethodsEntry;
 use super::constant_pool::{
     ConstantClass, ConstantFieldref, ConstantInterfaceMethodref, ConstantJvmUtf8,
     ConstantMethodHandle, ConstantMethodref, DynamicInvoke, LoadableJvmConstant,
 };
 use super::parser::{self, ClassAccessFlags, ClassFile, ConstantPoolInfo, MethodKind};
-use super::{JvmUnitField, JvmUnitMethod, get_class, get_name_and_type, get_string};
+use super::{JvmUnitField, JvmUnitMethod, check_format, get_class, get_name_and_type, get_string};
 
 use crate::types::{JvmMethodDescriptor, JvmTypeDescriptor};
 
@@ -35,6 +36,8 @@ pub struct JvmUnit {
     pub field_refs: HashMap<u16, ConstantFieldref>,
     pub method_refs: HashMap<u16, ConstantMethodref>,
     pub interface_method_refs: HashMap<u16, ConstantInterfaceMethodref>,
+    pub dynamic_invokes: HashMap<u16, DynamicInvoke>,
+    pub bootstrap_methods: Vec<BootstrapMethodsEntry>,
 }
 
 #[derive(Debug, Clone, Serialize)]
@@ -83,7 +86,10 @@ pub enum JvmVisibility {
 }
 
 impl JvmUnit {
+    /// Creates the unit of a class file, which is checked first (JVMS §4.8)
     pub fn from_class_file(class_file: ClassFile) -> Result<Self> {
+        check_format(&class_file)?;
+
         let minor_version = class_file.minor_version;
         let major_version = class_file.major_version;
 
@@ -460,6 +466,7 @@ impl JvmUnit {
         }
 
         let mut is_deprecated = false;
+        let mut bootstrap_methods = vec![];
 
         for attribute in class_file.attributes.iter() {
             let attribute_name = get_string(&jvm_strings, &attribute.attribute_name_index)?;
@@ -487,6 +494,17 @@ impl JvmUnit {
                             .collect::<Result<Vec<_>>>()?,
                     })
                 }
+                "BootstrapMethods" => {
+                    let attribute = parser::attributes::BootstrapMethods::read_be(
+                        &mut Cursor::new(&attribute.info),
+                    )?;
+
+                    bootstrap_methods = attribute
+                        .bootstrap_methods
+                        .into_iter()
+                        .map(|v| BootstrapMethodsEntry::try_from_parser(&loadable_constant_pool, v))
+                        .collect::<Result<Vec<_>>>()?;
+                }
                 "Deprecated" => {
                     is_deprecated = true;
                 }
@@ -515,6 +533,8 @@ impl JvmUnit {
             field_refs,
             method_refs,
             interface_method_refs,
+            dynamic_invokes,
+            bootstrap_methods,
         })
     }
 }
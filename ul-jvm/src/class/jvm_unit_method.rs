@@ -100,8 +100,6 @@ impl JvmUnitMethod {
                     let code_attr =
                         parser::attributes::Code::read_be(&mut Cursor::new(&attribute.info))?;
 
-                    // FIXME: error if multiple found
-
                     let mut line_number_table = vec![];
                     let mut local_variable_table = vec![];
                     let mut local_variable_type_table = vec![];
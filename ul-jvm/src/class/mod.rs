@@ -1,11 +1,13 @@
 pub mod attributes;
 pub mod constant_pool;
+mod format_check;
 mod jvm_unit;
 mod jvm_unit_field;
 mod jvm_unit_method;
 mod jvm_unit_util;
 pub mod parser;
 
+pub use format_check::*;
 pub use jvm_unit::*;
 pub use jvm_unit_field::*;
 pub use jvm_unit_method::*;
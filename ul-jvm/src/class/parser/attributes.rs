@@ -192,9 +192,9 @@ pub enum VerificationTypeInfo {
     #[br(magic = 0x02u8)]
     Float,
     #[br(magic = 0x03u8)]
-    Long,
-    #[br(magic = 0x04u8)]
     Double,
+    #[br(magic = 0x04u8)]
+    Long,
     #[br(magic = 0x05u8)]
     Null,
     #[br(magic = 0x06u8)]
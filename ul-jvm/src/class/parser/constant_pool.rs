@@ -84,10 +84,35 @@ pub enum ConstantPoolInfo {
     },
     #[br(magic = 19u8)]
     Module { name_index: u16 },
-    #[br(magic = 19u8)]
+    #[br(magic = 20u8)]
     Package { name_index: u16 },
 }
 
+impl ConstantPoolInfo {
+    /// The number of bytes of the entry in the class file, tag included
+    pub fn size(&self) -> u64 {
+        match self {
+            Self::Ignored => 0,
+            Self::Class { .. }
+            | Self::String { .. }
+            | Self::MethodType { .. }
+            | Self::Module { .. }
+            | Self::Package { .. } => 3,
+            Self::MethodHandle { .. } => 4,
+            Self::Fieldref { .. }
+            | Self::Methodref { .. }
+            | Self::InterfaceMethodref { .. }
+            | Self::Integer { .. }
+            | Self::Float { .. }
+            | Self::NameAndType { .. }
+            | Self::Dynamic { .. }
+            | Self::DynamicInvoke { .. } => 5,
+            Self::Long { .. } | Self::Double { .. } => 9,
+            Self::Utf8 { length, .. } => 3 + *length as u64,
+        }
+    }
+}
+
 #[derive(Debug, Clone, BinRead, Serialize)]
 pub struct AttributeInfo {
     pub attribute_name_index: u16,
@@ -96,6 +121,13 @@ pub struct AttributeInfo {
     pub info: Vec<u8>,
 }
 
+impl AttributeInfo {
+    /// The number of bytes of the attribute in the class file, header included
+    pub fn size(&self) -> u64 {
+        6 + self.info.len() as u64
+    }
+}
+
 #[derive(Debug, Clone, BinRead, Serialize)]
 pub enum MethodKind {
     #[br(magic = 1u8)]
@@ -6,7 +6,7 @@ use serde::Serialize;
 #[derive(Debug, Clone, BinRead)]
 #[br(import(length: u16))]
 pub struct ModifiedUtf8String(
-    #[br(count = length, assert(is_slice_valid(&self_0), "invalid string"))] Vec<u8>,
+    #[br(count = length, assert(is_slice_valid(&self_0), "invalid modified UTF-8 string"))] Vec<u8>,
 );
 
 impl ModifiedUtf8String {
@@ -23,13 +23,13 @@ impl ModifiedUtf8String {
                 normalized_utf8.push(b);
                 slice = &slice[1..];
             } else if b >> 5 == 0b110 {
-                if b == 0b11000000 && slice[1] == 0b11000000 {
+                if b == 0b11000000 && slice[1] == 0b10000000 {
                     normalized_utf8.push(0);
                 } else {
                     normalized_utf8.extend(&slice[..2]);
                 }
                 slice = &slice[2..];
-            } else if b == SUGGORATE_BYTE {
+            } else if is_surrogate_pair(slice) {
                 let v = slice[1] as u32;
                 let w = slice[2] as u32;
                 let y = slice[4] as u32;
@@ -55,7 +55,9 @@ impl ModifiedUtf8String {
             }
         }
 
-        String::from_utf8(normalized_utf8).unwrap()
+        // Unpaired surrogates are valid in modified UTF-8 but not in UTF-8
+        String::from_utf8(normalized_utf8)
+            .unwrap_or_else(|err| String::from_utf8_lossy(err.as_bytes()).into_owned())
     }
 }
 
@@ -76,10 +78,21 @@ impl AsRef<[u8]> for ModifiedUtf8String {
 
 const SUGGORATE_BYTE: u8 = 0b11101101;
 
+/// Whether the bytes start with a supplementary character, encoded as a high surrogate followed by
+/// a low surrogate (both starting with SUGGORATE_BYTE)
+fn is_surrogate_pair(slice: &[u8]) -> bool {
+    slice.len() >= 6
+        && slice[0] == SUGGORATE_BYTE
+        && slice[1] >> 4 == 0b1010
+        && slice[3] == SUGGORATE_BYTE
+        && slice[4] >> 4 == 0b1011
+}
+
 fn is_slice_valid(slice: &[u8]) -> bool {
     let mut remaining_for_cp = 0usize;
-    let mut supplementary = false;
 
+    // Surrogates are encoded as any other 3 bytes character, so supplementary characters need no
+    // special treatment here
     for (i, b) in slice.iter().copied().enumerate() {
         if b >= 0xF0 || b == 0 {
             error!("Invalid byte at {i} (failed at `b >= 0xF0 || b == 0`)");
@@ -87,29 +100,7 @@ fn is_slice_valid(slice: &[u8]) -> bool {
         }
 
         if remaining_for_cp > 0 {
-            if supplementary {
-                if remaining_for_cp == 5 && b >> 4 != 0b1010 {
-                    error!(
-                        "Invalid surrogate byte at {i} (failed at `remaining_for_cp == 5 && b >> 4 != 0b1010`)"
-                    );
-                    return false;
-                } else if (remaining_for_cp == 4 || remaining_for_cp == 1) && b >> 6 != 0b10 {
-                    error!(
-                        "Invalid surrogate byte at {i} (failed at `(remaining_for_cp == 4 || remaining_for_cp == 1) && b >> 6 != 0b10`)"
-                    );
-                    return false;
-                } else if remaining_for_cp == 3 && b != SUGGORATE_BYTE {
-                    error!(
-                        "Invalid surrogate byte at {i} (failed at `remaining_for_cp == 3 && b != SUGGORATE_BYTE`)"
-                    );
-                    return false;
-                } else if remaining_for_cp == 2 && b >> 4 != 0b1011 {
-                    error!(
-                        "Invalid surrogate byte at {i} (failed at `remaining_for_cp == 2 && b >> 4 != 0b1011`)"
-                    );
-                    return false;
-                }
-            } else if b >> 6 != 0b10 {
+            if b >> 6 != 0b10 {
                 error!("Invalid extended byte at {i} (failed at `b >> 6 != 0b10`)");
                 return false;
             }
@@ -118,12 +109,7 @@ fn is_slice_valid(slice: &[u8]) -> bool {
             continue;
         }
 
-        supplementary = false;
-
-        if b == SUGGORATE_BYTE {
-            supplementary = true;
-            remaining_for_cp = 5;
-        } else if b >> 5 == 0b110 {
+        if b >> 5 == 0b110 {
             remaining_for_cp = 1;
         } else if b >> 4 == 0b1110 {
             remaining_for_cp = 2;
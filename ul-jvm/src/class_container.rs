@@ -1,13 +1,12 @@
 use std::{
-    collections::HashSet,
+    collections::{HashMap, HashSet},
     fs::OpenOptions,
     io::{Cursor, Read},
     path::{Path, PathBuf},
     sync::Arc,
 };
 
-use cached::proc_macro::cached;
-use log::{debug, trace};
+use log::{debug, trace, warn};
 use zip::ZipArchive;
 
 #[derive(Debug, Clone, PartialEq)]
@@ -19,7 +18,13 @@ struct ClassContainerInner {
     units: HashSet<String>,
     other_files: HashSet<String>,
     is_jmod: bool,
-    // main_class: Option<String>,
+    manifest: Option<Manifest>,
+}
+
+/// The main section of a jar manifest (META-INF/MANIFEST.MF)
+#[derive(Debug, Default, PartialEq)]
+pub struct Manifest {
+    attributes: HashMap<String, String>,
 }
 
 impl ClassContainer {
@@ -31,7 +36,7 @@ impl ClassContainer {
         let mut units = HashSet::new();
         let mut other_files = HashSet::new();
         let is_jmod = if let Some(ext) = path.extension() {
-            ext.to_string_lossy() == "jmod"
+            ext.eq_ignore_ascii_case("jmod")
         } else {
             false
         };
@@ -54,16 +59,71 @@ impl ClassContainer {
             }
         }
 
+        let manifest = if !is_jmod && other_files.contains(Manifest::PATH) {
+            let mut content = vec![];
+
+            archive.by_name(Manifest::PATH)?.read_to_end(&mut content)?;
+
+            Some(Manifest::parse(&String::from_utf8_lossy(&content)))
+        } else {
+            None
+        };
+
         Ok(Self(Arc::new(ClassContainerInner {
             original_path: path.to_path_buf(),
             units,
             other_files,
             is_jmod,
+            manifest,
         })))
     }
 
-    pub fn has_unit(&self, unit_name: &str) -> bool {
-        self.0.units.contains(unit_name)
+    pub fn path(&self) -> &Path {
+        &self.0.original_path
+    }
+
+    pub fn manifest(&self) -> Option<&Manifest> {
+        self.0.manifest.as_ref()
+    }
+
+    /// The binary name of the class to start with `-jar`, from the manifest
+    pub fn main_class(&self) -> Option<String> {
+        self.manifest()?
+            .get("Main-Class")
+            .map(|name| name.replace('.', "/"))
+    }
+
+    /// The other jars and directories referenced by the manifest, resolved against the
+    /// directory of this container
+    pub fn class_path(&self) -> Vec<PathBuf> {
+        let Some(class_path) = self.manifest().and_then(|m| m.get("Class-Path")) else {
+            return vec![];
+        };
+
+        let base = self.0.original_path.parent().unwrap_or(Path::new(""));
+
+        class_path
+            .split_ascii_whitespace()
+            .filter_map(|url| {
+                let path = url.strip_prefix("file:").unwrap_or(url);
+
+                // Class-Path entries are relative URLs, anything else cannot be a local path
+                if path.contains("://") {
+                    warn!(
+                        "unsupported Class-Path entry {url} in {:?}",
+                        self.0.original_path
+                    );
+                    return None;
+                }
+
+                Some(base.join(path))
+            })
+            .collect()
+    }
+
+    /// The binary names of the units in this container
+    pub fn units(&self) -> impl Iterator<Item = &String> {
+        self.0.units.iter()
     }
 
     pub fn read_class_file(&self, unit_name: &str) -> anyhow::Result<Cursor<Vec<u8>>> {
@@ -84,7 +144,159 @@ impl ClassContainer {
     }
 }
 
-#[cached(result = true, key = "String", convert = r##"{ path.to_string() }"##)]
-pub fn read_container(path: &str) -> anyhow::Result<ClassContainer> {
-    ClassContainer::new(Path::new(path))
+impl Manifest {
+    pub const PATH: &str = "META-INF/MANIFEST.MF";
+
+    /// Parses the main section of a manifest. Lines starting with a space continue the value of
+    /// the previous one, and the main section ends at the first empty line.
+    pub fn parse(content: &str) -> Self {
+        let mut attributes = HashMap::new();
+        let mut current: Option<(String, String)> = None;
+
+        for line in content.split("\r\n").flat_map(|l| l.split(['\r', '\n'])) {
+            if let Some(continuation) = line.strip_prefix(' ') {
+                match current.as_mut() {
+                    Some((_, value)) => value.push_str(continuation),
+                    None => warn!("manifest continuation line without attribute: {line:?}"),
+                }
+
+                continue;
+            }
+
+            if let Some((name, value)) = current.take() {
+                attributes.insert(name, value);
+            }
+
+            if line.is_empty() {
+                if attributes.is_empty() {
+                    continue;
+                }
+
+                break;
+            }
+
+            match line.split_once(':') {
+                Some((name, value)) => {
+                    current = Some((
+                        name.to_string(),
+                        value.strip_prefix(' ').unwrap_or(value).to_string(),
+                    ))
+                }
+                None => warn!("invalid manifest line: {line:?}"),
+            }
+        }
+
+        if let Some((name, value)) = current {
+            attributes.insert(name, value);
+        }
+
+        Self { attributes }
+    }
+
+    /// Gets a main attribute, whose names are case-insensitive
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::{fs::File, io::Write};
+
+    use zip::{ZipWriter, write::SimpleFileOptions};
+
+    use super::*;
+
+    /// Creates an empty directory for a test, in the temporary directory
+    pub(crate) fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("ul-jvm-{}-{name}", std::process::id()));
+
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+
+        dir
+    }
+
+    /// Writes a jar holding the given files, by path in the jar
+    pub(crate) fn write_jar(path: &Path, files: &[(&str, &str)]) {
+        let mut jar = ZipWriter::new(File::create(path).unwrap());
+
+        for (name, content) in files {
+            jar.start_file(*name, SimpleFileOptions::default()).unwrap();
+            jar.write_all(content.as_bytes()).unwrap();
+        }
+
+        jar.finish().unwrap();
+    }
+
+    #[test]
+    fn manifest_continuation_lines_are_joined() {
+        let manifest = Manifest::parse(
+            "Manifest-Version: 1.0\nClass-Path: lib/a.jar li\n b/b.jar\n  lib/c.jar\n",
+        );
+
+        assert_eq!(
+            manifest.get("Class-Path"),
+            Some("lib/a.jar lib/b.jar lib/c.jar")
+        );
+    }
+
+    #[test]
+    fn manifest_crlf_line_endings_are_accepted() {
+        let manifest = Manifest::parse(
+            "Manifest-Version: 1.0\r\nMain-Class: com.example.Ma\r\n in\r\n\r\nName: x\r\n",
+        );
+
+        assert_eq!(manifest.get("manifest-version"), Some("1.0"));
+        assert_eq!(manifest.get("Main-Class"), Some("com.example.Main"));
+        // Only the main section is read
+        assert_eq!(manifest.get("Name"), None);
+    }
+
+    #[test]
+    fn manifest_class_path_is_relative_to_the_jar() {
+        let dir = test_dir("manifest-class-path");
+        let jar_path = dir.join("app.jar");
+
+        write_jar(
+            &jar_path,
+            &[
+                (
+                    Manifest::PATH,
+                    "Manifest-Version: 1.0\nMain-Class: com.example.Main\n\
+                    Class-Path: lib/a.jar file:classes/ http://example.com/b.jar\n",
+                ),
+                ("com/example/Main.class", ""),
+            ],
+        );
+
+        let jar = ClassContainer::new(&jar_path).unwrap();
+
+        assert_eq!(jar.main_class().as_deref(), Some("com/example/Main"));
+        assert_eq!(
+            jar.class_path(),
+            vec![dir.join("lib/a.jar"), dir.join("classes/")]
+        );
+        assert_eq!(
+            jar.units().collect::<Vec<_>>(),
+            vec![&String::from("com/example/Main")]
+        );
+    }
+
+    #[test]
+    fn jar_without_main_class_has_none() {
+        let dir = test_dir("no-main-class");
+        let jar_path = dir.join("lib.jar");
+
+        write_jar(&jar_path, &[(Manifest::PATH, "Manifest-Version: 1.0\n")]);
+
+        let jar = ClassContainer::new(&jar_path).unwrap();
+
+        assert!(jar.manifest().is_some());
+        assert_eq!(jar.main_class(), None);
+        assert!(jar.class_path().is_empty());
+    }
 }
@@ -0,0 +1,406 @@
+use std::{
+    collections::HashMap,
+    ffi::OsStr,
+    fmt::Display,
+    io::{Cursor, ErrorKind},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, bail};
+use log::{debug, trace, warn};
+
+use crate::class_container::ClassContainer;
+
+/// An ordered list of directories and containers (jars and jmods) to look class files up in,
+/// with an index of the units provided by the containers. Directories are not indexed, their
+/// class files being looked up when needed.
+#[derive(Debug, Default)]
+pub struct ClassPath {
+    entries: Vec<ClassPathEntry>,
+    /// Container providing each unit, by binary name (java/lang/Object). When several containers
+    /// provide the same unit, the first one is used.
+    index: HashMap<String, usize>,
+}
+
+#[derive(Debug)]
+pub enum ClassPathEntry {
+    Directory(PathBuf),
+    Container(ClassContainer),
+}
+
+impl ClassPath {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Parses a class path as given to `-cp` or in `CLASSPATH`: entries separated by the platform
+    /// path separator, where `dir/*` stands for every jar in `dir`
+    pub fn parse(class_path: &str) -> Self {
+        let mut result = Self::new();
+
+        for entry in std::env::split_paths(class_path) {
+            result.push(&entry);
+        }
+
+        result
+    }
+
+    /// Appends an entry, expanding wildcards and indexing the units it provides. Entries which
+    /// cannot be read are skipped.
+    pub fn push(&mut self, path: &Path) {
+        // As with java, an empty entry is the current directory
+        let path = if path.as_os_str().is_empty() {
+            Path::new(".")
+        } else {
+            path
+        };
+
+        if path.file_name() == Some(OsStr::new("*")) {
+            let dir = path.parent().unwrap_or(Path::new("."));
+
+            match list_jars(dir) {
+                Ok(jars) => {
+                    for jar in jars {
+                        self.push_entry(&jar);
+                    }
+                }
+                Err(err) => {
+                    warn!("unable to expand class path entry {path:?}: {err:#}. Skipping...")
+                }
+            }
+
+            return;
+        }
+
+        self.push_entry(path);
+    }
+
+    fn push_entry(&mut self, path: &Path) {
+        if self.entries.iter().any(|e| e.is_same_path(path)) {
+            debug!("class path entry {path:?} already present. Skipping...");
+            return;
+        }
+
+        if path.is_dir() {
+            self.add_entry(ClassPathEntry::Directory(path.to_path_buf()));
+        } else if path.is_file() {
+            match ClassContainer::new(path) {
+                Ok(v) => self.push_container(v),
+                Err(err) => {
+                    warn!("unable to read class path entry {path:?}: {err:#}. Skipping...");
+                }
+            }
+        } else {
+            debug!("class path entry {path:?} does not exist. Skipping...");
+        }
+    }
+
+    /// Appends an already opened container, followed by the entries of the Class-Path attribute
+    /// of its manifest
+    pub fn push_container(&mut self, container: ClassContainer) {
+        if self
+            .entries
+            .iter()
+            .any(|e| e.is_same_path(container.path()))
+        {
+            debug!(
+                "class path entry {:?} already present. Skipping...",
+                container.path()
+            );
+            return;
+        }
+
+        let manifest_class_path = container.class_path();
+
+        self.add_entry(ClassPathEntry::Container(container));
+
+        for path in manifest_class_path {
+            self.push_entry(&path);
+        }
+    }
+
+    fn add_entry(&mut self, entry: ClassPathEntry) {
+        let units = match &entry {
+            ClassPathEntry::Directory(_) => vec![],
+            ClassPathEntry::Container(container) => container.units().cloned().collect(),
+        };
+
+        let entry_index = self.entries.len();
+        let mut shadowed: HashMap<usize, usize> = HashMap::new();
+
+        for unit in units {
+            match self.index.get(&unit) {
+                Some(&provider) => {
+                    trace!(
+                        "{unit} in {entry} is shadowed by {}",
+                        self.entries[provider]
+                    );
+                    *shadowed.entry(provider).or_default() += 1;
+                }
+                None => {
+                    self.index.insert(unit, entry_index);
+                }
+            }
+        }
+
+        for (provider, count) in shadowed {
+            warn!(
+                "{count} classes in {entry} are shadowed by {}",
+                self.entries[provider]
+            );
+        }
+
+        debug!("added class path entry {entry}");
+
+        self.entries.push(entry);
+    }
+
+    /// The entry providing a unit, if any
+    pub fn find(&self, unit_name: &str) -> Option<&ClassPathEntry> {
+        let provider = self.index.get(unit_name).copied();
+
+        // Only the directories before the container providing the unit can shadow it
+        self.entries[..provider.unwrap_or(self.entries.len())]
+            .iter()
+            .find(|e| e.has_class_file(unit_name))
+            .or_else(|| provider.map(|i| &self.entries[i]))
+    }
+
+    pub fn read_class_file(&self, unit_name: &str) -> anyhow::Result<Cursor<Vec<u8>>> {
+        let Some(entry) = self.find(unit_name) else {
+            bail!("no JVM unit in class path for {unit_name} (searched {self})");
+        };
+
+        debug!("Found class file for {unit_name} in {entry}");
+
+        entry
+            .read_class_file(unit_name)
+            .with_context(|| format!("reading {unit_name} from {entry}"))
+    }
+}
+
+impl Display for ClassPath {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let paths = self
+            .entries
+            .iter()
+            .map(|e| e.path().to_path_buf())
+            .collect::<Vec<_>>();
+
+        match std::env::join_paths(paths) {
+            Ok(v) => write!(f, "{}", v.to_string_lossy()),
+            Err(_) => write!(f, "{:?}", self.entries),
+        }
+    }
+}
+
+impl ClassPathEntry {
+    pub fn path(&self) -> &Path {
+        match self {
+            Self::Directory(path) => path,
+            Self::Container(container) => container.path(),
+        }
+    }
+
+    fn is_same_path(&self, path: &Path) -> bool {
+        match (self.path().canonicalize(), path.canonicalize()) {
+            (Ok(a), Ok(b)) => a == b,
+            _ => false,
+        }
+    }
+
+    /// Whether this entry is a directory with a class file for the unit. The directories which
+    /// cannot be read are skipped with a warning.
+    fn has_class_file(&self, unit_name: &str) -> bool {
+        let Self::Directory(path) = self else {
+            return false;
+        };
+
+        let class_file = class_file_path(path, unit_name);
+
+        match std::fs::metadata(&class_file) {
+            Ok(metadata) => metadata.is_file(),
+            Err(err) if matches!(err.kind(), ErrorKind::NotFound | ErrorKind::NotADirectory) => {
+                false
+            }
+            Err(err) => {
+                warn!("unable to read {class_file:?}: {err}. Skipping...");
+                false
+            }
+        }
+    }
+
+    pub fn read_class_file(&self, unit_name: &str) -> anyhow::Result<Cursor<Vec<u8>>> {
+        match self {
+            Self::Directory(path) => Ok(Cursor::new(std::fs::read(class_file_path(
+                path, unit_name,
+            ))?)),
+            Self::Container(container) => container.read_class_file(unit_name),
+        }
+    }
+}
+
+impl Display for ClassPathEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.path().display())
+    }
+}
+
+/// The path of the class file of a unit in a directory
+fn class_file_path(dir: &Path, unit_name: &str) -> PathBuf {
+    dir.join(format!("{unit_name}.class"))
+}
+
+/// Lists the jars of a directory, for wildcard entries. Their order is unspecified with java, so
+/// they are sorted by name here.
+fn list_jars(dir: &Path) -> anyhow::Result<Vec<PathBuf>> {
+    let mut jars = vec![];
+
+    for dir_entry in std::fs::read_dir(dir)? {
+        let path = dir_entry?.path();
+
+        let is_jar = path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("jar"));
+
+        if is_jar && path.is_file() {
+            jars.push(path);
+        }
+    }
+
+    jars.sort();
+
+    Ok(jars)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::class_container::tests::{test_dir, write_jar};
+
+    fn entry_paths(class_path: &ClassPath) -> Vec<PathBuf> {
+        class_path
+            .entries
+            .iter()
+            .map(|e| e.path().to_path_buf())
+            .collect()
+    }
+
+    #[test]
+    fn class_path_is_split_on_the_path_separator() {
+        let dir = test_dir("class-path-split");
+        let (first, second) = (dir.join("first"), dir.join("second"));
+
+        std::fs::create_dir_all(first.join("com/example")).unwrap();
+        std::fs::create_dir_all(&second).unwrap();
+        std::fs::write(first.join("com/example/Main.class"), []).unwrap();
+        std::fs::write(second.join("Other.class"), []).unwrap();
+
+        let separator = if cfg!(windows) { ';' } else { ':' };
+        let class_path = ClassPath::parse(&format!(
+            "{}{separator}{}{separator}{}",
+            first.display(),
+            second.display(),
+            dir.join("missing").display()
+        ));
+
+        assert_eq!(entry_paths(&class_path), vec![first.clone(), second]);
+        assert_eq!(
+            class_path
+                .find("com/example/Main")
+                .map(ClassPathEntry::path),
+            Some(first.as_path())
+        );
+        assert!(class_path.find("Other").is_some());
+    }
+
+    #[test]
+    fn wildcard_expands_to_the_jars_of_the_directory() {
+        let dir = test_dir("class-path-wildcard");
+        let lib = dir.join("lib");
+
+        std::fs::create_dir_all(lib.join("dir.jar")).unwrap();
+        write_jar(&lib.join("b.jar"), &[("B.class", "")]);
+        write_jar(&lib.join("a.JAR"), &[("A.class", "")]);
+        std::fs::write(lib.join("notes.txt"), "not a jar").unwrap();
+        std::fs::write(lib.join("C.class"), []).unwrap();
+
+        let class_path = ClassPath::parse(&lib.join("*").to_string_lossy());
+
+        assert_eq!(
+            entry_paths(&class_path),
+            vec![lib.join("a.JAR"), lib.join("b.jar")]
+        );
+        assert!(class_path.find("A").is_some());
+        assert!(class_path.find("C").is_none());
+    }
+
+    #[test]
+    fn first_entry_providing_a_unit_is_used() {
+        let dir = test_dir("class-path-shadowing");
+
+        write_jar(&dir.join("a.jar"), &[("Main.class", "a")]);
+        write_jar(&dir.join("b.jar"), &[("Main.class", "b")]);
+
+        let mut class_path = ClassPath::new();
+        class_path.push(&dir.join("a.jar"));
+        class_path.push(&dir.join("b.jar"));
+        class_path.push(&dir.join("a.jar"));
+
+        assert_eq!(class_path.entries.len(), 2);
+        assert_eq!(
+            class_path.read_class_file("Main").unwrap().into_inner(),
+            b"a"
+        );
+    }
+
+    #[test]
+    fn directories_are_looked_up_when_needed() {
+        let dir = test_dir("class-path-lazy");
+        let classes = dir.join("classes");
+
+        std::fs::create_dir_all(&classes).unwrap();
+        write_jar(
+            &dir.join("a.jar"),
+            &[("Main.class", "jar"), ("Other.class", "jar")],
+        );
+
+        let mut class_path = ClassPath::new();
+        class_path.push(&dir.join("a.jar"));
+        class_path.push(&classes);
+
+        // Class files added after the directory are found, after the jar though
+        std::fs::create_dir_all(classes.join("com/example")).unwrap();
+        std::fs::write(classes.join("com/example/Main.class"), "directory").unwrap();
+        std::fs::write(classes.join("Main.class"), "directory").unwrap();
+
+        assert_eq!(
+            class_path
+                .read_class_file("com/example/Main")
+                .unwrap()
+                .into_inner(),
+            b"directory"
+        );
+        assert_eq!(
+            class_path.read_class_file("Main").unwrap().into_inner(),
+            b"jar"
+        );
+        assert!(class_path.find("com/example/Other").is_none());
+        // A file in place of a directory of the path is not an error
+        assert!(class_path.find("Main/Inner").is_none());
+
+        // Directories first on the class path shadow the containers
+        let mut class_path = ClassPath::new();
+        class_path.push(&classes);
+        class_path.push(&dir.join("a.jar"));
+
+        assert_eq!(
+            class_path.read_class_file("Main").unwrap().into_inner(),
+            b"directory"
+        );
+        assert_eq!(
+            class_path.read_class_file("Other").unwrap().into_inner(),
+            b"jar"
+        );
+    }
+}
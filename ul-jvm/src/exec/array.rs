@@ -1,11 +1,15 @@
+use parking_lot::Mutex;
+
 use crate::types::{JvmInt, JvmTypeDescriptor};
 
-use super::runtime_type::RuntimeType;
+use super::{exception::throw, monitor::LazyMonitor, runtime_type::RuntimeType};
 
-#[derive(Debug, Clone)]
+#[derive(Debug)]
 pub struct Array {
+    /// The type of the components of the array (int[] for int[][])
     pub compound_type: JvmTypeDescriptor,
-    pub array: Box<[RuntimeType]>,
+    array: Mutex<Box<[RuntimeType]>>,
+    pub monitor: LazyMonitor,
 }
 
 impl Array {
@@ -17,9 +21,66 @@ impl Array {
             content.push(default_value.clone());
         }
 
+        Self::from_values(compound_type, content)
+    }
+
+    pub fn from_values(compound_type: JvmTypeDescriptor, values: Vec<RuntimeType>) -> Self {
         Self {
             compound_type,
-            array: content.into_boxed_slice(),
+            array: Mutex::new(values.into_boxed_slice()),
+            monitor: LazyMonitor::default(),
         }
     }
+
+    pub fn length(&self) -> JvmInt {
+        self.array.lock().len() as JvmInt
+    }
+
+    /// Fails with an ArrayIndexOutOfBoundsException if the index is out of this array
+    pub fn check_index(&self, index: JvmInt) -> anyhow::Result<()> {
+        index_of(index, self.array.lock().len()).map(|_| ())
+    }
+
+    pub fn load(&self, index: JvmInt) -> anyhow::Result<RuntimeType> {
+        let array = self.array.lock();
+
+        Ok(array[index_of(index, array.len())?].clone())
+    }
+
+    /// Stores a value in the array, the value being expected to be of the compound type
+    pub fn store(&self, index: JvmInt, value: RuntimeType) -> anyhow::Result<()> {
+        let mut array = self.array.lock();
+        let index = index_of(index, array.len())?;
+
+        array[index] = value;
+
+        Ok(())
+    }
+
+    /// The heap values referenced by the components of the array, for the garbage collector
+    pub fn references(&self) -> Vec<RuntimeType> {
+        if !matches!(
+            self.compound_type,
+            JvmTypeDescriptor::Class(_) | JvmTypeDescriptor::Array(_)
+        ) {
+            return vec![];
+        }
+
+        self.array
+            .lock()
+            .iter()
+            .filter(|v| v.is_heap_reference())
+            .cloned()
+            .collect()
+    }
+}
+
+fn index_of(index: JvmInt, len: usize) -> anyhow::Result<usize> {
+    match usize::try_from(index) {
+        Ok(i) if i < len => Ok(i),
+        _ => throw!(
+            "java/lang/ArrayIndexOutOfBoundsException",
+            "Index {index} out of bounds for length {len}"
+        ),
+    }
 }
@@ -5,22 +5,27 @@ use std::{
         Arc,
         atomic::{AtomicBool, Ordering},
     },
+    thread::ThreadId,
 };
 
-use anyhow::anyhow;
-use parking_lot::{Mutex, ReentrantMutex, ReentrantMutexGuard};
+use anyhow::{anyhow, bail};
+use parking_lot::{Condvar, Mutex, ReentrantMutex, ReentrantMutexGuard};
 
 use crate::{
-    class::constant_pool::{
-        ConstantDouble, ConstantFieldref, ConstantInterfaceMethodref, ConstantLong,
-        ConstantMethodref, LoadableJvmConstant,
+    class::{
+        attributes::BootstrapMethodsEntry,
+        constant_pool::{
+            ConstantClass, ConstantDouble, ConstantFieldref, ConstantInterfaceMethodref,
+            ConstantLong, ConstantMethodref, DynamicInvoke, LoadableJvmConstant,
+        },
     },
     native::jnb::{JnbObject, JnbObjectType},
-    types::JvmMethodDescriptor,
+    types::{JvmMethodDescriptor, JvmTypeDescriptor},
 };
 
 use super::{
-    JvmExecEnv, heap::ObjectRef, interface::Interface, method::Method, runtime_type::RuntimeType,
+    JvmExecEnv, exception::throw, heap::ObjectRef, interface::Interface, invoke::CallSiteTarget,
+    method::Method, monitor::LazyMonitor, runtime_type::RuntimeType, thread::StackFrame, verifier,
 };
 
 #[derive(Debug)]
@@ -29,6 +34,98 @@ pub struct ClassInstance {
     pub is_abstract: bool,
     pub parent: Option<Box<ClassInstance>>,
     pub class_instance_impl: ClassInstanceImpl,
+    pub monitor: LazyMonitor,
+}
+
+impl ClassInstance {
+    /// Returns the part of this instance holding the fields declared by the given class
+    fn layer_of(&self, class_name: &str) -> Option<&ClassInstance> {
+        if self.class_type.name.as_str() == class_name {
+            Some(self)
+        } else {
+            self.parent.as_ref().and_then(|p| p.layer_of(class_name))
+        }
+    }
+
+    pub fn read_field(&self, class_name: &str, name: &str) -> anyhow::Result<RuntimeType> {
+        let layer = self.layer_of(class_name).ok_or_else(|| {
+            anyhow!(
+                "{} is not an instance of {class_name}",
+                self.class_type.name
+            )
+        })?;
+
+        match &layer.class_instance_impl {
+            ClassInstanceImpl::Normal { fields, .. } => fields
+                .get(&name.to_string())
+                .map(|f| f.lock().value.clone())
+                .ok_or_else(|| anyhow!("no field at {class_name}@{name}")),
+            ClassInstanceImpl::JnbStandalone { jnb } => jnb.get_field(name),
+        }
+    }
+
+    pub fn write_field(
+        &self,
+        class_name: &str,
+        name: &str,
+        value: RuntimeType,
+    ) -> anyhow::Result<()> {
+        let layer = self.layer_of(class_name).ok_or_else(|| {
+            anyhow!(
+                "{} is not an instance of {class_name}",
+                self.class_type.name
+            )
+        })?;
+
+        match &layer.class_instance_impl {
+            ClassInstanceImpl::Normal { fields, .. } => {
+                fields
+                    .get(&name.to_string())
+                    .ok_or_else(|| anyhow!("no field at {class_name}@{name}"))?
+                    .lock()
+                    .value = value;
+
+                Ok(())
+            }
+            ClassInstanceImpl::JnbStandalone { jnb } => jnb.set_field(name, value),
+        }
+    }
+
+    /// The heap values referenced by the fields of the instance, including the ones declared
+    /// by its superclasses, for the garbage collector
+    pub fn references(&self) -> Vec<RuntimeType> {
+        let mut references = Vec::new();
+        let mut layer = Some(self);
+
+        while let Some(instance) = layer {
+            match (
+                &instance.class_instance_impl,
+                &instance.class_type.class_impl,
+            ) {
+                (ClassInstanceImpl::Normal { fields, .. }, _) => references.extend(
+                    fields
+                        .values()
+                        .map(|f| f.lock().value.clone())
+                        .filter(RuntimeType::is_heap_reference),
+                ),
+                (
+                    ClassInstanceImpl::JnbStandalone { jnb },
+                    ClassImpl::JnbStandalone { jnb: ty, .. },
+                ) => references.extend(
+                    ty.descriptor()
+                        .fields
+                        .iter()
+                        .filter_map(|(name, _, _)| jnb.get_field(name).ok())
+                        .filter(RuntimeType::is_heap_reference),
+                ),
+                _ => {}
+            }
+
+            layer = instance.parent.as_deref();
+        }
+
+        references
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -63,6 +160,7 @@ impl Class {
                 .as_ref()
                 .map(|c| Box::new(c.instanciate_uninit())),
             class_instance_impl: instance_impl,
+            monitor: LazyMonitor::default(),
             // fields: self
             //     .0
             //     .fields
@@ -80,6 +178,7 @@ impl Class {
         super_class: Option<Class>,
         interfaces: Vec<Interface>,
         name: Arc<String>,
+        major_version: u16,
         constant_pool: ConstantPool,
         jnb_type: Box<dyn JnbObjectType>,
     ) -> Self {
@@ -87,8 +186,13 @@ impl Class {
             super_class,
             interfaces,
             name,
+            major_version,
             constant_pool,
-            statics_initialized: AtomicBool::new(false),
+            is_linked: AtomicBool::new(false),
+            init_state: Mutex::new(InitState::Uninitialized),
+            init_done: Condvar::new(),
+            call_sites: Mutex::new(HashMap::new()),
+            monitor: LazyMonitor::default(),
             class_impl: ClassImpl::JnbStandalone {
                 jnb: jnb_type,
                 statics_lock: ReentrantMutex::new(()),
@@ -100,34 +204,308 @@ impl Class {
         super_class: Option<Class>,
         interfaces: Vec<Interface>,
         name: Arc<String>,
+        major_version: u16,
         constant_pool: ConstantPool,
-        static_fields: HashMap<String, ClassField>,
-        fields: Box<[ClassField]>,
-        methods: HashMap<String, Box<[Method]>>,
-        is_abstract: bool,
-        jnb_type: Option<Box<dyn JnbObjectType>>,
+        members: ClassMembers,
     ) -> Self {
         Self(Arc::new(InnerClass {
             super_class,
             interfaces,
             name,
+            major_version,
             constant_pool,
-            statics_initialized: AtomicBool::new(false),
+            is_linked: AtomicBool::new(false),
+            init_state: Mutex::new(InitState::Uninitialized),
+            init_done: Condvar::new(),
+            call_sites: Mutex::new(HashMap::new()),
+            monitor: LazyMonitor::default(),
             class_impl: ClassImpl::Normal {
                 static_fields: ReentrantMutex::new(
-                    static_fields
+                    members
+                        .static_fields
                         .into_iter()
                         .map(|(k, v)| (k, Mutex::new(v)))
                         .collect(),
                 ),
-                fields,
-                methods,
-                is_abstract,
-                jnb: jnb_type,
+                fields: members.fields,
+                methods: members.methods,
+                is_abstract: members.is_abstract,
+                jnb: members.jnb_type,
             },
         }))
     }
 
+    pub fn is_abstract(&self) -> bool {
+        match &self.class_impl {
+            ClassImpl::Normal { is_abstract, .. } => *is_abstract,
+            ClassImpl::JnbStandalone { .. } => false,
+        }
+    }
+
+    /// Whether the given class is a (direct or indirect) superclass of this one
+    pub fn is_subclass_of(&self, class_name: &str) -> bool {
+        let mut current = self.super_class.as_ref();
+
+        while let Some(class) = current {
+            if class.name.as_str() == class_name {
+                return true;
+            }
+
+            current = class.super_class.as_ref();
+        }
+
+        false
+    }
+
+    /// Returns the declaration of an instance field of this class (and not of its superclasses)
+    pub fn get_declared_field(&self, name: &str) -> Option<ClassField> {
+        match &self.class_impl {
+            ClassImpl::Normal { fields, .. } => {
+                fields.iter().find(|f| f.name.as_str() == name).cloned()
+            }
+            ClassImpl::JnbStandalone { jnb, .. } => jnb
+                .descriptor()
+                .fields
+                .iter()
+                .find(|f| f.0 == name)
+                .map(|(name, ty, is_final)| ClassField {
+                    name: Arc::new(name.to_string()),
+                    value: RuntimeType::default_of(ty),
+                    ty: ty.clone(),
+                    is_final: *is_final,
+                }),
+        }
+    }
+
+    /// Returns the declaration of a static field of this class or interface (and not of its
+    /// superclasses or superinterfaces)
+    pub fn get_declared_static_field(&self, name: &str) -> Option<ClassField> {
+        match &self.class_impl {
+            ClassImpl::Normal { static_fields, .. } => {
+                static_fields.lock().get(name).map(|f| f.lock().clone())
+            }
+            ClassImpl::JnbStandalone { jnb, .. } => jnb
+                .descriptor()
+                .static_fields
+                .iter()
+                .find(|f| f.0 == name)
+                .map(|(name, ty, is_final)| ClassField {
+                    name: Arc::new(name.to_string()),
+                    value: RuntimeType::default_of(ty),
+                    ty: ty.clone(),
+                    is_final: *is_final,
+                }),
+        }
+    }
+
+    /// Resolves a static field as described by the JVMS (§5.4.3.2): it is looked up in this
+    /// class, then in its superinterfaces, then in its superclasses. Returns the class or
+    /// interface declaring it with its declaration.
+    pub fn resolve_static_field(
+        &self,
+        name: &str,
+        ty: &JvmTypeDescriptor,
+    ) -> Option<(Class, ClassField)> {
+        if let Some(field) = self.get_declared_static_field(name).filter(|f| &f.ty == ty) {
+            return Some((self.clone(), field));
+        }
+
+        self.interfaces
+            .iter()
+            .find_map(|i| i.resolve_static_field(name, ty))
+            .or_else(|| {
+                self.super_class
+                    .as_ref()
+                    .and_then(|c| c.resolve_static_field(name, ty))
+            })
+    }
+
+    /// Resolves an instance field as described by the JVMS (§5.4.3.2), returning the class
+    /// declaring it with its declaration.
+    pub fn resolve_instance_field(&self, name: &str) -> Option<(Class, ClassField)> {
+        if let Some(field) = self.get_declared_field(name) {
+            return Some((self.clone(), field));
+        }
+
+        self.super_class
+            .as_ref()
+            .and_then(|c| c.resolve_instance_field(name))
+    }
+
+    /// Looks up an instance method in this class, then in its superclasses, returning the class
+    /// declaring it along with the method.
+    pub fn find_instance_method(
+        &self,
+        name: &str,
+        ty: &JvmMethodDescriptor,
+    ) -> Option<(Class, Method)> {
+        if let Some(method) = self.get_instance_method(name, ty.clone()) {
+            return Some((self.clone(), method));
+        }
+
+        self.super_class
+            .as_ref()
+            .and_then(|c| c.find_instance_method(name, ty))
+    }
+
+    /// Resolves an instance method as described by the JVMS (§5.4.3.3), looking into the
+    /// superclasses then into the superinterfaces of this class.
+    pub fn resolve_method(&self, name: &str, ty: &JvmMethodDescriptor) -> Option<(Class, Method)> {
+        self.find_instance_method(name, ty)
+            .or_else(|| self.maximally_specific_methods(name, ty).into_iter().next())
+    }
+
+    /// Selects the method to invoke on an instance of this class for a resolved method, as
+    /// described by the JVMS (§5.4.6). Private resolved methods are not selected through this.
+    pub fn select_method(
+        &self,
+        name: &str,
+        ty: &JvmMethodDescriptor,
+    ) -> anyhow::Result<(Class, Method)> {
+        let mut current = Some(self);
+
+        while let Some(class) = current {
+            if let Some(method) = class
+                .get_instance_method(name, ty.clone())
+                .filter(|m| !m.is_private())
+            {
+                if method.is_abstract() {
+                    throw!(
+                        "java/lang/AbstractMethodError",
+                        "{}.{name} ({ty:?})",
+                        class.name
+                    );
+                }
+
+                return Ok((class.clone(), method));
+            }
+
+            current = class.super_class.as_ref();
+        }
+
+        let mut defaults = self
+            .maximally_specific_methods(name, ty)
+            .into_iter()
+            .filter(|(_, m)| !m.is_abstract());
+
+        match (defaults.next(), defaults.next()) {
+            (Some(selected), None) => Ok(selected),
+            (Some((first, _)), Some((second, _))) => throw!(
+                "java/lang/IncompatibleClassChangeError",
+                "conflicting default methods {name} ({ty:?}) in {} and {} for {}",
+                first.name,
+                second.name,
+                self.name
+            ),
+            (None, _) => throw!(
+                "java/lang/AbstractMethodError",
+                "{}.{name} ({ty:?})",
+                self.name
+            ),
+        }
+    }
+
+    /// Returns the maximally-specific superinterface methods (JVMS §5.4.3.3) matching the given
+    /// name and descriptor, along with the interfaces declaring them.
+    pub fn maximally_specific_methods(
+        &self,
+        name: &str,
+        ty: &JvmMethodDescriptor,
+    ) -> Vec<(Class, Method)> {
+        let candidates = self
+            .super_interfaces()
+            .into_iter()
+            .filter_map(|i| {
+                i.get_instance_method(name, ty.clone())
+                    .filter(|m| !m.is_private())
+                    .map(|m| (i, m))
+            })
+            .collect::<Vec<_>>();
+
+        candidates
+            .iter()
+            .filter(|(i, _)| {
+                !candidates
+                    .iter()
+                    .any(|(j, _)| j.is_subinterface_of(&i.name))
+            })
+            .map(|(i, m)| (i.as_class().clone(), m.clone()))
+            .collect()
+    }
+
+    /// Returns all the (direct or indirect) superinterfaces of this class and its superclasses
+    pub fn super_interfaces(&self) -> Vec<Interface> {
+        let mut found: Vec<Interface> = vec![];
+        let mut pending = vec![];
+        let mut current = Some(self);
+
+        while let Some(class) = current {
+            pending.extend(class.interfaces.iter().cloned());
+            current = class.super_class.as_ref();
+        }
+
+        while let Some(interface) = pending.pop() {
+            if !found.iter().any(|i| i.name == interface.name) {
+                pending.extend(interface.interfaces.iter().cloned());
+                found.push(interface);
+            }
+        }
+
+        found
+    }
+
+    /// Whether this class implements the given interface, directly or not
+    pub fn implements(&self, interface_name: &str) -> bool {
+        self.super_interfaces()
+            .iter()
+            .any(|i| i.name.as_str() == interface_name)
+    }
+
+    /// Whether instances of this class are also instances of the given class or interface
+    pub fn is_assignable_to(&self, class_name: &str) -> bool {
+        class_name == "java/lang/Object"
+            || self.name.as_str() == class_name
+            || self.is_subclass_of(class_name)
+            || self.implements(class_name)
+    }
+
+    /// Whether instances of this class are also values of the given reference type
+    pub fn is_subtype_of(&self, ty: &JvmTypeDescriptor) -> bool {
+        match ty {
+            JvmTypeDescriptor::Class(name) => self.is_assignable_to(name),
+            _ => false,
+        }
+    }
+
+    /// Whether values of a reference type are also values of another reference type, as checked
+    /// by checkcast (arrays are covariant, primitive arrays only match themselves). The classes
+    /// and interfaces named by `from` are found with `lookup`.
+    pub fn is_type_assignable(
+        from: &JvmTypeDescriptor,
+        to: &JvmTypeDescriptor,
+        lookup: &impl Fn(&str) -> Option<Class>,
+    ) -> anyhow::Result<bool> {
+        Ok(match (from, to) {
+            (JvmTypeDescriptor::Class(from), to) => lookup(from)
+                .ok_or_else(|| anyhow!("no class nor interface found for {from}"))?
+                .is_subtype_of(to),
+            (JvmTypeDescriptor::Array(_), JvmTypeDescriptor::Class(name)) => matches!(
+                name.as_str(),
+                "java/lang/Object" | "java/lang/Cloneable" | "java/io/Serializable"
+            ),
+            (JvmTypeDescriptor::Array(from), JvmTypeDescriptor::Array(to)) => {
+                match (from.as_ref(), to.as_ref()) {
+                    (
+                        JvmTypeDescriptor::Class(_) | JvmTypeDescriptor::Array(_),
+                        JvmTypeDescriptor::Class(_) | JvmTypeDescriptor::Array(_),
+                    ) => Self::is_type_assignable(from, to, lookup)?,
+                    (from, to) => from == to,
+                }
+            }
+            _ => false,
+        })
+    }
+
     pub fn get_static_method(&self, name: &str, ty: JvmMethodDescriptor) -> Option<Method> {
         match &self.class_impl {
             ClassImpl::Normal { methods, .. } => methods.get(name).and_then(|methods| {
@@ -194,11 +572,51 @@ impl Class {
             .ok_or(anyhow!("no static field at {}@{name}", self.name))
     }
 
-    pub fn write_static(&self, name: &String, value: RuntimeType) -> anyhow::Result<()> {
+    /// Writes a static field declared by this class from the method running in the given frame.
+    /// Final fields can only be written by the <clinit> of this class.
+    pub fn write_static(
+        &self,
+        env: &JvmExecEnv,
+        writer: &StackFrame,
+        name: &String,
+        value: RuntimeType,
+    ) -> anyhow::Result<()> {
         let lock = self.lock_statics();
 
-        // TODO: check for final flag
-        lock.set(name, value)
+        if lock.is_final(name)?
+            && (writer.current_class.name != self.name
+                || writer.method.name().as_str() != "<clinit>")
+        {
+            throw!(
+                "java/lang/IllegalAccessError",
+                "final field {}.{name} set from {}.{}",
+                self.name,
+                writer.current_class.name,
+                writer.method.name()
+            );
+        }
+
+        lock.set(env, name, value)
+    }
+
+    /// The heap values referenced by the static fields of the class, roots of the garbage
+    /// collector
+    pub fn static_references(&self) -> Vec<RuntimeType> {
+        match &self.class_impl {
+            ClassImpl::Normal { static_fields, .. } => static_fields
+                .lock()
+                .values()
+                .map(|f| f.lock().value.clone())
+                .filter(RuntimeType::is_heap_reference)
+                .collect(),
+            ClassImpl::JnbStandalone { jnb, .. } => jnb
+                .descriptor()
+                .static_fields
+                .iter()
+                .map(|(name, _, _)| jnb.get_static_field(name))
+                .filter(RuntimeType::is_heap_reference)
+                .collect(),
+        }
     }
 
     pub fn lock_statics(&self) -> StaticLock {
@@ -210,11 +628,128 @@ impl Class {
         }
     }
 
-    pub fn set_initialized_if_needed(&self) -> bool {
-        self.0
-            .statics_initialized
-            .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
-            .is_ok()
+    /// The errors thrown by the linkage of the call sites of the class, kept alive to be
+    /// rethrown, roots of the garbage collector
+    pub fn call_site_errors(&self) -> Vec<RuntimeType> {
+        self.call_sites
+            .lock()
+            .values()
+            .filter_map(|target| target.as_ref().err())
+            .map(|error| RuntimeType::Class(error.clone()))
+            .collect()
+    }
+
+    /// Gets the target of the invokedynamic call site at the given address, or the error thrown
+    /// by its linkage, if already linked
+    pub fn get_call_site(&self, pc: usize) -> Option<Result<CallSiteTarget, ObjectRef>> {
+        self.call_sites.lock().get(&pc).cloned()
+    }
+
+    /// Binds an invokedynamic call site to its target, or to the error thrown by its linkage.
+    /// When the call site was linked in the meantime, the first outcome is kept and returned.
+    pub fn set_call_site(
+        &self,
+        pc: usize,
+        target: Result<CallSiteTarget, ObjectRef>,
+    ) -> Result<CallSiteTarget, ObjectRef> {
+        self.call_sites.lock().entry(pc).or_insert(target).clone()
+    }
+
+    /// Whether this class declares instance methods which are not abstract, as the default
+    /// methods of interfaces
+    pub fn declares_concrete_instance_methods(&self) -> bool {
+        match &self.class_impl {
+            ClassImpl::Normal { methods, .. } => methods
+                .values()
+                .flatten()
+                .any(|m| !m.is_static() && !m.is_abstract()),
+            ClassImpl::JnbStandalone { .. } => false,
+        }
+    }
+
+    /// The methods declared by this class or interface (and not by its supertypes)
+    pub fn declared_methods(&self) -> impl Iterator<Item = &Method> {
+        match &self.class_impl {
+            ClassImpl::Normal { methods, .. } => Some(methods.values().flatten()),
+            ClassImpl::JnbStandalone { .. } => None,
+        }
+        .into_iter()
+        .flatten()
+    }
+
+    /// Links this class or interface unless already done (JVMS §5.4): its superclass and
+    /// superinterfaces are linked first, then its methods are verified. A class failing
+    /// verification is left unlinked, the VerifyError being thrown again on the next attempt.
+    pub fn link(&self, env: &JvmExecEnv) -> anyhow::Result<()> {
+        if self.is_linked.load(Ordering::Acquire) {
+            return Ok(());
+        }
+
+        if let Some(super_class) = &self.super_class {
+            super_class.link(env)?;
+        }
+
+        for interface in &self.interfaces {
+            interface.link(env)?;
+        }
+
+        verifier::verify_class(env, self)?;
+
+        self.is_linked.store(true, Ordering::Release);
+
+        Ok(())
+    }
+
+    /// Starts the initialization of this class by the current thread (steps 1 to 6 of JVMS
+    /// §5.5), waiting for any other thread initializing it. Returns false when there is nothing
+    /// to do: the class is initialized, or being initialized by the current thread.
+    pub fn begin_initialization(&self) -> anyhow::Result<bool> {
+        let current_thread = std::thread::current().id();
+        let mut state = self.init_state.lock();
+
+        loop {
+            match *state {
+                InitState::BeingInitialized(thread) if thread != current_thread => {
+                    self.init_done.wait(&mut state)
+                }
+                InitState::BeingInitialized(_) | InitState::Initialized => return Ok(false),
+                InitState::Erroneous => throw!(
+                    "java/lang/NoClassDefFoundError",
+                    "Could not initialize class {}",
+                    self.name.replace('/', ".")
+                ),
+                InitState::Uninitialized => {
+                    *state = InitState::BeingInitialized(current_thread);
+                    return Ok(true);
+                }
+            }
+        }
+    }
+
+    /// Whether initializing this class has nothing to do: it is initialized, or being initialized
+    /// by the current thread
+    pub fn is_initialized(&self) -> bool {
+        match *self.init_state.lock() {
+            InitState::Initialized => true,
+            InitState::BeingInitialized(thread) => thread == std::thread::current().id(),
+            InitState::Uninitialized | InitState::Erroneous => false,
+        }
+    }
+
+    fn is_being_initialized_by_current_thread(&self) -> bool {
+        *self.init_state.lock() == InitState::BeingInitialized(std::thread::current().id())
+    }
+
+    /// Ends the initialization of this class, waking up the threads waiting for it. A class
+    /// whose initialization failed cannot be used anymore.
+    pub fn end_initialization(&self, succeeded: bool) {
+        *self.init_state.lock() = if succeeded {
+            InitState::Initialized
+        } else {
+            InitState::Erroneous
+        };
+
+        self.init_done.notify_all();
     }
 }
 
@@ -250,12 +785,14 @@ impl Deref for Class {
 //     }
 // }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct ConstantPool {
     loadables: HashMap<u16, LoadableJvmConstant>,
     fieldrefs: HashMap<u16, ConstantFieldref>,
     methodrefs: HashMap<u16, ConstantMethodref>,
     interface_methodrefs: HashMap<u16, ConstantInterfaceMethodref>,
+    dynamic_invokes: HashMap<u16, DynamicInvoke>,
+    bootstrap_methods: Vec<BootstrapMethodsEntry>,
 }
 
 impl ConstantPool {
@@ -264,12 +801,16 @@ impl ConstantPool {
         fieldrefs: HashMap<u16, ConstantFieldref>,
         methodrefs: HashMap<u16, ConstantMethodref>,
         interface_methodrefs: HashMap<u16, ConstantInterfaceMethodref>,
+        dynamic_invokes: HashMap<u16, DynamicInvoke>,
+        bootstrap_methods: Vec<BootstrapMethodsEntry>,
     ) -> Self {
         Self {
             loadables,
             fieldrefs,
             methodrefs,
             interface_methodrefs,
+            dynamic_invokes,
+            bootstrap_methods,
         }
     }
 
@@ -285,6 +826,19 @@ impl ConstantPool {
         self.interface_methodrefs.get(&cp_index).cloned()
     }
 
+    pub fn get_dynamic_invoke(&self, cp_index: u16) -> Option<DynamicInvoke> {
+        self.dynamic_invokes.get(&cp_index).cloned()
+    }
+
+    /// Gets an entry of the BootstrapMethods attribute of the class
+    pub fn get_bootstrap_method(&self, index: u16) -> Option<BootstrapMethodsEntry> {
+        self.bootstrap_methods.get(index as usize).cloned()
+    }
+
+    pub fn get_loadable(&self, cp_index: u16) -> Option<LoadableJvmConstant> {
+        self.loadables.get(&cp_index).cloned()
+    }
+
     pub fn get_long(&self, cp_index: u16) -> Option<ConstantLong> {
         self.loadables.get(&cp_index).cloned().and_then(|v| {
             if let LoadableJvmConstant::Long(v) = v {
@@ -295,6 +849,16 @@ impl ConstantPool {
         })
     }
 
+    pub fn get_class(&self, cp_index: u16) -> Option<ConstantClass> {
+        self.loadables.get(&cp_index).cloned().and_then(|v| {
+            if let LoadableJvmConstant::Class(v) = v {
+                Some(v)
+            } else {
+                None
+            }
+        })
+    }
+
     pub fn get_double(&self, cp_index: u16) -> Option<ConstantDouble> {
         self.loadables.get(&cp_index).cloned().and_then(|v| {
             if let LoadableJvmConstant::Double(v) = v {
@@ -306,16 +870,48 @@ impl ConstantPool {
     }
 }
 
+/// The members declared by a class, as given to Class::new
+#[derive(Debug)]
+pub struct ClassMembers {
+    pub static_fields: HashMap<String, ClassField>,
+    pub fields: Box<[ClassField]>,
+    pub methods: HashMap<String, Box<[Method]>>,
+    pub is_abstract: bool,
+    pub jnb_type: Option<Box<dyn JnbObjectType>>,
+}
+
 #[derive(Debug)]
 pub struct InnerClass {
     pub super_class: Option<Class>,
     pub interfaces: Vec<Interface>,
     pub name: Arc<String>,
+    /// Major version of the class file the class was loaded from
+    pub major_version: u16,
     pub constant_pool: ConstantPool,
-    statics_initialized: AtomicBool,
+    /// Whether the class has been verified (JVMS §5.4)
+    is_linked: AtomicBool,
+    init_state: Mutex<InitState>,
+    /// Notified when the initialization of the class ends, for the threads waiting for it
+    init_done: Condvar,
+    /// Targets of the linked invokedynamic call sites, or the errors thrown by their linkage
+    /// (JVMS §5.4.3.6), by address of the instruction
+    call_sites: Mutex<HashMap<usize, Result<CallSiteTarget, ObjectRef>>>,
+    /// Monitor of the Class object, held by static synchronized methods
+    pub monitor: LazyMonitor,
     class_impl: ClassImpl,
 }
 
+/// State of the initialization of a class or interface (JVMS §5.5)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum InitState {
+    Uninitialized,
+    /// Being initialized by the given thread
+    BeingInitialized(ThreadId),
+    Initialized,
+    /// The initialization failed, so the class cannot be initialized anymore
+    Erroneous,
+}
+
 #[derive(Debug)]
 pub enum ClassImpl {
     Normal {
@@ -353,19 +949,55 @@ impl StaticLock<'_> {
     pub fn get(&self, name: &str) -> Option<RuntimeType> {
         match self {
             StaticLock::Normal(guard) => guard.get(name).map(|v| v.lock().value.clone()),
-            StaticLock::JnbStandalone(jnb_object_type, _) => {
-                // TODO: check if the variable exists
-                Some(jnb_object_type.get_static_field(name))
-            }
+            StaticLock::JnbStandalone(jnb_object_type, _) => jnb_object_type
+                .descriptor()
+                .static_fields
+                .iter()
+                .any(|f| f.0 == name)
+                .then(|| jnb_object_type.get_static_field(name)),
+        }
+    }
+
+    /// The type of a static field and whether it is final
+    fn declaration(&self, name: &str) -> anyhow::Result<(JvmTypeDescriptor, bool)> {
+        match self {
+            StaticLock::Normal(guard) => guard.get(name).map(|f| {
+                let field = f.lock();
+
+                (field.ty.clone(), field.is_final)
+            }),
+            StaticLock::JnbStandalone(jnb_object_type, _) => jnb_object_type
+                .descriptor()
+                .static_fields
+                .iter()
+                .find(|f| f.0 == name)
+                .map(|(_, ty, is_final)| (ty.clone(), *is_final)),
         }
+        .ok_or_else(|| anyhow!("no static field named {name}"))
     }
 
-    pub fn set(&self, name: &str, value: RuntimeType) -> anyhow::Result<()> {
-        // TODO: check if variable exists and check value type
+    pub fn is_final(&self, name: &str) -> anyhow::Result<bool> {
+        self.declaration(name).map(|(_, is_final)| is_final)
+    }
+
+    /// Sets a static field, the value being checked (and narrowed) against the type of the field.
+    /// As for the verifier, a field of an interface type accepts any reference (JVMS §4.10.1.2).
+    pub fn set(&self, env: &JvmExecEnv, name: &str, value: RuntimeType) -> anyhow::Result<()> {
+        let (ty, _) = self.declaration(name)?;
+        let value = value.store_as(&ty)?;
+
+        if value.is_reference()
+            && !env.is_instance_of(&value, &ty)?
+            && !matches!(&ty, JvmTypeDescriptor::Class(name) if env.get_interface(name).is_some())
+        {
+            bail!("{value:?} cannot be stored as {ty:?}");
+        }
 
         match self {
             StaticLock::Normal(guard) => {
-                guard.get(name).unwrap().lock().value = value;
+                if let Some(field) = guard.get(name) {
+                    field.lock().value = value;
+                }
             }
             StaticLock::JnbStandalone(jnb_object_type, _) => {
                 jnb_object_type.set_static_field(name, value)
@@ -380,6 +1012,7 @@ impl StaticLock<'_> {
 pub struct ClassField {
     pub name: Arc<String>,
     pub value: RuntimeType,
+    pub ty: JvmTypeDescriptor,
     pub is_final: bool,
 }
 
@@ -0,0 +1,156 @@
+use std::{fmt::Display, sync::Arc};
+
+use anyhow::anyhow;
+
+use super::{JvmExecEnv, heap::ObjectRef, runtime_type::RuntimeType, thread::JvmThread};
+
+/// A Java exception being thrown, carried as an error until a handler catches it
+#[derive(Debug)]
+pub enum JavaException {
+    /// An exception raised by the interpreter itself (like the NullPointerException of a null
+    /// dereference), which is instantiated once it has to be caught
+    New {
+        class_name: String,
+        message: Option<String>,
+        /// The exception which caused this one, if any
+        cause: Option<ObjectRef>,
+    },
+    /// An exception object, thrown by athrow or not caught by a callee
+    Thrown(ObjectRef),
+}
+
+impl JavaException {
+    pub fn new(class_name: &str, message: impl Into<String>) -> Self {
+        Self::New {
+            class_name: class_name.to_string(),
+            message: Some(message.into()),
+            cause: None,
+        }
+    }
+
+    /// An exception wrapping another one as its cause, without any message of its own (as the
+    /// ExceptionInInitializerError of a failed static initializer)
+    pub fn caused_by(class_name: &str, cause: ObjectRef) -> Self {
+        Self::New {
+            class_name: class_name.to_string(),
+            message: None,
+            cause: Some(cause),
+        }
+    }
+
+    /// Gets the exception object, instantiating it if needed. The constructors of exceptions
+    /// raised by the interpreter are not run, only their message is set.
+    pub fn into_object(self, env: &JvmExecEnv) -> anyhow::Result<ObjectRef> {
+        let (class_name, message, cause) = match self {
+            Self::Thrown(object) => return Ok(object),
+            Self::New {
+                class_name,
+                message,
+                cause,
+            } => (class_name, message, cause),
+        };
+
+        let class = env
+            .get_class(&class_name)
+            .ok_or_else(|| anyhow!("cannot throw {class_name} ({message:?}): class not found"))?;
+
+        // The cause is only held here while the initializer of the class may run
+        let pinned_cause = cause.as_ref().and_then(ObjectRef::upgrade);
+
+        JvmThread::initialize_class(env, &class)?;
+
+        drop(pinned_cause);
+
+        let object = env.heap.new_object(class);
+        let instance = object
+            .get()
+            .ok_or_else(|| anyhow!("exception collected right after its allocation"))?;
+
+        if let Some(message) = message {
+            instance.write_field(
+                "java/lang/Throwable",
+                "detailMessage",
+                RuntimeType::InternedString(Arc::new(message)),
+            )?;
+        }
+
+        if let Some(cause) = cause {
+            instance.write_field("java/lang/Throwable", "cause", RuntimeType::Class(cause))?;
+        }
+
+        Ok(object)
+    }
+}
+
+impl Display for JavaException {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::New {
+                class_name,
+                message,
+                cause,
+            } => {
+                write!(f, "{}", class_name.replace('/', "."))?;
+
+                if let Some(message) = message {
+                    write!(f, ": {message}")?;
+                }
+
+                match cause {
+                    Some(cause) => write!(f, "\nCaused by: {}", Self::Thrown(cause.clone())),
+                    None => Ok(()),
+                }
+            }
+            Self::Thrown(object) => {
+                let Some(instance) = object.get() else {
+                    return write!(f, "<collected exception>");
+                };
+
+                write!(f, "{}", instance.class_type.name.replace('/', "."))?;
+
+                if let Some(message) = instance
+                    .read_field("java/lang/Throwable", "detailMessage")
+                    .ok()
+                    .as_ref()
+                    .and_then(RuntimeType::as_native_string)
+                {
+                    write!(f, ": {message}")?;
+                }
+
+                // The cause of an exception without any is the exception itself
+                match instance.read_field("java/lang/Throwable", "cause") {
+                    Ok(RuntimeType::Class(cause)) if !cause.is_null() && !cause.ptr_eq(object) => {
+                        write!(f, "\nCaused by: {}", Self::Thrown(cause))
+                    }
+                    _ => Ok(()),
+                }
+            }
+        }
+    }
+}
+
+impl std::error::Error for JavaException {}
+
+/// The end of the program requested by System.exit, carried as an error up to the launcher
+#[derive(Debug)]
+pub struct ProgramExit {
+    pub status: i32,
+}
+
+impl Display for ProgramExit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "program exited with status {}", self.status)
+    }
+}
+
+impl std::error::Error for ProgramExit {}
+
+/// Returns early with a Java exception of the given class and formatted message, as bail! does
+/// for other errors
+macro_rules! throw {
+    ($class_name:expr, $($arg:tt)+) => {
+        return Err($crate::exec::exception::JavaException::new($class_name, format!($($arg)+)).into())
+    };
+}
+
+pub(crate) use throw;
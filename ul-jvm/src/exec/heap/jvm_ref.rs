@@ -1,4 +1,7 @@
-use std::sync::{Arc, Weak, atomic::AtomicUsize};
+use std::sync::{
+    Arc, Weak,
+    atomic::{AtomicUsize, Ordering},
+};
 
 use crate::exec::{array::Array, class::ClassInstance};
 
@@ -27,11 +30,28 @@ impl<T> JvmStrongRef<T> {
         }
     }
 
-    pub fn duplicate(&self) -> Self {
-        Self {
-            inner: self.inner.clone(),
-            last_visited: AtomicUsize::new(0),
-        }
+    pub fn get(&self) -> Option<&Arc<T>> {
+        self.inner.as_ref()
+    }
+
+    pub(super) fn address(&self) -> Option<usize> {
+        self.inner
+            .as_ref()
+            .map(|v| Arc::as_ptr(v) as *const () as usize)
+    }
+
+    pub(super) fn strong_count(&self) -> usize {
+        self.inner.as_ref().map(Arc::strong_count).unwrap_or(0)
+    }
+
+    /// Number of the last garbage collection which reached the value
+    pub(super) fn last_visited(&self) -> usize {
+        self.last_visited.load(Ordering::Relaxed)
+    }
+
+    /// Marks the value as reached by the given collection, returning false if it already was
+    pub(super) fn visit(&self, epoch: usize) -> bool {
+        self.last_visited.swap(epoch, Ordering::Relaxed) != epoch
     }
 }
 
@@ -67,6 +87,35 @@ impl<T> JvmRef<T> {
         self.inner.is_none()
     }
 
+    /// Gets the referenced value, or None for null (or collected) references
+    pub fn get(&self) -> Option<Arc<T>> {
+        self.inner.as_ref().and_then(Weak::upgrade)
+    }
+
+    pub fn ptr_eq(&self, other: &Self) -> bool {
+        match (&self.inner, &other.inner) {
+            (Some(l), Some(r)) => Weak::ptr_eq(l, r),
+            (None, None) => true,
+            _ => false,
+        }
+    }
+
+    /// The identity hash code of the referenced value (as given by Object.hashCode), derived
+    /// from its address, 0 for null
+    pub fn identity_hash(&self) -> i32 {
+        self.inner
+            .as_ref()
+            .map(|v| (Weak::as_ptr(v) as *const () as usize >> 3) as i32)
+            .unwrap_or(0)
+    }
+
+    /// Address of the referenced value, even if collected, None for null
+    pub(super) fn address(&self) -> Option<usize> {
+        self.inner
+            .as_ref()
+            .map(|v| Weak::as_ptr(v) as *const () as usize)
+    }
+
     pub fn upgrade(&self) -> Option<JvmStrongRef<T>> {
         self.inner.as_ref().map(|v| JvmStrongRef {
             inner: v.upgrade(),
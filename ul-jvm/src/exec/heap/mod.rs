@@ -1,14 +1,20 @@
 mod jvm_ref;
 
+use std::{
+    collections::HashMap,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
 pub use jvm_ref::{ArrayRef, ObjectRef, StrongArrayRef, StrongObjectRef};
+use log::debug;
 use parking_lot::Mutex;
 
 use crate::types::{JvmInt, JvmTypeDescriptor};
 
-use super::{
-    array::Array,
-    class::{Class, ClassInstance},
-};
+use super::{array::Array, class::Class, runtime_type::RuntimeType};
+
+/// Number of allocations after which the first collection happens
+const MIN_COLLECTION_THRESHOLD: usize = 16 * 1024;
 
 #[derive(Debug)]
 pub enum AllocatableType {
@@ -16,23 +22,85 @@ pub enum AllocatableType {
     Class(StrongObjectRef),
 }
 
+impl AllocatableType {
+    /// Address of the allocated value, shared with the weak references to it
+    fn address(&self) -> Option<usize> {
+        match self {
+            AllocatableType::Array(array) => array.address(),
+            AllocatableType::Class(object) => object.address(),
+        }
+    }
+
+    fn last_visited(&self) -> usize {
+        match self {
+            AllocatableType::Array(array) => array.last_visited(),
+            AllocatableType::Class(object) => object.last_visited(),
+        }
+    }
+
+    /// Marks the value as visited by the given collection, returning false if it already was
+    fn visit(&self, epoch: usize) -> bool {
+        match self {
+            AllocatableType::Array(array) => array.visit(epoch),
+            AllocatableType::Class(object) => object.visit(epoch),
+        }
+    }
+
+    /// Whether a strong reference to the value is held outside of the heap
+    fn is_held(&self) -> bool {
+        match self {
+            AllocatableType::Array(array) => array.strong_count() > 1,
+            AllocatableType::Class(object) => object.strong_count() > 1,
+        }
+    }
+
+    /// The references held by the value: the fields of objects or the components of arrays
+    fn references(&self) -> Vec<RuntimeType> {
+        match self {
+            AllocatableType::Array(array) => {
+                array.get().map(|a| a.references()).unwrap_or_default()
+            }
+            AllocatableType::Class(object) => {
+                object.get().map(|o| o.references()).unwrap_or_default()
+            }
+        }
+    }
+}
+
 #[derive(Debug, Default)]
 pub struct JvmHeap {
     values: Mutex<Vec<AllocatableType>>,
+    /// Number of the last collection, the values it reached having it as last_visited
+    epoch: AtomicUsize,
+    /// Number of values allocated since the last collection
+    allocated: AtomicUsize,
+    /// Number of allocations triggering the next collection, growing with the live values
+    threshold: AtomicUsize,
 }
 
 impl JvmHeap {
     pub fn new() -> Self {
-        Self {
-            values: Mutex::new(Vec::new()),
-        }
+        Self::default()
     }
 
     pub fn new_array(&self, compound_type: JvmTypeDescriptor, size: JvmInt) -> ArrayRef {
         let strong_ref = StrongArrayRef::new(Array::new_default(compound_type, size));
         let ret_ref = strong_ref.new_ref();
 
-        self.values.lock().push(AllocatableType::Array(strong_ref));
+        self.push(AllocatableType::Array(strong_ref));
+
+        ret_ref
+    }
+
+    pub fn new_array_with(
+        &self,
+        compound_type: JvmTypeDescriptor,
+        values: Vec<RuntimeType>,
+    ) -> ArrayRef {
+        let strong_ref = StrongArrayRef::new(Array::from_values(compound_type, values));
+        let ret_ref = strong_ref.new_ref();
+
+        self.push(AllocatableType::Array(strong_ref));
 
         ret_ref
     }
@@ -41,18 +109,260 @@ impl JvmHeap {
         let strong_ref = StrongObjectRef::new(class.instanciate_uninit());
         let ret_ref = strong_ref.new_ref();
 
-        self.values.lock().push(AllocatableType::Class(strong_ref));
+        self.push(AllocatableType::Class(strong_ref));
 
         ret_ref
     }
 
-    pub fn store_object(&self, instance: ClassInstance) -> StrongObjectRef {
-        let strong_ref = StrongObjectRef::new(instance);
+    fn push(&self, value: AllocatableType) {
+        self.values.lock().push(value);
+        self.allocated.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Whether enough values were allocated since the last collection to run a new one
+    pub fn should_collect(&self) -> bool {
+        self.allocated.load(Ordering::Relaxed)
+            >= self
+                .threshold
+                .load(Ordering::Relaxed)
+                .max(MIN_COLLECTION_THRESHOLD)
+    }
+
+    /// Frees the values which cannot be reached from the given roots, nor from the values
+    /// strongly referenced outside of the heap (JVMS §2.5.3)
+    ///
+    /// The world must be stopped: any reference not in the roots, like the ones held by a
+    /// suspended instruction, may be left dangling.
+    pub fn collect(&self, roots: impl IntoIterator<Item = RuntimeType>) {
+        let mut values = self.values.lock();
+        let epoch = self.epoch.fetch_add(1, Ordering::Relaxed) + 1;
+
+        let indices = values
+            .iter()
+            .enumerate()
+            .filter_map(|(idx, value)| Some((value.address()?, idx)))
+            .collect::<HashMap<_, _>>();
+
+        // Mark
+        let mut pending = roots.into_iter().collect::<Vec<_>>();
+
+        pending.extend(
+            values
+                .iter()
+                .filter(|v| v.is_held() && v.visit(epoch))
+                .flat_map(AllocatableType::references),
+        );
+
+        while let Some(value) = pending.pop() {
+            let address = match &value {
+                RuntimeType::Array(array) => array.address(),
+                RuntimeType::Class(object) => object.address(),
+                _ => None,
+            };
+
+            let Some(allocated) = address.and_then(|a| indices.get(&a)).map(|&i| &values[i]) else {
+                continue;
+            };
+
+            if allocated.visit(epoch) {
+                pending.extend(allocated.references());
+            }
+        }
+
+        // Sweep
+        let before = values.len();
+
+        values.retain(|v| v.last_visited() == epoch);
+
+        debug!(
+            "garbage collection {epoch}: freed {} values, {} live",
+            before - values.len(),
+            values.len()
+        );
+
+        self.allocated.store(0, Ordering::Relaxed);
+        self.threshold.store(values.len() * 2, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, sync::Arc};
+
+    use super::*;
+    use crate::exec::{
+        JvmExecEnv,
+        class::{ClassField, ClassMembers},
+    };
+
+    fn object_type() -> JvmTypeDescriptor {
+        JvmTypeDescriptor::Class(String::from("java/lang/Object"))
+    }
+
+    fn field(name: &str) -> ClassField {
+        ClassField {
+            name: Arc::new(name.to_string()),
+            value: RuntimeType::Class(ObjectRef::new_null()),
+            ty: object_type(),
+            is_final: false,
+        }
+    }
+
+    /// A class with an instance field next, and a static field head
+    fn node_class() -> Class {
+        Class::new(
+            None,
+            vec![],
+            Arc::new(String::from("Node")),
+            61,
+            Default::default(),
+            ClassMembers {
+                static_fields: HashMap::from([(String::from("head"), field("head"))]),
+                fields: Box::new([field("next")]),
+                methods: HashMap::new(),
+                is_abstract: false,
+                jnb_type: None,
+            },
+        )
+    }
+
+    fn new_node(heap: &JvmHeap, next: RuntimeType) -> ObjectRef {
+        let node = heap.new_object(node_class());
+
+        node.get()
+            .unwrap()
+            .write_field("Node", "next", next)
+            .unwrap();
+
+        node
+    }
+
+    fn live_count(heap: &JvmHeap) -> usize {
+        heap.values.lock().len()
+    }
+
+    #[test]
+    fn unreachable_values_are_freed() {
+        let heap = JvmHeap::new();
+        let array = heap.new_array(JvmTypeDescriptor::Int, 4);
+        let object = new_node(&heap, RuntimeType::Class(ObjectRef::new_null()));
+        // A cycle only referenced by itself
+        let cycle = new_node(&heap, RuntimeType::Class(ObjectRef::new_null()));
+        let other = new_node(&heap, RuntimeType::Class(cycle.clone()));
+        cycle
+            .get()
+            .unwrap()
+            .write_field("Node", "next", RuntimeType::Class(other.clone()))
+            .unwrap();
+
+        heap.collect([
+            RuntimeType::Int(1),
+            RuntimeType::Class(ObjectRef::new_null()),
+        ]);
+
+        assert_eq!(live_count(&heap), 0);
+        assert!(array.get().is_none());
+        assert!(object.get().is_none());
+        assert!(cycle.get().is_none());
+        assert!(other.get().is_none());
+    }
+
+    #[test]
+    fn values_reachable_from_fields_and_arrays_are_kept() {
+        let heap = JvmHeap::new();
+        let leaf = heap.new_array(JvmTypeDescriptor::Int, 1);
+        let node = new_node(&heap, RuntimeType::Array(leaf.clone()));
+        let array = heap.new_array_with(
+            JvmTypeDescriptor::Array(Box::new(object_type())),
+            vec![RuntimeType::Class(node.clone())],
+        );
+        let garbage = new_node(&heap, RuntimeType::Class(node.clone()));
+
+        heap.collect([RuntimeType::Array(array.clone())]);
+
+        assert_eq!(live_count(&heap), 3);
+        assert!(array.get().is_some());
+        assert!(node.get().is_some());
+        assert!(leaf.get().is_some());
+        assert!(garbage.get().is_none());
+    }
+
+    #[test]
+    fn values_reachable_from_static_fields_are_kept() {
+        let env = JvmExecEnv::new();
+        let class = node_class();
+        let head = new_node(&env.heap, RuntimeType::Class(ObjectRef::new_null()));
+        let garbage = new_node(&env.heap, RuntimeType::Class(ObjectRef::new_null()));
+
+        class
+            .lock_statics()
+            .set(&env, "head", RuntimeType::Class(head.clone()))
+            .unwrap();
+        env.classes
+            .write()
+            .insert(class.name.to_string(), class.clone());
+
+        env.collect_garbage([]);
+
+        assert!(head.get().is_some());
+        assert!(garbage.get().is_none());
+    }
+
+    #[test]
+    fn values_held_outside_of_the_heap_are_kept() {
+        let heap = JvmHeap::new();
+        let leaf = heap.new_array(JvmTypeDescriptor::Int, 1);
+        let held = new_node(&heap, RuntimeType::Array(leaf.clone()));
+        let strong = held.upgrade().unwrap();
+
+        heap.collect([]);
+
+        assert!(held.get().is_some());
+        assert!(leaf.get().is_some());
+
+        drop(strong);
+        heap.collect([]);
+
+        assert!(held.get().is_none());
+        assert!(leaf.get().is_none());
+    }
+
+    #[test]
+    fn threshold_is_twice_the_live_values() {
+        let heap = JvmHeap::new();
+        let kept = (0..10)
+            .map(|_| RuntimeType::Array(heap.new_array(JvmTypeDescriptor::Int, 1)))
+            .collect::<Vec<_>>();
+
+        for _ in 0..MIN_COLLECTION_THRESHOLD {
+            heap.new_array(JvmTypeDescriptor::Int, 1);
+        }
+
+        assert!(heap.should_collect());
+
+        heap.collect(kept.clone());
 
-        self.values
-            .lock()
-            .push(AllocatableType::Class(strong_ref.duplicate()));
+        assert_eq!(live_count(&heap), 10);
+        assert_eq!(heap.allocated.load(Ordering::Relaxed), 0);
+        assert_eq!(heap.threshold.load(Ordering::Relaxed), 20);
+        assert!(!heap.should_collect());
+
+        // The threshold grows with the live values, beyond the minimal one
+        let kept = (0..MIN_COLLECTION_THRESHOLD)
+            .map(|_| RuntimeType::Array(heap.new_array(JvmTypeDescriptor::Int, 1)))
+            .collect::<Vec<_>>();
+
+        heap.collect(kept);
+
+        assert_eq!(
+            heap.threshold.load(Ordering::Relaxed),
+            2 * MIN_COLLECTION_THRESHOLD
+        );
+
+        for _ in 0..MIN_COLLECTION_THRESHOLD {
+            heap.new_array(JvmTypeDescriptor::Int, 1);
+        }
 
-        strong_ref
+        assert!(!heap.should_collect());
     }
 }
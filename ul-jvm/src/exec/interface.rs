@@ -1,35 +1,40 @@
-use std::{collections::HashMap, ops::Deref, sync::Arc};
+use std::ops::Deref;
 
-use super::class::ClassField;
+use super::class::Class;
 
+/// An interface, backed by an abstract class with no superclass holding its constant pool, its
+/// methods (abstract, default and static ones) and its static fields. The interfaces of this
+/// class are the superinterfaces of the interface.
 #[derive(Debug, Clone)]
-pub struct Interface(Arc<InterfaceInner>);
+pub struct Interface(Class);
 
 impl Interface {
-    pub fn new(name: Arc<String>, static_fields: HashMap<String, ClassField>) -> Self {
-        Self(Arc::new(InterfaceInner {
-            name,
-            static_fields,
-        }))
+    pub fn new(class: Class) -> Self {
+        Self(class)
+    }
+
+    pub fn as_class(&self) -> &Class {
+        &self.0
+    }
+
+    /// Whether the given interface is a (direct or indirect) superinterface of this one
+    pub fn is_subinterface_of(&self, interface_name: &str) -> bool {
+        self.interfaces
+            .iter()
+            .any(|i| i.name.as_str() == interface_name || i.is_subinterface_of(interface_name))
     }
 }
 
-impl AsRef<InterfaceInner> for Interface {
-    fn as_ref(&self) -> &InterfaceInner {
+impl AsRef<Class> for Interface {
+    fn as_ref(&self) -> &Class {
         &self.0
     }
 }
 
 impl Deref for Interface {
-    type Target = InterfaceInner;
+    type Target = Class;
 
     fn deref(&self) -> &Self::Target {
-        self.0.deref()
+        &self.0
     }
 }
-
-#[derive(Debug)]
-pub struct InterfaceInner {
-    pub name: Arc<String>,
-    pub static_fields: HashMap<String, ClassField>,
-}
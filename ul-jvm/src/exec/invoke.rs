@@ -0,0 +1,261 @@
+use std::{
+    collections::HashMap,
+    fmt::{Display, LowerExp},
+    sync::Arc,
+};
+
+use crate::{
+    class::constant_pool::{ConstantMethodHandle, LoadableJvmConstant},
+    types::{JvmMethodDescriptor, JvmTypeDescriptor},
+};
+
+use super::{
+    class::{Class, ClassField, ClassMembers, ConstantPool},
+    exception::throw,
+    interface::Interface,
+    method::Method,
+    runtime_type::RuntimeType,
+};
+
+/// Tag of the recipes of StringConcatFactory standing for the next argument of the call site
+pub const CONCAT_ARGUMENT_TAG: char = '\u{1}';
+/// Tag of the recipes of StringConcatFactory standing for the next constant of the bootstrap
+pub const CONCAT_CONSTANT_TAG: char = '\u{2}';
+
+/// Flag of LambdaMetafactory.altMetafactory for lambdas implementing java/io/Serializable
+pub const LAMBDA_FLAG_SERIALIZABLE: i32 = 1 << 0;
+/// Flag of LambdaMetafactory.altMetafactory for lambdas implementing marker interfaces
+pub const LAMBDA_FLAG_MARKERS: i32 = 1 << 1;
+/// Flag of LambdaMetafactory.altMetafactory for lambdas with bridge methods
+pub const LAMBDA_FLAG_BRIDGES: i32 = 1 << 2;
+
+/// The behaviour an invokedynamic call site is bound to once linked
+#[derive(Debug, Clone)]
+pub enum CallSiteTarget {
+    /// The target method handle of the CallSite returned by the bootstrap method, invoked with
+    /// the arguments of the call site
+    MethodHandle(Arc<ConstantMethodHandle>),
+    /// A string concatenation from StringConcatFactory, evaluated natively instead of running
+    /// the bootstrap method (which requires most of java.lang.invoke)
+    StringConcat {
+        recipe: Arc<str>,
+        constants: Arc<[String]>,
+    },
+    /// Creates an instance of a synthetic class implementing a functional interface, capturing
+    /// the arguments of the call site (as the classes spun by LambdaMetafactory)
+    Lambda(Class),
+}
+
+impl CallSiteTarget {
+    /// Links a call site bootstrapped by a method of java/lang/invoke/StringConcatFactory
+    pub fn string_concat(
+        bootstrap_name: &str,
+        ty: &JvmMethodDescriptor,
+        bootstrap_arguments: Vec<LoadableJvmConstant>,
+    ) -> anyhow::Result<Self> {
+        let (recipe, constants) = match bootstrap_name {
+            // Every argument is concatenated as is
+            "makeConcat" => (
+                CONCAT_ARGUMENT_TAG
+                    .to_string()
+                    .repeat(ty.parameter_types.len()),
+                vec![],
+            ),
+            "makeConcatWithConstants" => {
+                let mut arguments = bootstrap_arguments.into_iter();
+
+                let Some(LoadableJvmConstant::String(recipe)) = arguments.next() else {
+                    throw!(
+                        "java/lang/BootstrapMethodError",
+                        "no recipe given to makeConcatWithConstants"
+                    );
+                };
+
+                let constants = arguments
+                    .map(|constant| match constant {
+                        LoadableJvmConstant::String(v) => Ok(v.to_string()),
+                        LoadableJvmConstant::Integer(v) => Ok(v.to_string()),
+                        LoadableJvmConstant::Long(v) => Ok(v.to_string()),
+                        LoadableJvmConstant::Float(v) => Ok(java_decimal_string(v, v.into())),
+                        LoadableJvmConstant::Double(v) => Ok(java_decimal_string(v, v)),
+                        v => throw!(
+                            "java/lang/BootstrapMethodError",
+                            "unsupported string concatenation constant: {v:?}"
+                        ),
+                    })
+                    .collect::<anyhow::Result<Vec<_>>>()?;
+
+                (recipe.to_string(), constants)
+            }
+            v => throw!(
+                "java/lang/BootstrapMethodError",
+                "unknown StringConcatFactory bootstrap method {v}"
+            ),
+        };
+
+        let argument_count = recipe.matches(CONCAT_ARGUMENT_TAG).count();
+        let constant_count = recipe.matches(CONCAT_CONSTANT_TAG).count();
+
+        if argument_count != ty.parameter_types.len() || constant_count != constants.len() {
+            throw!(
+                "java/lang/BootstrapMethodError",
+                "string concatenation recipe with {argument_count} arguments and \
+                {constant_count} constants for {} arguments and {} constants",
+                ty.parameter_types.len(),
+                constants.len()
+            );
+        }
+
+        Ok(Self::StringConcat {
+            recipe: recipe.into(),
+            constants: constants.into(),
+        })
+    }
+}
+
+/// The functional interface implemented by the lambdas of a LambdaMetafactory call site, and the
+/// values they capture
+#[derive(Debug)]
+pub struct LambdaShape<'a> {
+    /// The functional interface, then the marker interfaces
+    pub interfaces: Vec<Interface>,
+    /// The types of the captured values, the parameters of the call site
+    pub captured_types: &'a [JvmTypeDescriptor],
+    pub method_name: &'a str,
+    /// The type of the interface method, then the ones of its bridges
+    pub method_types: Vec<JvmMethodDescriptor>,
+}
+
+/// A conversion of a value passed to or returned by the implementation of a lambda, as allowed by
+/// LambdaMetafactory
+#[derive(Debug, Clone, PartialEq)]
+pub enum Adaptation {
+    /// The value is passed as is: references are only checked at their use
+    Identity,
+    /// Widening primitive conversion to the given type
+    Widen(JvmTypeDescriptor),
+    /// Boxing of a value of the given primitive type into an instance of its wrapper class
+    Box(JvmTypeDescriptor),
+    /// Unboxing of an instance of a wrapper class, then widening to the given primitive type
+    Unbox(JvmTypeDescriptor),
+}
+
+/// The conversions of the values passed to the implementation of a lambda (the captured values,
+/// then the arguments of the interface method), and of its result
+#[derive(Debug, Clone)]
+pub struct LambdaAdaptations {
+    pub parameters: Arc<[Adaptation]>,
+    pub ret: Adaptation,
+}
+
+/// Creates the synthetic class of the lambdas created by a LambdaMetafactory call site. Its
+/// fields hold the captured values, and its methods (the one of the functional interface and its
+/// bridges) forward to the implementation method handle.
+pub fn new_lambda_class(
+    name: String,
+    major_version: u16,
+    object: Class,
+    shape: LambdaShape,
+    implementation: Arc<ConstantMethodHandle>,
+    adaptations: LambdaAdaptations,
+) -> Class {
+    let LambdaShape {
+        interfaces,
+        captured_types,
+        method_name,
+        method_types,
+    } = shape;
+
+    let captured_fields = (1..=captured_types.len())
+        .map(|i| Arc::new(format!("arg${i}")))
+        .collect::<Arc<[_]>>();
+
+    let fields = captured_fields
+        .iter()
+        .zip(captured_types)
+        .map(|(name, ty)| ClassField {
+            name: name.clone(),
+            value: RuntimeType::default_of(ty),
+            ty: ty.clone(),
+            is_final: true,
+        })
+        .collect();
+
+    let methods = method_types
+        .into_iter()
+        .map(|ty| {
+            Method::new_forwarding(
+                ty.return_type,
+                ty.parameter_types,
+                Arc::new(method_name.to_string()),
+                implementation.clone(),
+                captured_fields.clone(),
+                adaptations.clone(),
+            )
+        })
+        .collect();
+
+    Class::new(
+        Some(object),
+        interfaces,
+        Arc::new(name),
+        major_version,
+        ConstantPool::default(),
+        ClassMembers {
+            static_fields: HashMap::new(),
+            fields,
+            methods: HashMap::from([(method_name.to_string(), methods)]),
+            is_abstract: false,
+            jnb_type: None,
+        },
+    )
+}
+
+/// Formats a number as String.valueOf does, for the values of primitive types
+pub fn java_string_of(value: &RuntimeType) -> Option<String> {
+    Some(match value {
+        RuntimeType::Int(v) => v.to_string(),
+        RuntimeType::Long(v) => v.to_string(),
+        RuntimeType::Float(v) => java_decimal_string(*v, (*v).into()),
+        RuntimeType::Double(v) => java_decimal_string(*v, *v),
+        _ => return None,
+    })
+}
+
+/// Formats a float or a double as Float.toString and Double.toString do: in plain notation with
+/// at least one fractional digit between 10^-3 and 10^7, in computerized scientific notation
+/// otherwise.
+fn java_decimal_string<T: Display + LowerExp>(value: T, as_f64: f64) -> String {
+    if as_f64.is_nan() {
+        return String::from("NaN");
+    }
+
+    if as_f64.is_infinite() {
+        return String::from(if as_f64 > 0.0 {
+            "Infinity"
+        } else {
+            "-Infinity"
+        });
+    }
+
+    let magnitude = as_f64.abs();
+
+    if magnitude == 0.0 || (1e-3..1e7).contains(&magnitude) {
+        let plain = value.to_string();
+
+        if plain.contains('.') {
+            plain
+        } else {
+            format!("{plain}.0")
+        }
+    } else {
+        let scientific = format!("{value:e}");
+        let (mantissa, exponent) = scientific.split_once('e').unwrap_or((&scientific, "0"));
+
+        if mantissa.contains('.') {
+            format!("{mantissa}E{exponent}")
+        } else {
+            format!("{mantissa}.0E{exponent}")
+        }
+    }
+}
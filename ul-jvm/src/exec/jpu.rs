@@ -1,206 +1,383 @@
+use std::{
+    cmp::Ordering,
+    str::FromStr,
+    sync::{Arc, atomic},
+};
+
 use anyhow::{Context, anyhow, bail};
+use either::Either;
 use log::{debug, trace};
 
-use crate::{exec::runtime_type::RuntimeType, types::JvmInt};
+use crate::{
+    class::constant_pool::{
+        ConstantFieldref, ConstantInterfaceMethodref, ConstantMethodHandle, ConstantMethodref,
+        DynamicInvoke, LoadableJvmConstant,
+    },
+    exec::runtime_type::RuntimeType,
+    types::{JvmDouble, JvmFloat, JvmInt, JvmLong, JvmMethodDescriptor, JvmTypeDescriptor},
+};
+
+use super::{
+    JvmExecEnv,
+    array::Array,
+    class::{Class, ClassField, ClassInstance},
+    exception::{JavaException, ProgramExit, throw},
+    heap::{ArrayRef, ObjectRef},
+    interface::Interface,
+    invoke::{
+        Adaptation, CONCAT_ARGUMENT_TAG, CONCAT_CONSTANT_TAG, CallSiteTarget, LAMBDA_FLAG_BRIDGES,
+        LAMBDA_FLAG_MARKERS, LAMBDA_FLAG_SERIALIZABLE, LambdaAdaptations, LambdaShape,
+        java_string_of, new_lambda_class,
+    },
+    method::Method,
+    monitor::Monitor,
+    thread::JvmThread,
+};
+
+/// Comparison performed by the conditional branch instructions, in the order used by the
+/// opcodes of each family (ifeq, ifne, iflt, ifge, ifgt, ifle).
+#[derive(Debug, Clone, Copy)]
+pub enum Condition {
+    Eq,
+    Ne,
+    Lt,
+    Ge,
+    Gt,
+    Le,
+}
+
+impl Condition {
+    pub fn from_opcode_offset(offset: u8) -> Self {
+        match offset {
+            0 => Self::Eq,
+            1 => Self::Ne,
+            2 => Self::Lt,
+            3 => Self::Ge,
+            4 => Self::Gt,
+            5 => Self::Le,
+            v => unreachable!("invalid condition offset {v}"),
+        }
+    }
 
-use super::{JvmExecEnv, class::Class, thread::JvmThread};
+    pub fn test(self, ordering: Ordering) -> bool {
+        match self {
+            Self::Eq => ordering.is_eq(),
+            Self::Ne => ordering.is_ne(),
+            Self::Lt => ordering.is_lt(),
+            Self::Ge => ordering.is_ge(),
+            Self::Gt => ordering.is_gt(),
+            Self::Le => ordering.is_le(),
+        }
+    }
+}
 
 pub struct JvmProcessUnit<'a> {
     env: &'a JvmExecEnv,
-    skip_static_init: bool,
 }
 
 impl<'a> JvmProcessUnit<'a> {
-    pub fn jpu_new(env: &'a JvmExecEnv, skip_static_init: bool) -> Self {
-        Self {
-            env,
-            skip_static_init,
+    pub fn jpu_new(env: &'a JvmExecEnv) -> Self {
+        Self { env }
+    }
+
+    pub fn aaload(&self, thread: &mut JvmThread) -> anyhow::Result<()> {
+        trace!("aaload");
+
+        array_load(
+            thread,
+            |ty| {
+                matches!(
+                    ty,
+                    JvmTypeDescriptor::Class(_) | JvmTypeDescriptor::Array(_)
+                )
+            },
+            "reference",
+        )
+    }
+
+    pub fn aastore(&self, thread: &mut JvmThread) -> anyhow::Result<()> {
+        trace!("aastore");
+
+        let value = thread.pop_operand_stack()?;
+        let index = pop_int(thread)?;
+        let array = pop_array(thread)?;
+
+        if !matches!(
+            array.compound_type,
+            JvmTypeDescriptor::Class(_) | JvmTypeDescriptor::Array(_)
+        ) {
+            bail!(
+                "unexpected array of {:?} (array of references expected)",
+                array.compound_type
+            );
+        }
+
+        if !value.is_reference() {
+            bail!("unexpected value (reference expected): {value:?}");
+        }
+
+        array.check_index(index)?;
+
+        if !self.env.is_instance_of(&value, &array.compound_type)? {
+            throw!(
+                "java/lang/ArrayStoreException",
+                "{value:?} stored in an array of {:?}",
+                array.compound_type
+            );
         }
+
+        array.store(index, value)
     }
 
-    pub fn bipush(&self, thread: &mut JvmThread, value: JvmInt) -> anyhow::Result<()> {
-        trace!("bipush {value}");
+    pub fn aconst_null(&self, thread: &mut JvmThread) -> anyhow::Result<()> {
+        trace!("aconst_null");
 
-        thread.push_operand_stack(RuntimeType::Int(value));
+        thread.push_operand_stack(RuntimeType::Class(ObjectRef::new_null()));
 
         Ok(())
     }
 
-    pub fn dstore(&self, thread: &mut JvmThread, local_index: u8) -> anyhow::Result<()> {
-        trace!("dstore");
+    pub fn aload(&self, thread: &mut JvmThread, local_index: u16) -> anyhow::Result<()> {
+        trace!("aload {local_index}");
 
-        let local_index = local_index as usize;
-        // TODO: check for double type
-        let value = thread.pop_operand_stack()?;
+        let value = thread.read_local(local_index as usize)?;
 
-        thread.store_to_local(local_index, value)?;
-        thread.forbid_local(local_index + 1)?;
+        if !value.is_reference() {
+            bail!("unexpected value (reference expected): {value:?}");
+        }
+
+        thread.push_operand_stack(value);
 
         Ok(())
     }
 
-    pub fn getstatic(&self, thread: &mut JvmThread, cp_index: u16) -> anyhow::Result<()> {
-        trace!("getstatic");
+    pub fn anewarray(&self, thread: &mut JvmThread, cp_index: u16) -> anyhow::Result<()> {
+        trace!("anewarray");
 
-        let field_ref = thread
+        let class_ref = thread
             .current_frame()?
             .current_class
             .constant_pool
-            .get_field_ref(cp_index)
-            .ok_or_else(|| anyhow!("no field_ref"))?;
+            .get_class(cp_index)
+            .ok_or_else(|| anyhow!("no class at {cp_index}"))?;
 
-        let target_class = self
-            .resolve_class(&field_ref.class.name)
-            .context("getstatic")?;
+        let compound_type = type_of_class_name(&class_ref.name)?;
+        let count = pop_array_size(thread)?;
 
-        self.init_static(thread, &target_class)?;
+        let array = self.env.heap.new_array(compound_type, count);
 
-        let zarma = target_class.read_static(&field_ref.name)?;
+        thread.push_operand_stack(RuntimeType::Array(array));
 
-        thread.push_operand_stack(zarma);
+        Ok(())
+    }
+
+    pub fn areturn(&self, thread: &mut JvmThread) -> anyhow::Result<()> {
+        trace!("areturn");
+
+        let value = pop_reference(thread)?;
+
+        self.return_value(thread, value)?;
 
         Ok(())
     }
 
-    pub fn iadd(&self, thread: &mut JvmThread) -> anyhow::Result<()> {
-        trace!("iadd");
+    pub fn arraylength(&self, thread: &mut JvmThread) -> anyhow::Result<()> {
+        trace!("arraylength");
 
-        let l = thread.pop_operand_stack()?;
-        let r = thread.pop_operand_stack()?;
+        let array = pop_array(thread)?;
 
-        let (l, r) = match (l, r) {
-            (RuntimeType::Int(l), RuntimeType::Int(r)) => (l, r),
-            (l, r) => bail!("expected two ints, got {l:?} and {r:?}"),
-        };
+        thread.push_operand_stack(RuntimeType::Int(array.length()));
+
+        Ok(())
+    }
+
+    pub fn astore(&self, thread: &mut JvmThread, local_index: u16) -> anyhow::Result<()> {
+        trace!("astore {local_index}");
+
+        let value = thread.pop_operand_stack()?;
+
+        // astore is also used to store the return addresses pushed by jsr
+        if !value.is_reference() && !matches!(value, RuntimeType::ReturnAddress(_)) {
+            bail!("unexpected value (reference or return address expected): {value:?}");
+        }
 
-        thread.push_operand_stack(RuntimeType::Int(l + r));
+        thread.store_to_local(local_index as usize, value)?;
 
         Ok(())
     }
 
-    pub fn idiv(&self, thread: &mut JvmThread) -> anyhow::Result<()> {
-        trace!("idiv");
+    pub fn athrow(&self, thread: &mut JvmThread) -> anyhow::Result<()> {
+        trace!("athrow");
+
+        match thread.pop_operand_stack()? {
+            RuntimeType::Class(object) if object.is_null() => {
+                throw!(
+                    "java/lang/NullPointerException",
+                    "Cannot throw a null exception"
+                )
+            }
+            RuntimeType::Class(object) => Err(JavaException::Thrown(object).into()),
+            v => bail!("unexpected value (object expected): {v:?}"),
+        }
+    }
 
-        let l = thread.pop_operand_stack()?;
-        let r = thread.pop_operand_stack()?;
+    pub fn baload(&self, thread: &mut JvmThread) -> anyhow::Result<()> {
+        trace!("baload");
 
-        let (l, r) = match (l, r) {
-            (RuntimeType::Int(l), RuntimeType::Int(r)) => (l, r),
-            (l, r) => bail!("expected two ints, got {l:?} and {r:?}"),
-        };
+        array_load(
+            thread,
+            |ty| matches!(ty, JvmTypeDescriptor::Byte | JvmTypeDescriptor::Boolean),
+            "byte or boolean",
+        )
+    }
+
+    pub fn bastore(&self, thread: &mut JvmThread) -> anyhow::Result<()> {
+        trace!("bastore");
 
-        // TODO: throw an exception when r is 0
+        array_store(
+            thread,
+            |ty| matches!(ty, JvmTypeDescriptor::Byte | JvmTypeDescriptor::Boolean),
+            "byte or boolean",
+        )
+    }
+
+    pub fn bipush(&self, thread: &mut JvmThread, value: JvmInt) -> anyhow::Result<()> {
+        trace!("bipush {value}");
 
-        thread.push_operand_stack(RuntimeType::Int(l / r));
+        thread.push_operand_stack(RuntimeType::Int(value));
 
         Ok(())
     }
 
-    pub fn iload(&self, thread: &mut JvmThread, local_index: u8) -> anyhow::Result<()> {
-        trace!("iload {local_index}");
+    pub fn caload(&self, thread: &mut JvmThread) -> anyhow::Result<()> {
+        trace!("caload");
 
-        let local_index = local_index as usize;
-        let value = thread.read_local(local_index)?;
+        array_load(thread, |ty| matches!(ty, JvmTypeDescriptor::Char), "char")
+    }
 
-        match value {
-            RuntimeType::Int(_) => (),
-            v => bail!("unexpected value (int expected): {v:?}"),
+    pub fn castore(&self, thread: &mut JvmThread) -> anyhow::Result<()> {
+        trace!("castore");
+
+        array_store(thread, |ty| matches!(ty, JvmTypeDescriptor::Char), "char")
+    }
+
+    pub fn checkcast(&self, thread: &mut JvmThread, cp_index: u16) -> anyhow::Result<()> {
+        trace!("checkcast {cp_index}");
+
+        let ty = self.resolve_type(thread, cp_index)?;
+        let value = thread.peek_operand_stack(0)?;
+
+        if !self.env.is_instance_of(value, &ty)? {
+            throw!(
+                "java/lang/ClassCastException",
+                "class {} cannot be cast to class {}",
+                type_of_reference(value)?.binary_name(),
+                ty.binary_name()
+            );
         }
 
-        thread.push_operand_stack(value);
+        Ok(())
+    }
+
+    pub fn d2f(&self, thread: &mut JvmThread) -> anyhow::Result<()> {
+        trace!("d2f");
+
+        let value = pop_double(thread)?;
+
+        thread.push_operand_stack(RuntimeType::Float(value as JvmFloat));
 
         Ok(())
     }
 
-    pub fn invokestatic(&self, thread: &mut JvmThread, cp_index: u16) -> anyhow::Result<()> {
-        trace!("invokestatic");
+    pub fn d2i(&self, thread: &mut JvmThread) -> anyhow::Result<()> {
+        trace!("d2i");
 
-        let current_class: Class = thread.current_frame()?.current_class.clone();
+        let value = pop_double(thread)?;
 
-        // TODO: handle synchronized
-        let (target_class, name, ty) = current_class
-            .constant_pool
-            .get_method_ref(cp_index)
-            .map(|m| (m.class, m.name, m.ty))
-            .or_else(|| {
-                current_class
-                    .constant_pool
-                    .get_interface_method_ref(cp_index)
-                    .map(|m| (m.class, m.name, m.ty))
-            })
-            .ok_or_else(|| anyhow!("no methodref at {cp_index}"))?;
+        thread.push_operand_stack(RuntimeType::Int(double_to_int(value)));
 
-        let target_class = self.resolve_class(&target_class.name)?;
-        let method = target_class
-            .get_static_method(&name, ty.clone())
-            .ok_or_else(|| {
-                anyhow!(
-                    "no method {ty:?} named {name} found in {}",
-                    target_class.name
-                )
-            })?;
+        Ok(())
+    }
 
-        self.init_static(thread, &target_class)?;
+    pub fn d2l(&self, thread: &mut JvmThread) -> anyhow::Result<()> {
+        trace!("d2l");
 
-        trace!(
-            "invokestatic, calling {}:{name} ({ty:?}) (native: {})",
-            target_class.name,
-            method.is_native()
-        );
-        thread.jmp_jvm_method(target_class.clone(), &method)?;
+        let value = pop_double(thread)?;
+
+        thread.push_operand_stack(RuntimeType::Long(double_to_long(value)));
 
         Ok(())
     }
 
-    pub fn istore(&self, thread: &mut JvmThread, local_index: u8) -> anyhow::Result<()> {
-        trace!("istore {local_index}");
+    pub fn dadd(&self, thread: &mut JvmThread) -> anyhow::Result<()> {
+        trace!("dadd");
 
-        let local_index = local_index as usize;
-        let value = thread.pop_operand_stack()?;
+        let (l, r) = pop_double_pair(thread)?;
 
-        // TODO: check for int type
-        thread.store_to_local(local_index, value)?;
-        thread.allow_local(local_index + 1)?;
+        thread.push_operand_stack(RuntimeType::Double(l + r));
 
         Ok(())
     }
 
-    pub fn ld2c_w(&self, thread: &mut JvmThread, cp_index: u16) -> anyhow::Result<()> {
-        trace!("ld2c_w");
+    pub fn daload(&self, thread: &mut JvmThread) -> anyhow::Result<()> {
+        trace!("daload");
 
-        let class = thread.current_frame()?.current_class.clone();
+        array_load(
+            thread,
+            |ty| matches!(ty, JvmTypeDescriptor::Double),
+            "double",
+        )
+    }
 
-        let value = class
-            .constant_pool
-            .get_long(cp_index)
-            .map(RuntimeType::Long)
-            .or_else(|| {
-                class
-                    .constant_pool
-                    .get_double(cp_index)
-                    .map(RuntimeType::Double)
-            });
+    pub fn dastore(&self, thread: &mut JvmThread) -> anyhow::Result<()> {
+        trace!("dastore");
 
-        if let Some(value) = value {
-            thread.push_operand_stack(value);
-        } else {
-            // TODO: implement this case, but I'm not quite sure what I am to expect from here (kinda tired rn)
-            todo!("unsupported ld2c_w constant pool value at {cp_index}");
-        }
+        array_store(
+            thread,
+            |ty| matches!(ty, JvmTypeDescriptor::Double),
+            "double",
+        )
+    }
+
+    pub fn dcmp(&self, thread: &mut JvmThread, nan_result: JvmInt) -> anyhow::Result<()> {
+        trace!("dcmp (NaN: {nan_result})");
+
+        let (l, r) = pop_double_pair(thread)?;
+
+        let result = l.partial_cmp(&r).map(|v| v as JvmInt).unwrap_or(nan_result);
+
+        thread.push_operand_stack(RuntimeType::Int(result));
 
         Ok(())
     }
 
-    pub fn lload(&self, thread: &mut JvmThread, local_index: u8) -> anyhow::Result<()> {
-        trace!("lload");
+    pub fn dconst(&self, thread: &mut JvmThread, value: JvmDouble) -> anyhow::Result<()> {
+        trace!("dconst {value}");
+
+        thread.push_operand_stack(RuntimeType::Double(value));
+
+        Ok(())
+    }
+
+    pub fn ddiv(&self, thread: &mut JvmThread) -> anyhow::Result<()> {
+        trace!("ddiv");
+
+        let (l, r) = pop_double_pair(thread)?;
+
+        thread.push_operand_stack(RuntimeType::Double(l / r));
+
+        Ok(())
+    }
+
+    pub fn dload(&self, thread: &mut JvmThread, local_index: u16) -> anyhow::Result<()> {
+        trace!("dload {local_index}");
 
         let local_index = local_index as usize;
         let value = thread.read_local(local_index)?;
-        // TODO: check to see if local_index + 1 is forbidden
 
         match value {
-            RuntimeType::Long(_) => (),
-            v => bail!("unexpected value (long expected): {v:?}"),
+            RuntimeType::Double(_) => (),
+            v => bail!("unexpected value (double expected): {v:?}"),
         }
 
         thread.push_operand_stack(value);
@@ -208,210 +385,4281 @@ impl<'a> JvmProcessUnit<'a> {
         Ok(())
     }
 
-    pub fn lstore(&self, thread: &mut JvmThread, local_index: u8) -> anyhow::Result<()> {
-        trace!("lstore");
+    pub fn dmul(&self, thread: &mut JvmThread) -> anyhow::Result<()> {
+        trace!("dmul");
+
+        let (l, r) = pop_double_pair(thread)?;
+
+        thread.push_operand_stack(RuntimeType::Double(l * r));
+
+        Ok(())
+    }
+
+    pub fn dneg(&self, thread: &mut JvmThread) -> anyhow::Result<()> {
+        trace!("dneg");
+
+        let value = pop_double(thread)?;
+
+        thread.push_operand_stack(RuntimeType::Double(-value));
+
+        Ok(())
+    }
+
+    pub fn drem(&self, thread: &mut JvmThread) -> anyhow::Result<()> {
+        trace!("drem");
+
+        let (l, r) = pop_double_pair(thread)?;
+
+        // Rust's remainder on floating-point values is the C fmod, which is the truncating
+        // remainder the JVMS asks for (and not the IEEE 754 remainder operation)
+        thread.push_operand_stack(RuntimeType::Double(l % r));
+
+        Ok(())
+    }
+
+    pub fn dreturn(&self, thread: &mut JvmThread) -> anyhow::Result<()> {
+        trace!("dreturn");
+
+        let value = pop_double(thread)?;
+
+        self.return_value(thread, RuntimeType::Double(value))?;
+
+        Ok(())
+    }
+
+    pub fn dstore(&self, thread: &mut JvmThread, local_index: u16) -> anyhow::Result<()> {
+        trace!("dstore");
 
         let local_index = local_index as usize;
-        // TODO: check for long type
-        let value = thread.pop_operand_stack()?;
+        let value = pop_double(thread)?;
 
-        thread.store_to_local(local_index, value)?;
+        thread.store_to_local(local_index, RuntimeType::Double(value))?;
         thread.forbid_local(local_index + 1)?;
 
         Ok(())
     }
 
-    pub fn ret(&self, thread: &mut JvmThread, local_index: u8) -> anyhow::Result<()> {
-        trace!("ret {local_index}");
+    pub fn dsub(&self, thread: &mut JvmThread) -> anyhow::Result<()> {
+        trace!("dsub");
 
-        let value = match thread.read_local(local_index as usize)? {
-            RuntimeType::ReturnAddress(v) => v,
-            v => bail!("expected return address, got {v:?}"),
-        };
+        let (l, r) = pop_double_pair(thread)?;
 
-        thread.jmp_to(value);
+        thread.push_operand_stack(RuntimeType::Double(l - r));
 
         Ok(())
     }
 
-    pub fn vreturn(&self, thread: &mut JvmThread) -> anyhow::Result<()> {
-        trace!("vreturn");
+    pub fn dup(&self, thread: &mut JvmThread) -> anyhow::Result<()> {
+        trace!("dup");
 
-        thread.ret()?;
+        dup_slots(thread, 1, 0)
+    }
+
+    pub fn dup2(&self, thread: &mut JvmThread) -> anyhow::Result<()> {
+        trace!("dup2");
+
+        dup_slots(thread, 2, 0)
+    }
+
+    pub fn dup2_x1(&self, thread: &mut JvmThread) -> anyhow::Result<()> {
+        trace!("dup2_x1");
+
+        dup_slots(thread, 2, 1)
+    }
+
+    pub fn dup2_x2(&self, thread: &mut JvmThread) -> anyhow::Result<()> {
+        trace!("dup2_x2");
+
+        dup_slots(thread, 2, 2)
+    }
+
+    pub fn dup_x1(&self, thread: &mut JvmThread) -> anyhow::Result<()> {
+        trace!("dup_x1");
+
+        dup_slots(thread, 1, 1)
+    }
+
+    pub fn dup_x2(&self, thread: &mut JvmThread) -> anyhow::Result<()> {
+        trace!("dup_x2");
+
+        dup_slots(thread, 1, 2)
+    }
+
+    pub fn f2d(&self, thread: &mut JvmThread) -> anyhow::Result<()> {
+        trace!("f2d");
+
+        let value = pop_float(thread)?;
+
+        thread.push_operand_stack(RuntimeType::Double(value as JvmDouble));
 
         Ok(())
     }
 
-    // TODO: work on a better way to also look for interfaces
-    fn resolve_class(&self, class: &String) -> anyhow::Result<Class> {
-        // TODO: load missing classes
+    pub fn f2i(&self, thread: &mut JvmThread) -> anyhow::Result<()> {
+        trace!("f2i");
 
-        let Some(class) = self.env.classes.get(class) else {
-            bail!("no class found for {class}");
-        };
+        let value = pop_float(thread)?;
+
+        thread.push_operand_stack(RuntimeType::Int(double_to_int(value as JvmDouble)));
 
-        Ok(class.clone())
+        Ok(())
     }
 
-    fn init_static(&self, thread: &JvmThread, class: &Class) -> anyhow::Result<()> {
-        if !self.skip_static_init || class.name != thread.current_frame()?.current_class.name {
-            debug!("initializing class {}", class.name);
-            JvmThread::run_clinit_thread(self.env, class.clone())
-        } else {
-            Ok(())
-        }
+    pub fn f2l(&self, thread: &mut JvmThread) -> anyhow::Result<()> {
+        trace!("f2l");
+
+        let value = pop_float(thread)?;
+
+        thread.push_operand_stack(RuntimeType::Long(double_to_long(value as JvmDouble)));
+
+        Ok(())
     }
-}
 
-/*
-    Instructions:
-    - aaload:               TODO
-    - aastore:              TODO
-    - aconst_null:          TODO
-    - aload:                TODO
-    - aload_<n>:            TODO
-    - anewarray:            TODO
-    - areturn:              TODO
-    - arraylength:          TODO
-    - astore:               TODO
-    - astore_<n>:           TODO
-    - athrow:               TODO
-    - baload:               TODO
-    - bastore:              TODO
-    - bipush:               COMPLETED
-    - caload:               TODO
-    - castore:              TODO
-    - checkcast:            TODO
-    - d2f:                  TODO
-    - d2i:                  TODO
-    - d2l:                  TODO
-    - dadd:                 TODO
-    - daload:               TODO
-    - dastore:              TODO
-    - dcmp<op>:             TODO
-    - dconst_<d>:           TODO
-    - ddiv:                 TODO
-    - dload:                TODO
-    - dload_<n>:            TODO
-    - dmul:                 TODO
-    - dneg:                 TODO
-    - drem:                 TODO
-    - dreturn:              TODO
-    - dstore:               COMPLETED
-    - dstore_<n>:           COMPLETED
-    - dsub:                 TODO
-    - dup:                  TODO
-    - dup_x1:               TODO
-    - dup_x2:               TODO
-    - dup2:                 TODO
-    - dup2_x1:              TODO
-    - dup2_x2:              TODO
-    - f2d:                  TODO
-    - f2i:                  TODO
-    - f2l:                  TODO
-    - fadd:                 TODO
-    - faload:               TODO
-    - fastore:              TODO
-    - fcmp<op>:             TODO
-    - fconst_<f>:           TODO
-    - fdiv:                 TODO
-    - fload:                TODO
-    - fload_<n>:            TODO
-    - fmul:                 TODO
-    - fneg:                 TODO
-    - frem:                 TODO
-    - freturn:              TODO
-    - fstore:               TODO
-    - fstore_<n>:           TODO
-    - fsub:                 TODO
-    - getfield:             TODO
-    - getstatic:            COMPLETED
-    - goto:                 TODO
-    - goto_w:               TODO
-    - i2b:                  TODO
-    - i2c:                  TODO
-    - i2d:                  TODO
-    - i2f:                  TODO
-    - i2l:                  TODO
-    - i2s:                  TODO
-    - iadd:                 COMPLETED
-    - iaload:               TODO
-    - iand:                 TODO
-    - iastore:              TODO
-    - iconst_<i>:           COMPLETED
-    - idiv:                 COMPLETED
-    - if_acmp<cond>:        TODO
-    - if_icmp<cond>:        TODO
-    - if<cond>:             TODO
-    - ifnonnull:            TODO
-    - ifnull:               TODO
-    - iinc:                 TODO
-    - iload:                COMPLETED
-    - iload_<n>:            COMPLETED
-    - imul:                 TODO
-    - ineg:                 TODO
-    - instanceof:           TODO
-    - invokedynamic:        TODO
-    - invokeinterface:      TODO
-    - invokespecial:        TODO
-    - invokestatic:         PARTIAL
-    - invokevirtual:        TODO
-    - ior:                  TODO
-    - irem:                 TODO
-    - ireturn:              TODO
-    - ishl:                 TODO
-    - ishr:                 TODO
-    - istore:               COMPLETED
-    - istore_<n>:           COMPLETED
-    - isub:                 TODO
-    - iushr:                TODO
-    - ixor:                 TODO
-    - jsr:                  TODO
-    - jsr_w:                TODO
-    - l2d:                  TODO
-    - l2f:                  TODO
-    - l2i:                  TODO
-    - ladd:                 TODO
-    - laload:               TODO
-    - land:                 TODO
-    - lastore:              TODO
-    - lcmp:                 TODO
-    - lconst_<l>:           TODO
-    - ldc:                  TODO
-    - ldc_w:                TODO
-    - ldc2_w:               INCOMPLETE
-    - ldiv:                 TODO
-    - lload:                COMPLETED
-    - lload_<n>:            COMPLETED
-    - lmul:                 TODO
-    - lneg:                 TODO
-    - lookupswitch:         TODO
-    - lor:                  TODO
-    - lrem:                 TODO
-    - lreturn:              TODO
-    - lshl:                 TODO
-    - lshr:                 TODO
-    - lstore:               COMPLETED
-    - lstore_<n>:           COMPLETED
-    - lsub:                 TODO
-    - lushr:                TODO
-    - lxor:                 TODO
-    - monitorenter:         TODO
-    - monitorexit:          TODO
-    - multianewarray:       TODO
-    - new:                  TODO
-    - newarray:             TODO
-    - nop:                  TODO
-    - pop:                  TODO
-    - pop2:                 TODO
-    - putfield:             TODO
-    - putstatic:            TODO
-    - ret:                  DONE
-    - return:               DONE
-    - saload:               TODO
-    - sastore:              TODO
-    - sipush:               TODO
-    - swap:                 TODO
-    - tableswitch:          TODO
-    - wide:                 TODO
-*/
+    pub fn fadd(&self, thread: &mut JvmThread) -> anyhow::Result<()> {
+        trace!("fadd");
+
+        let (l, r) = pop_float_pair(thread)?;
+
+        thread.push_operand_stack(RuntimeType::Float(l + r));
+
+        Ok(())
+    }
+
+    pub fn faload(&self, thread: &mut JvmThread) -> anyhow::Result<()> {
+        trace!("faload");
+
+        array_load(thread, |ty| matches!(ty, JvmTypeDescriptor::Float), "float")
+    }
+
+    pub fn fastore(&self, thread: &mut JvmThread) -> anyhow::Result<()> {
+        trace!("fastore");
+
+        array_store(thread, |ty| matches!(ty, JvmTypeDescriptor::Float), "float")
+    }
+
+    pub fn fcmp(&self, thread: &mut JvmThread, nan_result: JvmInt) -> anyhow::Result<()> {
+        trace!("fcmp (NaN: {nan_result})");
+
+        let (l, r) = pop_float_pair(thread)?;
+
+        let result = l.partial_cmp(&r).map(|v| v as JvmInt).unwrap_or(nan_result);
+
+        thread.push_operand_stack(RuntimeType::Int(result));
+
+        Ok(())
+    }
+
+    pub fn fconst(&self, thread: &mut JvmThread, value: JvmFloat) -> anyhow::Result<()> {
+        trace!("fconst {value}");
+
+        thread.push_operand_stack(RuntimeType::Float(value));
+
+        Ok(())
+    }
+
+    pub fn fdiv(&self, thread: &mut JvmThread) -> anyhow::Result<()> {
+        trace!("fdiv");
+
+        let (l, r) = pop_float_pair(thread)?;
+
+        thread.push_operand_stack(RuntimeType::Float(l / r));
+
+        Ok(())
+    }
+
+    pub fn fload(&self, thread: &mut JvmThread, local_index: u16) -> anyhow::Result<()> {
+        trace!("fload {local_index}");
+
+        let local_index = local_index as usize;
+        let value = thread.read_local(local_index)?;
+
+        match value {
+            RuntimeType::Float(_) => (),
+            v => bail!("unexpected value (float expected): {v:?}"),
+        }
+
+        thread.push_operand_stack(value);
+
+        Ok(())
+    }
+
+    pub fn fmul(&self, thread: &mut JvmThread) -> anyhow::Result<()> {
+        trace!("fmul");
+
+        let (l, r) = pop_float_pair(thread)?;
+
+        thread.push_operand_stack(RuntimeType::Float(l * r));
+
+        Ok(())
+    }
+
+    pub fn fneg(&self, thread: &mut JvmThread) -> anyhow::Result<()> {
+        trace!("fneg");
+
+        let value = pop_float(thread)?;
+
+        thread.push_operand_stack(RuntimeType::Float(-value));
+
+        Ok(())
+    }
+
+    pub fn frem(&self, thread: &mut JvmThread) -> anyhow::Result<()> {
+        trace!("frem");
+
+        let (l, r) = pop_float_pair(thread)?;
+
+        // Rust's remainder on floating-point values is the C fmod, which is the truncating
+        // remainder the JVMS asks for (and not the IEEE 754 remainder operation)
+        thread.push_operand_stack(RuntimeType::Float(l % r));
+
+        Ok(())
+    }
+
+    pub fn freturn(&self, thread: &mut JvmThread) -> anyhow::Result<()> {
+        trace!("freturn");
+
+        let value = pop_float(thread)?;
+
+        self.return_value(thread, RuntimeType::Float(value))?;
+
+        Ok(())
+    }
+
+    pub fn fstore(&self, thread: &mut JvmThread, local_index: u16) -> anyhow::Result<()> {
+        trace!("fstore {local_index}");
+
+        let local_index = local_index as usize;
+        let value = pop_float(thread)?;
+
+        thread.store_to_local(local_index, RuntimeType::Float(value))?;
+
+        Ok(())
+    }
+
+    pub fn fsub(&self, thread: &mut JvmThread) -> anyhow::Result<()> {
+        trace!("fsub");
+
+        let (l, r) = pop_float_pair(thread)?;
+
+        thread.push_operand_stack(RuntimeType::Float(l - r));
+
+        Ok(())
+    }
+
+    pub fn getfield(&self, thread: &mut JvmThread, cp_index: u16) -> anyhow::Result<()> {
+        trace!("getfield");
+
+        let field_ref = thread
+            .current_frame()?
+            .current_class
+            .constant_pool
+            .get_field_ref(cp_index)
+            .ok_or_else(|| anyhow!("no field_ref at {cp_index}"))?;
+
+        let (owner, _) = self
+            .resolve_class(&field_ref.class.name)?
+            .resolve_instance_field(&field_ref.name)
+            .ok_or_else(|| {
+                JavaException::new(
+                    "java/lang/NoSuchFieldError",
+                    format!("{}.{}", field_ref.class.name, field_ref.name),
+                )
+            })?;
+
+        // Strings represented natively have no fields: the ones of java/lang/String are derived
+        // from their content, for the methods of String to run on them as on String objects
+        if owner.name.as_str() == "java/lang/String"
+            && let Some(string) = thread.peek_operand_stack(0)?.as_native_string().cloned()
+        {
+            let value = self.native_string_field(&string, &field_ref.name)?;
+
+            thread.pop_operand_stack()?;
+            thread.push_operand_stack(value);
+
+            return Ok(());
+        }
+
+        let object = pop_object(thread)?;
+        let value = object.read_field(&owner.name, &field_ref.name)?;
+
+        thread.push_operand_stack(value);
+
+        Ok(())
+    }
+
+    pub fn getstatic(&self, thread: &mut JvmThread, cp_index: u16) -> anyhow::Result<()> {
+        trace!("getstatic");
+
+        let field_ref = thread
+            .current_frame()?
+            .current_class
+            .constant_pool
+            .get_field_ref(cp_index)
+            .ok_or_else(|| anyhow!("no field_ref"))?;
+
+        let (owner, _) = self.resolve_static_field(&field_ref).context("getstatic")?;
+
+        // The class or interface declaring the field is the one initialized
+        self.init_static(thread, &owner)?;
+
+        let zarma = owner.read_static(&field_ref.name)?;
+
+        thread.push_operand_stack(zarma);
+
+        Ok(())
+    }
+
+    pub fn goto(&self, thread: &mut JvmThread, op_pc: usize, offset: JvmInt) -> anyhow::Result<()> {
+        trace!("goto {offset}");
+
+        branch(thread, op_pc, offset)
+    }
+
+    pub fn i2b(&self, thread: &mut JvmThread) -> anyhow::Result<()> {
+        trace!("i2b");
+
+        let value = pop_int(thread)?;
+
+        thread.push_operand_stack(RuntimeType::Int(value as i8 as JvmInt));
+
+        Ok(())
+    }
+
+    pub fn i2c(&self, thread: &mut JvmThread) -> anyhow::Result<()> {
+        trace!("i2c");
+
+        let value = pop_int(thread)?;
+
+        thread.push_operand_stack(RuntimeType::Int(value as u16 as JvmInt));
+
+        Ok(())
+    }
+
+    pub fn i2d(&self, thread: &mut JvmThread) -> anyhow::Result<()> {
+        trace!("i2d");
+
+        let value = pop_int(thread)?;
+
+        thread.push_operand_stack(RuntimeType::Double(value as JvmDouble));
+
+        Ok(())
+    }
+
+    pub fn i2f(&self, thread: &mut JvmThread) -> anyhow::Result<()> {
+        trace!("i2f");
+
+        let value = pop_int(thread)?;
+
+        thread.push_operand_stack(RuntimeType::Float(value as JvmFloat));
+
+        Ok(())
+    }
+
+    pub fn i2l(&self, thread: &mut JvmThread) -> anyhow::Result<()> {
+        trace!("i2l");
+
+        let value = pop_int(thread)?;
+
+        thread.push_operand_stack(RuntimeType::Long(value as JvmLong));
+
+        Ok(())
+    }
+
+    pub fn i2s(&self, thread: &mut JvmThread) -> anyhow::Result<()> {
+        trace!("i2s");
+
+        let value = pop_int(thread)?;
+
+        thread.push_operand_stack(RuntimeType::Int(value as i16 as JvmInt));
+
+        Ok(())
+    }
+
+    pub fn iadd(&self, thread: &mut JvmThread) -> anyhow::Result<()> {
+        trace!("iadd");
+
+        let (l, r) = pop_int_pair(thread)?;
+
+        thread.push_operand_stack(RuntimeType::Int(l.wrapping_add(r)));
+
+        Ok(())
+    }
+
+    pub fn iaload(&self, thread: &mut JvmThread) -> anyhow::Result<()> {
+        trace!("iaload");
+
+        array_load(thread, |ty| matches!(ty, JvmTypeDescriptor::Int), "int")
+    }
+
+    pub fn iand(&self, thread: &mut JvmThread) -> anyhow::Result<()> {
+        trace!("iand");
+
+        let (l, r) = pop_int_pair(thread)?;
+
+        thread.push_operand_stack(RuntimeType::Int(l & r));
+
+        Ok(())
+    }
+
+    pub fn iastore(&self, thread: &mut JvmThread) -> anyhow::Result<()> {
+        trace!("iastore");
+
+        array_store(thread, |ty| matches!(ty, JvmTypeDescriptor::Int), "int")
+    }
+
+    pub fn idiv(&self, thread: &mut JvmThread) -> anyhow::Result<()> {
+        trace!("idiv");
+
+        let (l, r) = pop_int_pair(thread)?;
+
+        if r == 0 {
+            throw!("java/lang/ArithmeticException", "/ by zero");
+        }
+
+        // wrapping_div handles the JvmInt::MIN / -1 overflow the way Java does
+        thread.push_operand_stack(RuntimeType::Int(l.wrapping_div(r)));
+
+        Ok(())
+    }
+
+    pub fn if_acmp(
+        &self,
+        thread: &mut JvmThread,
+        op_pc: usize,
+        offset: i16,
+        condition: Condition,
+    ) -> anyhow::Result<()> {
+        trace!("if_acmp{condition:?} {offset}");
+
+        let r = pop_reference(thread)?;
+        let l = pop_reference(thread)?;
+
+        let same = l.same_reference(&r);
+
+        let taken = match condition {
+            Condition::Eq => same,
+            Condition::Ne => !same,
+            v => bail!("invalid condition for if_acmp: {v:?}"),
+        };
+
+        if taken {
+            branch(thread, op_pc, offset as JvmInt)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn if_cond(
+        &self,
+        thread: &mut JvmThread,
+        op_pc: usize,
+        offset: i16,
+        condition: Condition,
+    ) -> anyhow::Result<()> {
+        trace!("if{condition:?} {offset}");
+
+        let value = pop_int(thread)?;
+
+        if condition.test(value.cmp(&0)) {
+            branch(thread, op_pc, offset as JvmInt)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn if_icmp(
+        &self,
+        thread: &mut JvmThread,
+        op_pc: usize,
+        offset: i16,
+        condition: Condition,
+    ) -> anyhow::Result<()> {
+        trace!("if_icmp{condition:?} {offset}");
+
+        let (l, r) = pop_int_pair(thread)?;
+
+        if condition.test(l.cmp(&r)) {
+            branch(thread, op_pc, offset as JvmInt)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn ifnull(
+        &self,
+        thread: &mut JvmThread,
+        op_pc: usize,
+        offset: i16,
+        expect_null: bool,
+    ) -> anyhow::Result<()> {
+        trace!("ifnull {offset} (expect null: {expect_null})");
+
+        let value = pop_reference(thread)?;
+
+        if value.is_null() == expect_null {
+            branch(thread, op_pc, offset as JvmInt)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn iinc(&self, thread: &mut JvmThread, local_index: u16, value: i16) -> anyhow::Result<()> {
+        trace!("iinc {local_index} {value}");
+
+        let local_index = local_index as usize;
+
+        let current = match thread.read_local(local_index)? {
+            RuntimeType::Int(v) => v,
+            v => bail!("unexpected value (int expected): {v:?}"),
+        };
+
+        thread.store_to_local(
+            local_index,
+            RuntimeType::Int(current.wrapping_add(value as JvmInt)),
+        )?;
+
+        Ok(())
+    }
+
+    pub fn imul(&self, thread: &mut JvmThread) -> anyhow::Result<()> {
+        trace!("imul");
+
+        let (l, r) = pop_int_pair(thread)?;
+
+        thread.push_operand_stack(RuntimeType::Int(l.wrapping_mul(r)));
+
+        Ok(())
+    }
+
+    pub fn ineg(&self, thread: &mut JvmThread) -> anyhow::Result<()> {
+        trace!("ineg");
+
+        let value = pop_int(thread)?;
+
+        thread.push_operand_stack(RuntimeType::Int(value.wrapping_neg()));
+
+        Ok(())
+    }
+
+    pub fn iload(&self, thread: &mut JvmThread, local_index: u16) -> anyhow::Result<()> {
+        trace!("iload {local_index}");
+
+        let local_index = local_index as usize;
+        let value = thread.read_local(local_index)?;
+
+        match value {
+            RuntimeType::Int(_) => (),
+            v => bail!("unexpected value (int expected): {v:?}"),
+        }
+
+        thread.push_operand_stack(value);
+
+        Ok(())
+    }
+
+    pub fn instanceof(&self, thread: &mut JvmThread, cp_index: u16) -> anyhow::Result<()> {
+        trace!("instanceof {cp_index}");
+
+        let ty = self.resolve_type(thread, cp_index)?;
+        let value = thread.pop_operand_stack()?;
+
+        let is_instance = !value.is_null() && self.env.is_instance_of(&value, &ty)?;
+
+        thread.push_operand_stack(RuntimeType::Int(is_instance.into()));
+
+        Ok(())
+    }
+
+    pub fn invokedynamic(
+        &self,
+        thread: &mut JvmThread,
+        op_pc: usize,
+        cp_index: u16,
+    ) -> anyhow::Result<()> {
+        trace!("invokedynamic {cp_index}");
+
+        let current_class = thread.current_frame()?.current_class.clone();
+        let dynamic_invoke = current_class
+            .constant_pool
+            .get_dynamic_invoke(cp_index)
+            .ok_or_else(|| anyhow!("no dynamic call site specifier at {cp_index}"))?;
+
+        // Every invokedynamic instruction is a call site of its own, linked on first execution
+        let target = match current_class.get_call_site(op_pc) {
+            Some(target) => target,
+            None => {
+                let suspension = thread.suspend(self.env);
+                // A failed linkage is not attempted again, the same error is thrown by every
+                // later execution of the instruction (JVMS §5.4.3.6)
+                let target = match self.link_call_site(&current_class, &dynamic_invoke) {
+                    Ok(target) => Ok(target),
+                    Err(err) => Err(err.downcast::<JavaException>()?.into_object(self.env)?),
+                };
+                drop(suspension);
+
+                current_class.set_call_site(op_pc, target)
+            }
+        }
+        .map_err(JavaException::Thrown)?;
+
+        trace!("invokedynamic, calling {target:?}");
+
+        match target {
+            CallSiteTarget::MethodHandle(handle) => self.invoke_method_handle(thread, &handle),
+            CallSiteTarget::StringConcat { recipe, constants } => {
+                self.concat_strings(thread, &recipe, &constants, &dynamic_invoke.ty)
+            }
+            CallSiteTarget::Lambda(class) => self.new_lambda(thread, class, &dynamic_invoke.ty),
+        }
+    }
+
+    pub fn invokeinterface(
+        &self,
+        thread: &mut JvmThread,
+        cp_index: u16,
+        count: u8,
+    ) -> anyhow::Result<()> {
+        trace!("invokeinterface");
+
+        let method_ref = thread
+            .current_frame()?
+            .current_class
+            .constant_pool
+            .get_interface_method_ref(cp_index)
+            .ok_or_else(|| anyhow!("no interface methodref at {cp_index}"))?;
+        let ty = &method_ref.ty;
+
+        // The receiver and every parameter take one slot, two for longs and doubles
+        let expected_count = 1 + ty
+            .parameter_types
+            .iter()
+            .map(|t| if t.is_two_slots() { 2 } else { 1 })
+            .sum::<usize>();
+
+        if usize::from(count) != expected_count {
+            bail!("invokeinterface count of {count} where {expected_count} was expected");
+        }
+
+        self.invoke_interface_method(thread, method_ref)
+    }
+
+    pub fn invokespecial(&self, thread: &mut JvmThread, cp_index: u16) -> anyhow::Result<()> {
+        trace!("invokespecial");
+
+        let current_class: Class = thread.current_frame()?.current_class.clone();
+
+        let (class, method, name, ty) =
+            if let Some(method_ref) = current_class.constant_pool.get_method_ref(cp_index) {
+                let (name, ty) = (method_ref.name, method_ref.ty);
+                let resolved_class = self.resolve_class(&method_ref.class.name)?;
+                let (declaring_class, resolved) = resolved_class
+                    .resolve_method(&name, &ty)
+                    .ok_or_else(|| no_instance_method_error(&resolved_class, &name, &ty))?;
+
+                // Unless it is an instance initialization method or a private one, a method of a
+                // superclass is selected again from the superclass of the current class (the
+                // ACC_SUPER flag is considered set in every class file, as done since Java SE 8).
+                let (class, method) = if name.as_str() != "<init>"
+                    && !resolved.is_private()
+                    && current_class.is_subclass_of(&resolved_class.name)
+                {
+                    current_class
+                        .super_class
+                        .as_ref()
+                        .ok_or_else(|| anyhow!("{} has no superclass", current_class.name))?
+                        .select_method(&name, &ty)?
+                } else {
+                    (declaring_class, resolved)
+                };
+
+                (class, method, name, ty)
+            } else if let Some(method_ref) = current_class
+                .constant_pool
+                .get_interface_method_ref(cp_index)
+            {
+                let (name, ty) = (method_ref.name, method_ref.ty);
+                let interface = self.resolve_interface(&method_ref.class.name)?;
+
+                // Interface.super.method() calls select the method from the named interface
+                let (class, method) = match interface.get_instance_method(&name, ty.clone()) {
+                    Some(method) if method.is_private() => (interface.as_class().clone(), method),
+                    _ => interface.select_method(&name, &ty)?,
+                };
+
+                (class, method, name, ty)
+            } else {
+                bail!("no methodref at {cp_index}");
+            };
+
+        if method.is_abstract() {
+            throw!(
+                "java/lang/AbstractMethodError",
+                "{}.{name} ({ty:?})",
+                class.name
+            );
+        }
+
+        if thread
+            .peek_operand_stack(ty.parameter_types.len())?
+            .is_null()
+        {
+            throw!(
+                "java/lang/NullPointerException",
+                "invokespecial of {name} on null"
+            );
+        }
+
+        trace!("invokespecial, calling {}:{name} ({ty:?})", class.name);
+        self.jmp_method(thread, class, &method)?;
+
+        Ok(())
+    }
+
+    pub fn invokestatic(&self, thread: &mut JvmThread, cp_index: u16) -> anyhow::Result<()> {
+        trace!("invokestatic");
+
+        let current_class: Class = thread.current_frame()?.current_class.clone();
+
+        let (target_class, name, ty) = current_class
+            .constant_pool
+            .get_method_ref(cp_index)
+            .map(|m| (m.class, m.name, m.ty))
+            .or_else(|| {
+                current_class
+                    .constant_pool
+                    .get_interface_method_ref(cp_index)
+                    .map(|m| (m.class, m.name, m.ty))
+            })
+            .ok_or_else(|| anyhow!("no methodref at {cp_index}"))?;
+
+        self.invoke_static_method(thread, &target_class.name, &name, &ty)
+    }
+
+    pub fn invokevirtual(&self, thread: &mut JvmThread, cp_index: u16) -> anyhow::Result<()> {
+        trace!("invokevirtual");
+
+        let method_ref = thread
+            .current_frame()?
+            .current_class
+            .constant_pool
+            .get_method_ref(cp_index)
+            .ok_or_else(|| anyhow!("no methodref at {cp_index}"))?;
+
+        self.invoke_virtual_method(thread, method_ref)
+    }
+
+    pub fn ior(&self, thread: &mut JvmThread) -> anyhow::Result<()> {
+        trace!("ior");
+
+        let (l, r) = pop_int_pair(thread)?;
+
+        thread.push_operand_stack(RuntimeType::Int(l | r));
+
+        Ok(())
+    }
+
+    pub fn irem(&self, thread: &mut JvmThread) -> anyhow::Result<()> {
+        trace!("irem");
+
+        let (l, r) = pop_int_pair(thread)?;
+
+        if r == 0 {
+            throw!("java/lang/ArithmeticException", "/ by zero");
+        }
+
+        thread.push_operand_stack(RuntimeType::Int(l.wrapping_rem(r)));
+
+        Ok(())
+    }
+
+    pub fn ireturn(&self, thread: &mut JvmThread) -> anyhow::Result<()> {
+        trace!("ireturn");
+
+        let value = pop_int(thread)?;
+
+        self.return_value(thread, RuntimeType::Int(value))?;
+
+        Ok(())
+    }
+
+    pub fn ishl(&self, thread: &mut JvmThread) -> anyhow::Result<()> {
+        trace!("ishl");
+
+        let (l, r) = pop_int_pair(thread)?;
+
+        // Only the 5 lowest bits of the shift distance are used
+        thread.push_operand_stack(RuntimeType::Int(l.wrapping_shl((r & 0x1f) as u32)));
+
+        Ok(())
+    }
+
+    pub fn ishr(&self, thread: &mut JvmThread) -> anyhow::Result<()> {
+        trace!("ishr");
+
+        let (l, r) = pop_int_pair(thread)?;
+
+        thread.push_operand_stack(RuntimeType::Int(l.wrapping_shr((r & 0x1f) as u32)));
+
+        Ok(())
+    }
+
+    pub fn istore(&self, thread: &mut JvmThread, local_index: u16) -> anyhow::Result<()> {
+        trace!("istore {local_index}");
+
+        let local_index = local_index as usize;
+        let value = thread.pop_operand_stack()?;
+
+        // The type of the value is checked by the verifier
+        thread.store_to_local(local_index, value)?;
+        thread.allow_local(local_index + 1)?;
+
+        Ok(())
+    }
+
+    pub fn isub(&self, thread: &mut JvmThread) -> anyhow::Result<()> {
+        trace!("isub");
+
+        let (l, r) = pop_int_pair(thread)?;
+
+        thread.push_operand_stack(RuntimeType::Int(l.wrapping_sub(r)));
+
+        Ok(())
+    }
+
+    pub fn iushr(&self, thread: &mut JvmThread) -> anyhow::Result<()> {
+        trace!("iushr");
+
+        let (l, r) = pop_int_pair(thread)?;
+
+        thread.push_operand_stack(RuntimeType::Int(((l as u32) >> (r & 0x1f)) as JvmInt));
+
+        Ok(())
+    }
+
+    pub fn ixor(&self, thread: &mut JvmThread) -> anyhow::Result<()> {
+        trace!("ixor");
+
+        let (l, r) = pop_int_pair(thread)?;
+
+        thread.push_operand_stack(RuntimeType::Int(l ^ r));
+
+        Ok(())
+    }
+
+    /// The jsr and jsr_w instructions. They are only allowed in class files older than version 51
+    /// (JVMS §4.9.1), which the verifier checks when linking the class.
+    pub fn jsr(&self, thread: &mut JvmThread, op_pc: usize, offset: JvmInt) -> anyhow::Result<()> {
+        trace!("jsr {offset}");
+
+        // The subroutine returns to the instruction following jsr
+        thread.push_operand_stack(RuntimeType::ReturnAddress(thread.pc));
+
+        branch(thread, op_pc, offset)
+    }
+
+    pub fn l2d(&self, thread: &mut JvmThread) -> anyhow::Result<()> {
+        trace!("l2d");
+
+        let value = pop_long(thread)?;
+
+        thread.push_operand_stack(RuntimeType::Double(value as JvmDouble));
+
+        Ok(())
+    }
+
+    pub fn l2f(&self, thread: &mut JvmThread) -> anyhow::Result<()> {
+        trace!("l2f");
+
+        let value = pop_long(thread)?;
+
+        thread.push_operand_stack(RuntimeType::Float(value as JvmFloat));
+
+        Ok(())
+    }
+
+    pub fn l2i(&self, thread: &mut JvmThread) -> anyhow::Result<()> {
+        trace!("l2i");
+
+        let value = pop_long(thread)?;
+
+        thread.push_operand_stack(RuntimeType::Int(value as JvmInt));
+
+        Ok(())
+    }
+
+    pub fn ladd(&self, thread: &mut JvmThread) -> anyhow::Result<()> {
+        trace!("ladd");
+
+        let (l, r) = pop_long_pair(thread)?;
+
+        thread.push_operand_stack(RuntimeType::Long(l.wrapping_add(r)));
+
+        Ok(())
+    }
+
+    pub fn laload(&self, thread: &mut JvmThread) -> anyhow::Result<()> {
+        trace!("laload");
+
+        array_load(thread, |ty| matches!(ty, JvmTypeDescriptor::Long), "long")
+    }
+
+    pub fn land(&self, thread: &mut JvmThread) -> anyhow::Result<()> {
+        trace!("land");
+
+        let (l, r) = pop_long_pair(thread)?;
+
+        thread.push_operand_stack(RuntimeType::Long(l & r));
+
+        Ok(())
+    }
+
+    pub fn lastore(&self, thread: &mut JvmThread) -> anyhow::Result<()> {
+        trace!("lastore");
+
+        array_store(thread, |ty| matches!(ty, JvmTypeDescriptor::Long), "long")
+    }
+
+    pub fn lcmp(&self, thread: &mut JvmThread) -> anyhow::Result<()> {
+        trace!("lcmp");
+
+        let (l, r) = pop_long_pair(thread)?;
+
+        thread.push_operand_stack(RuntimeType::Int(l.cmp(&r) as JvmInt));
+
+        Ok(())
+    }
+
+    pub fn lconst(&self, thread: &mut JvmThread, value: JvmLong) -> anyhow::Result<()> {
+        trace!("lconst {value}");
+
+        thread.push_operand_stack(RuntimeType::Long(value));
+
+        Ok(())
+    }
+
+    pub fn ld2c_w(&self, thread: &mut JvmThread, cp_index: u16) -> anyhow::Result<()> {
+        trace!("ld2c_w");
+
+        let class = thread.current_frame()?.current_class.clone();
+
+        let value = class
+            .constant_pool
+            .get_long(cp_index)
+            .map(RuntimeType::Long)
+            .or_else(|| {
+                class
+                    .constant_pool
+                    .get_double(cp_index)
+                    .map(RuntimeType::Double)
+            });
+
+        let Some(value) = value else {
+            bail!("no long nor double constant at {cp_index} for ldc2_w");
+        };
+
+        thread.push_operand_stack(value);
+
+        Ok(())
+    }
+
+    /// Both ldc and ldc_w
+    pub fn ldc(&self, thread: &mut JvmThread, cp_index: u16) -> anyhow::Result<()> {
+        trace!("ldc {cp_index}");
+
+        let constant = thread
+            .current_frame()?
+            .current_class
+            .constant_pool
+            .get_loadable(cp_index)
+            .ok_or_else(|| anyhow!("no loadable constant at {cp_index}"))?;
+
+        let value = match constant {
+            LoadableJvmConstant::Long(_) | LoadableJvmConstant::Double(_) => {
+                bail!("ldc of the category 2 constant at {cp_index}")
+            }
+            constant => self.load_constant(constant)?,
+        };
+
+        thread.push_operand_stack(value);
+
+        Ok(())
+    }
+
+    pub fn ldiv(&self, thread: &mut JvmThread) -> anyhow::Result<()> {
+        trace!("ldiv");
+
+        let (l, r) = pop_long_pair(thread)?;
+
+        if r == 0 {
+            throw!("java/lang/ArithmeticException", "/ by zero");
+        }
+
+        thread.push_operand_stack(RuntimeType::Long(l.wrapping_div(r)));
+
+        Ok(())
+    }
+
+    pub fn lload(&self, thread: &mut JvmThread, local_index: u16) -> anyhow::Result<()> {
+        trace!("lload");
+
+        let local_index = local_index as usize;
+        let value = thread.read_local(local_index)?;
+        // TODO: check to see if local_index + 1 is forbidden
+
+        match value {
+            RuntimeType::Long(_) => (),
+            v => bail!("unexpected value (long expected): {v:?}"),
+        }
+
+        thread.push_operand_stack(value);
+
+        Ok(())
+    }
+
+    pub fn lmul(&self, thread: &mut JvmThread) -> anyhow::Result<()> {
+        trace!("lmul");
+
+        let (l, r) = pop_long_pair(thread)?;
+
+        thread.push_operand_stack(RuntimeType::Long(l.wrapping_mul(r)));
+
+        Ok(())
+    }
+
+    pub fn lneg(&self, thread: &mut JvmThread) -> anyhow::Result<()> {
+        trace!("lneg");
+
+        let value = pop_long(thread)?;
+
+        thread.push_operand_stack(RuntimeType::Long(value.wrapping_neg()));
+
+        Ok(())
+    }
+
+    pub fn lookupswitch(
+        &self,
+        thread: &mut JvmThread,
+        op_pc: usize,
+        default: JvmInt,
+        pairs: &[(JvmInt, JvmInt)],
+    ) -> anyhow::Result<()> {
+        trace!("lookupswitch ({} pairs)", pairs.len());
+
+        let key = pop_int(thread)?;
+
+        // The pairs are sorted by key, as required by the spec
+        let offset = pairs
+            .binary_search_by_key(&key, |(k, _)| *k)
+            .map(|idx| pairs[idx].1)
+            .unwrap_or(default);
+
+        branch(thread, op_pc, offset)
+    }
+
+    pub fn lor(&self, thread: &mut JvmThread) -> anyhow::Result<()> {
+        trace!("lor");
+
+        let (l, r) = pop_long_pair(thread)?;
+
+        thread.push_operand_stack(RuntimeType::Long(l | r));
+
+        Ok(())
+    }
+
+    pub fn lrem(&self, thread: &mut JvmThread) -> anyhow::Result<()> {
+        trace!("lrem");
+
+        let (l, r) = pop_long_pair(thread)?;
+
+        if r == 0 {
+            throw!("java/lang/ArithmeticException", "/ by zero");
+        }
+
+        thread.push_operand_stack(RuntimeType::Long(l.wrapping_rem(r)));
+
+        Ok(())
+    }
+
+    pub fn lreturn(&self, thread: &mut JvmThread) -> anyhow::Result<()> {
+        trace!("lreturn");
+
+        let value = pop_long(thread)?;
+
+        self.return_value(thread, RuntimeType::Long(value))?;
+
+        Ok(())
+    }
+
+    pub fn lshl(&self, thread: &mut JvmThread) -> anyhow::Result<()> {
+        trace!("lshl");
+
+        let r = pop_int(thread)?;
+        let l = pop_long(thread)?;
+
+        // Only the 6 lowest bits of the shift distance are used
+        thread.push_operand_stack(RuntimeType::Long(l.wrapping_shl((r & 0x3f) as u32)));
+
+        Ok(())
+    }
+
+    pub fn lshr(&self, thread: &mut JvmThread) -> anyhow::Result<()> {
+        trace!("lshr");
+
+        let r = pop_int(thread)?;
+        let l = pop_long(thread)?;
+
+        // Only the 6 lowest bits of the shift distance are used
+        thread.push_operand_stack(RuntimeType::Long(l.wrapping_shr((r & 0x3f) as u32)));
+
+        Ok(())
+    }
+
+    pub fn lstore(&self, thread: &mut JvmThread, local_index: u16) -> anyhow::Result<()> {
+        trace!("lstore");
+
+        let local_index = local_index as usize;
+        let value = pop_long(thread)?;
+
+        thread.store_to_local(local_index, RuntimeType::Long(value))?;
+        thread.forbid_local(local_index + 1)?;
+
+        Ok(())
+    }
+
+    pub fn lsub(&self, thread: &mut JvmThread) -> anyhow::Result<()> {
+        trace!("lsub");
+
+        let (l, r) = pop_long_pair(thread)?;
+
+        thread.push_operand_stack(RuntimeType::Long(l.wrapping_sub(r)));
+
+        Ok(())
+    }
+
+    pub fn lushr(&self, thread: &mut JvmThread) -> anyhow::Result<()> {
+        trace!("lushr");
+
+        let r = pop_int(thread)?;
+        let l = pop_long(thread)?;
+
+        // Only the 6 lowest bits of the shift distance are used
+        thread.push_operand_stack(RuntimeType::Long(((l as u64) >> (r & 0x3f)) as JvmLong));
+
+        Ok(())
+    }
+
+    pub fn lxor(&self, thread: &mut JvmThread) -> anyhow::Result<()> {
+        trace!("lxor");
+
+        let (l, r) = pop_long_pair(thread)?;
+
+        thread.push_operand_stack(RuntimeType::Long(l ^ r));
+
+        Ok(())
+    }
+
+    pub fn monitorenter(&self, thread: &mut JvmThread) -> anyhow::Result<()> {
+        trace!("monitorenter");
+
+        let monitor = self.monitor_of(&pop_reference(thread)?)?;
+
+        monitor.enter();
+        thread.current_frame_mut()?.monitors.push(monitor);
+
+        Ok(())
+    }
+
+    pub fn monitorexit(&self, thread: &mut JvmThread) -> anyhow::Result<()> {
+        trace!("monitorexit");
+
+        let monitor = self.monitor_of(&pop_reference(thread)?)?;
+        let frame = thread.current_frame_mut()?;
+
+        // Only monitors entered in the same frame can be exited (JVMS §2.11.10)
+        let Some(index) = frame
+            .monitors
+            .iter()
+            .rposition(|m| Arc::ptr_eq(m, &monitor))
+        else {
+            throw!(
+                "java/lang/IllegalMonitorStateException",
+                "current thread is not owner"
+            );
+        };
+
+        frame.monitors.remove(index);
+        monitor.exit();
+
+        Ok(())
+    }
+
+    pub fn multianewarray(
+        &self,
+        thread: &mut JvmThread,
+        cp_index: u16,
+        dimensions: u8,
+    ) -> anyhow::Result<()> {
+        trace!("multianewarray {dimensions}");
+
+        let class_ref = thread
+            .current_frame()?
+            .current_class
+            .constant_pool
+            .get_class(cp_index)
+            .ok_or_else(|| anyhow!("no class at {cp_index}"))?;
+
+        let array_type = type_of_class_name(&class_ref.name)?;
+
+        if dimensions == 0 {
+            bail!("multianewarray with no dimension");
+        }
+
+        let mut counts = (0..dimensions)
+            .map(|_| pop_array_size(thread))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        // The count of the first dimension is the deepest in the operand stack
+        counts.reverse();
+
+        let array = self.new_multi_array(&array_type, &counts)?;
+
+        thread.push_operand_stack(RuntimeType::Array(array));
+
+        Ok(())
+    }
+
+    /// The new instruction (renamed as new is expected to build Self)
+    pub fn new_object(&self, thread: &mut JvmThread, cp_index: u16) -> anyhow::Result<()> {
+        trace!("new");
+
+        let class_ref = thread
+            .current_frame()?
+            .current_class
+            .constant_pool
+            .get_class(cp_index)
+            .ok_or_else(|| anyhow!("no class at {cp_index}"))?;
+
+        let class = self.resolve_class(&class_ref.name).context("new")?;
+
+        if class.is_abstract() {
+            throw!("java/lang/InstantiationError", "{}", class.name);
+        }
+
+        self.init_static(thread, &class)?;
+
+        let object = self.env.heap.new_object(class);
+
+        thread.push_operand_stack(RuntimeType::Class(object));
+
+        Ok(())
+    }
+
+    pub fn newarray(&self, thread: &mut JvmThread, atype: u8) -> anyhow::Result<()> {
+        trace!("newarray {atype}");
+
+        let compound_type = match atype {
+            4 => JvmTypeDescriptor::Boolean,
+            5 => JvmTypeDescriptor::Char,
+            6 => JvmTypeDescriptor::Float,
+            7 => JvmTypeDescriptor::Double,
+            8 => JvmTypeDescriptor::Byte,
+            9 => JvmTypeDescriptor::Short,
+            10 => JvmTypeDescriptor::Int,
+            11 => JvmTypeDescriptor::Long,
+            v => bail!("unknown array type for newarray: {v}"),
+        };
+
+        let count = pop_array_size(thread)?;
+
+        let array = self.env.heap.new_array(compound_type, count);
+
+        thread.push_operand_stack(RuntimeType::Array(array));
+
+        Ok(())
+    }
+
+    pub fn nop(&self) -> anyhow::Result<()> {
+        trace!("nop");
+
+        Ok(())
+    }
+
+    pub fn pop(&self, thread: &mut JvmThread) -> anyhow::Result<()> {
+        trace!("pop");
+
+        pop_slots(thread, 1)
+    }
+
+    pub fn pop2(&self, thread: &mut JvmThread) -> anyhow::Result<()> {
+        trace!("pop2");
+
+        pop_slots(thread, 2)
+    }
+
+    pub fn putfield(&self, thread: &mut JvmThread, cp_index: u16) -> anyhow::Result<()> {
+        trace!("putfield");
+
+        let frame = thread.current_frame()?;
+        let current_class = frame.current_class.clone();
+        let in_initializer = frame.method.name().as_str() == "<init>";
+
+        let field_ref = current_class
+            .constant_pool
+            .get_field_ref(cp_index)
+            .ok_or_else(|| anyhow!("no field_ref at {cp_index}"))?;
+
+        let (owner, field) = self
+            .resolve_class(&field_ref.class.name)?
+            .resolve_instance_field(&field_ref.name)
+            .ok_or_else(|| {
+                JavaException::new(
+                    "java/lang/NoSuchFieldError",
+                    format!("{}.{}", field_ref.class.name, field_ref.name),
+                )
+            })?;
+
+        // Final fields can only be set by the initialization methods of their class
+        if field.is_final && (owner.name != current_class.name || !in_initializer) {
+            throw!(
+                "java/lang/IllegalAccessError",
+                "final field {}.{} set from {}",
+                owner.name,
+                field.name,
+                current_class.name
+            );
+        }
+
+        let value = thread.pop_operand_stack()?.store_as(&field_ref.ty)?;
+
+        // Strings represented natively do not cache their hash code, the only field of
+        // java/lang/String written once the string is created
+        if owner.name.as_str() == "java/lang/String"
+            && thread.peek_operand_stack(0)?.as_native_string().is_some()
+        {
+            thread.pop_operand_stack()?;
+
+            return Ok(());
+        }
+
+        let object = pop_object(thread)?;
+
+        object.write_field(&owner.name, &field_ref.name, value)?;
+
+        Ok(())
+    }
+
+    pub fn putstatic(&self, thread: &mut JvmThread, cp_index: u16) -> anyhow::Result<()> {
+        trace!("putstatic");
+
+        let current_class = thread.current_frame()?.current_class.clone();
+
+        let field_ref = current_class
+            .constant_pool
+            .get_field_ref(cp_index)
+            .ok_or_else(|| anyhow!("no field_ref at {cp_index}"))?;
+
+        let (owner, _) = self.resolve_static_field(&field_ref).context("putstatic")?;
+
+        self.init_static(thread, &owner)?;
+
+        let value = thread.pop_operand_stack()?;
+
+        owner.write_static(self.env, thread.current_frame()?, &field_ref.name, value)?;
+
+        Ok(())
+    }
+
+    pub fn ret(&self, thread: &mut JvmThread, local_index: u16) -> anyhow::Result<()> {
+        trace!("ret {local_index}");
+
+        let value = match thread.read_local(local_index as usize)? {
+            RuntimeType::ReturnAddress(v) => v,
+            v => bail!("expected return address, got {v:?}"),
+        };
+
+        thread.jmp_to(value);
+
+        Ok(())
+    }
+
+    pub fn saload(&self, thread: &mut JvmThread) -> anyhow::Result<()> {
+        trace!("saload");
+
+        array_load(thread, |ty| matches!(ty, JvmTypeDescriptor::Short), "short")
+    }
+
+    pub fn sastore(&self, thread: &mut JvmThread) -> anyhow::Result<()> {
+        trace!("sastore");
+
+        array_store(thread, |ty| matches!(ty, JvmTypeDescriptor::Short), "short")
+    }
+
+    pub fn sipush(&self, thread: &mut JvmThread, value: JvmInt) -> anyhow::Result<()> {
+        trace!("sipush {value}");
+
+        thread.push_operand_stack(RuntimeType::Int(value));
+
+        Ok(())
+    }
+
+    pub fn swap(&self, thread: &mut JvmThread) -> anyhow::Result<()> {
+        trace!("swap");
+
+        let value1 = thread.pop_operand_stack()?;
+        let value2 = thread.pop_operand_stack()?;
+
+        if value1.is_two_slots() || value2.is_two_slots() {
+            bail!("swap of category 2 values: {value2:?}, {value1:?}");
+        }
+
+        thread.push_operand_stack(value1);
+        thread.push_operand_stack(value2);
+
+        Ok(())
+    }
+
+    pub fn tableswitch(
+        &self,
+        thread: &mut JvmThread,
+        op_pc: usize,
+        default: JvmInt,
+        low: JvmInt,
+        offsets: &[JvmInt],
+    ) -> anyhow::Result<()> {
+        trace!("tableswitch (low: {low}, {} offsets)", offsets.len());
+
+        let index = pop_int(thread)?;
+
+        let offset = (index as JvmLong - low as JvmLong)
+            .try_into()
+            .ok()
+            .and_then(|idx: usize| offsets.get(idx))
+            .copied()
+            .unwrap_or(default);
+
+        branch(thread, op_pc, offset)
+    }
+
+    pub fn vreturn(&self, thread: &mut JvmThread) -> anyhow::Result<()> {
+        trace!("vreturn");
+
+        if let Some(ty) = thread.current_frame()?.method.ret_type() {
+            bail!("return from a method returning {ty:?}");
+        }
+
+        thread.ret()?;
+
+        Ok(())
+    }
+
+    /// Allocates an array of the given type with its first dimensions initialized
+    /// (one per count)
+    fn new_multi_array(
+        &self,
+        array_type: &JvmTypeDescriptor,
+        counts: &[JvmInt],
+    ) -> anyhow::Result<ArrayRef> {
+        let JvmTypeDescriptor::Array(compound_type) = array_type else {
+            bail!("too many dimensions for an array of {array_type:?}");
+        };
+
+        let Some((&count, next_counts)) = counts.split_first() else {
+            bail!("no count for an array of {array_type:?}");
+        };
+
+        if next_counts.is_empty() {
+            return Ok(self
+                .env
+                .heap
+                .new_array(compound_type.as_ref().clone(), count));
+        }
+
+        let values = (0..count)
+            .map(|_| {
+                self.new_multi_array(compound_type, next_counts)
+                    .map(RuntimeType::Array)
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        Ok(self
+            .env
+            .heap
+            .new_array_with(compound_type.as_ref().clone(), values))
+    }
+
+    /// Links an invokedynamic call site by running its bootstrap method (JVMS §5.4.3.6), the
+    /// invoking thread being suspended
+    ///
+    /// Only the method handles loaded as constants are supported as targets of the returned
+    /// CallSite: the ones built by MethodHandles.Lookup or the combinators of MethodHandles are
+    /// objects of java.lang.invoke, which cannot be invoked natively.
+    fn link_call_site(
+        &self,
+        class: &Class,
+        dynamic_invoke: &DynamicInvoke,
+    ) -> anyhow::Result<CallSiteTarget> {
+        let bootstrap = class
+            .constant_pool
+            .get_bootstrap_method(dynamic_invoke.bootstrap_method_attr_index)
+            .ok_or_else(|| {
+                anyhow!(
+                    "no bootstrap method at {} in {}",
+                    dynamic_invoke.bootstrap_method_attr_index,
+                    class.name
+                )
+            })?;
+
+        let ConstantMethodHandle::InvokeStatic(Either::Left(method_ref)) =
+            &bootstrap.bootstrap_method_ref
+        else {
+            bail!(
+                "unsupported bootstrap method handle {:?}",
+                bootstrap.bootstrap_method_ref
+            );
+        };
+
+        if method_ref.class.name.as_str() == "java/lang/invoke/StringConcatFactory" {
+            return CallSiteTarget::string_concat(
+                &method_ref.name,
+                &dynamic_invoke.ty,
+                bootstrap.bootstrap_arguments,
+            );
+        }
+
+        if method_ref.class.name.as_str() == "java/lang/invoke/LambdaMetafactory" {
+            return self.link_lambda(
+                class,
+                &method_ref.name,
+                dynamic_invoke,
+                bootstrap.bootstrap_arguments,
+            );
+        }
+
+        let bootstrap_class = self.resolve_class(&method_ref.class.name)?;
+        let method = bootstrap_class
+            .get_static_method(&method_ref.name, method_ref.ty.clone())
+            .ok_or_else(|| {
+                JavaException::new(
+                    "java/lang/NoSuchMethodError",
+                    format!(
+                        "{}.{} ({:?})",
+                        bootstrap_class.name, method_ref.name, method_ref.ty
+                    ),
+                )
+            })?;
+
+        // The bootstrap class is initialized before allocating the arguments, which are only
+        // held here until the invocation
+        JvmThread::initialize_class(self.env, &bootstrap_class)?;
+
+        let mut args = vec![
+            self.new_lookup(class)?,
+            RuntimeType::InternedString(dynamic_invoke.name.clone()),
+            RuntimeType::MethodType(Arc::new(dynamic_invoke.ty.clone())),
+        ];
+
+        for constant in bootstrap.bootstrap_arguments {
+            args.push(self.load_constant(constant)?);
+        }
+
+        let args = self.pack_varargs(method.parameters(), args)?;
+
+        debug!(
+            "linking call site {} with {}:{}",
+            dynamic_invoke.name, bootstrap_class.name, method_ref.name
+        );
+
+        let call_site = match JvmThread::invoke(self.env, bootstrap_class, &method, args) {
+            Ok(call_site) => call_site,
+            Err(err) => {
+                let exception = err.downcast::<JavaException>()?.into_object(self.env)?;
+
+                // Errors are rethrown as is, other exceptions are wrapped
+                if exception
+                    .get()
+                    .is_some_and(|e| e.class_type.is_assignable_to("java/lang/Error"))
+                {
+                    return Err(JavaException::Thrown(exception).into());
+                }
+
+                throw!(
+                    "java/lang/BootstrapMethodError",
+                    "call site initialization exception: {}",
+                    JavaException::Thrown(exception)
+                );
+            }
+        };
+
+        let call_site = match call_site {
+            Some(RuntimeType::Class(object)) => object.get(),
+            _ => None,
+        }
+        .filter(|c| c.class_type.is_assignable_to("java/lang/invoke/CallSite"))
+        .ok_or_else(|| {
+            JavaException::new(
+                "java/lang/BootstrapMethodError",
+                format!(
+                    "bootstrap method of {} returned no CallSite",
+                    dynamic_invoke.name
+                ),
+            )
+        })?;
+
+        match call_site.read_field("java/lang/invoke/CallSite", "target")? {
+            RuntimeType::MethodHandle(handle) => Ok(CallSiteTarget::MethodHandle(handle)),
+            v if v.is_null() => throw!(
+                "java/lang/BootstrapMethodError",
+                "CallSite of {} returned by its bootstrap method has no target",
+                dynamic_invoke.name
+            ),
+            _ => throw!(
+                "java/lang/BootstrapMethodError",
+                "unsupported target of the CallSite of {}: only method handles loaded as \
+                constants are supported",
+                dynamic_invoke.name
+            ),
+        }
+    }
+
+    /// Links a call site bootstrapped by a method of java/lang/invoke/LambdaMetafactory, creating
+    /// the synthetic class of its lambdas instead of running the bootstrap method
+    fn link_lambda(
+        &self,
+        caller: &Class,
+        bootstrap_name: &str,
+        dynamic_invoke: &DynamicInvoke,
+        bootstrap_arguments: Vec<LoadableJvmConstant>,
+    ) -> anyhow::Result<CallSiteTarget> {
+        let mut arguments = bootstrap_arguments.into_iter();
+
+        let (
+            Some(LoadableJvmConstant::MethodType {
+                descriptor: method_type,
+            }),
+            Some(LoadableJvmConstant::MethodHandle(implementation)),
+            Some(LoadableJvmConstant::MethodType {
+                descriptor: instantiated_type,
+            }),
+        ) = (arguments.next(), arguments.next(), arguments.next())
+        else {
+            throw!(
+                "java/lang/BootstrapMethodError",
+                "invalid arguments given to LambdaMetafactory.{bootstrap_name}"
+            );
+        };
+
+        let Some(JvmTypeDescriptor::Class(interface_name)) = &dynamic_invoke.ty.return_type else {
+            throw!(
+                "java/lang/BootstrapMethodError",
+                "lambda call site {} does not return an interface",
+                dynamic_invoke.name
+            );
+        };
+
+        let mut interfaces = vec![self.resolve_interface(interface_name)?];
+        let mut method_types = vec![method_type];
+
+        match bootstrap_name {
+            "metafactory" => (),
+            "altMetafactory" => {
+                let Some(LoadableJvmConstant::Integer(flags)) = arguments.next() else {
+                    throw!(
+                        "java/lang/BootstrapMethodError",
+                        "no flags given to LambdaMetafactory.altMetafactory"
+                    );
+                };
+
+                if flags & LAMBDA_FLAG_SERIALIZABLE != 0 {
+                    interfaces.push(self.resolve_interface("java/io/Serializable")?);
+                }
+
+                // Marker interfaces then bridges are given as a count followed by the values
+                for flag in [LAMBDA_FLAG_MARKERS, LAMBDA_FLAG_BRIDGES] {
+                    if flags & flag == 0 {
+                        continue;
+                    }
+
+                    let count = match arguments.next() {
+                        Some(LoadableJvmConstant::Integer(count)) => count,
+                        _ => throw!(
+                            "java/lang/BootstrapMethodError",
+                            "no count given to LambdaMetafactory.altMetafactory"
+                        ),
+                    };
+
+                    for _ in 0..count {
+                        match (flag, arguments.next()) {
+                            (LAMBDA_FLAG_MARKERS, Some(LoadableJvmConstant::Class(marker))) => {
+                                interfaces.push(self.resolve_interface(&marker.name)?)
+                            }
+                            (
+                                LAMBDA_FLAG_BRIDGES,
+                                Some(LoadableJvmConstant::MethodType { descriptor }),
+                            ) => method_types.push(descriptor),
+                            (_, v) => throw!(
+                                "java/lang/BootstrapMethodError",
+                                "invalid argument given to LambdaMetafactory.altMetafactory: \
+                                {v:?}"
+                            ),
+                        }
+                    }
+                }
+            }
+            v => throw!(
+                "java/lang/BootstrapMethodError",
+                "unknown LambdaMetafactory bootstrap method {v}"
+            ),
+        }
+
+        // The captured arguments then the arguments of the interface method are converted to the
+        // parameter types of the implementation, and its result to the instantiated return type
+        let implementation_type = method_handle_type(&implementation)?;
+        let parameter_types = dynamic_invoke
+            .ty
+            .parameter_types
+            .iter()
+            .chain(&instantiated_type.parameter_types)
+            .collect::<Vec<_>>();
+
+        let parameter_adaptations = parameter_types
+            .iter()
+            .zip(&implementation_type.parameter_types)
+            .map(|(from, to)| adaptation(from, to))
+            .collect::<Option<Arc<[_]>>>()
+            .filter(|_| parameter_types.len() == implementation_type.parameter_types.len());
+
+        let return_adaptation = match (
+            &implementation_type.return_type,
+            &instantiated_type.return_type,
+        ) {
+            (None, None) => Some(Adaptation::Identity),
+            // The object built by a constructor is pushed before it runs, and is not converted
+            (Some(from), Some(to)) => adaptation(from, to).filter(|adaptation| {
+                *adaptation == Adaptation::Identity
+                    || !matches!(implementation, ConstantMethodHandle::NewInvokeSpecial(_))
+            }),
+            _ => None,
+        };
+
+        let (Some(parameter_adaptations), Some(return_adaptation)) =
+            (parameter_adaptations, return_adaptation)
+        else {
+            throw!(
+                "java/lang/BootstrapMethodError",
+                "unsupported adaptation of {implementation_type:?} to {instantiated_type:?} \
+                for lambda call site {}",
+                dynamic_invoke.name
+            );
+        };
+
+        let object = self.resolve_class("java/lang/Object")?;
+        let name = format!(
+            "{}$$Lambda${}",
+            caller.name,
+            self.env
+                .synthetic_class_count
+                .fetch_add(1, atomic::Ordering::Relaxed)
+        );
+
+        debug!("linking call site {} with {name}", dynamic_invoke.name);
+
+        let class = new_lambda_class(
+            name.clone(),
+            caller.major_version,
+            object,
+            LambdaShape {
+                interfaces,
+                captured_types: &dynamic_invoke.ty.parameter_types,
+                method_name: &dynamic_invoke.name,
+                method_types,
+            },
+            Arc::new(implementation),
+            LambdaAdaptations {
+                parameters: parameter_adaptations,
+                ret: return_adaptation,
+            },
+        );
+
+        self.env.classes.write().insert(name, class.clone());
+
+        Ok(CallSiteTarget::Lambda(class))
+    }
+
+    /// Creates an instance of the synthetic class of a lambda, capturing the arguments on the
+    /// operand stack
+    fn new_lambda(
+        &self,
+        thread: &mut JvmThread,
+        class: Class,
+        ty: &JvmMethodDescriptor,
+    ) -> anyhow::Result<()> {
+        let captured = pop_values(thread, ty.parameter_types.len())?;
+        let lambda = self.env.heap.new_object(class.clone());
+
+        let instance = lambda
+            .get()
+            .ok_or_else(|| anyhow!("lambda collected right after its allocation"))?;
+
+        for (i, value) in captured.into_iter().enumerate() {
+            instance.write_field(&class.name, &format!("arg${}", i + 1), value)?;
+        }
+
+        thread.push_operand_stack(RuntimeType::Class(lambda));
+
+        Ok(())
+    }
+
+    /// Evaluates a string concatenation recipe, its arguments being on the operand stack
+    fn concat_strings(
+        &self,
+        thread: &mut JvmThread,
+        recipe: &str,
+        constants: &[String],
+        ty: &JvmMethodDescriptor,
+    ) -> anyhow::Result<()> {
+        // Objects are converted by their toString method, run while the thread is suspended
+        let has_objects = (0..ty.parameter_types.len())
+            .any(|depth| matches!(thread.peek_operand_stack(depth), Ok(RuntimeType::Class(_))));
+        let _suspension = has_objects.then(|| thread.suspend(self.env));
+
+        let mut args = ty
+            .parameter_types
+            .iter()
+            .map(|_| thread.pop_operand_stack())
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        args.reverse();
+
+        let mut args = args.into_iter().zip(ty.parameter_types.iter());
+        let mut constants = constants.iter();
+        let mut result = String::new();
+
+        for c in recipe.chars() {
+            match c {
+                CONCAT_ARGUMENT_TAG => {
+                    let (value, ty) = args
+                        .next()
+                        .ok_or_else(|| anyhow!("missing argument for recipe {recipe:?}"))?;
+
+                    result.push_str(&self.stringify(value, ty)?);
+                }
+                CONCAT_CONSTANT_TAG => result.push_str(
+                    constants
+                        .next()
+                        .ok_or_else(|| anyhow!("missing constant for recipe {recipe:?}"))?,
+                ),
+                c => result.push(c),
+            }
+        }
+
+        thread.push_operand_stack(RuntimeType::String(Arc::new(result)));
+
+        Ok(())
+    }
+
+    /// Converts a value of the given type to a string, as String.valueOf does
+    fn stringify(&self, value: RuntimeType, ty: &JvmTypeDescriptor) -> anyhow::Result<String> {
+        Ok(match (ty, value) {
+            (JvmTypeDescriptor::Boolean, RuntimeType::Int(v)) => (v != 0).to_string(),
+            (JvmTypeDescriptor::Char, RuntimeType::Int(v)) => char::from_u32(v as u16 as u32)
+                .unwrap_or(char::REPLACEMENT_CHARACTER)
+                .to_string(),
+            (_, v) if v.is_null() => String::from("null"),
+            (_, RuntimeType::InternedString(v) | RuntimeType::String(v)) => v.to_string(),
+            (_, RuntimeType::Class(object)) => self.object_to_string(object)?,
+            (_, RuntimeType::Array(array)) => format!(
+                "{}@{:x}",
+                type_of_reference(&RuntimeType::Array(array.clone()))?.binary_name(),
+                array.identity_hash()
+            ),
+            (_, RuntimeType::ClassMirror(ty)) => match ty.as_ref() {
+                JvmTypeDescriptor::Class(name) if self.env.get_interface(name).is_some() => {
+                    format!("interface {}", ty.binary_name())
+                }
+                JvmTypeDescriptor::Class(_) | JvmTypeDescriptor::Array(_) => {
+                    format!("class {}", ty.binary_name())
+                }
+                primitive => primitive_type_name(primitive)?.to_string(),
+            },
+            (_, v) => java_string_of(&v)
+                .ok_or_else(|| anyhow!("unsupported string conversion of {v:?}"))?,
+        })
+    }
+
+    /// Calls the toString method of an object. Strings, boxed primitives and the default
+    /// implementation of java/lang/Object are converted natively.
+    fn object_to_string(&self, object: ObjectRef) -> anyhow::Result<String> {
+        let instance = object
+            .get()
+            .ok_or_else(|| anyhow!("string conversion of a collected object"))?;
+        let class = instance.class_type.clone();
+
+        if class.name.as_str() == "java/lang/String" {
+            return string_of_object(&instance);
+        }
+
+        if let Some(ty) = boxed_primitive_type(&class.name) {
+            return self.stringify(instance.read_field(&class.name, "value")?, &ty);
+        }
+
+        let (declaring_class, method) = class.select_method(
+            "toString",
+            &JvmMethodDescriptor {
+                parameter_types: vec![],
+                return_type: Some(JvmTypeDescriptor::Class(String::from("java/lang/String"))),
+            },
+        )?;
+
+        if declaring_class.name.as_str() == "java/lang/Object" {
+            return Ok(format!(
+                "{}@{:x}",
+                class.name.replace('/', "."),
+                object.identity_hash()
+            ));
+        }
+
+        match JvmThread::invoke(
+            self.env,
+            declaring_class,
+            &method,
+            vec![RuntimeType::Class(object)],
+        )? {
+            Some(v) if v.is_null() => Ok(String::from("null")),
+            Some(RuntimeType::Class(string)) => self.object_to_string(string),
+            Some(v) => match v.as_native_string() {
+                Some(string) => Ok(string.to_string()),
+                None => bail!("unsupported result of toString: {v:?}"),
+            },
+            None => bail!("toString returned no value"),
+        }
+    }
+
+    /// A field of java/lang/String for a string represented natively, encoded as read back by
+    /// string_of_object: LATIN1 when every character fits in a byte, UTF16 otherwise
+    fn native_string_field(&self, string: &str, name: &str) -> anyhow::Result<RuntimeType> {
+        let is_latin1 = string.chars().all(|c| u32::from(c) <= 0xff);
+
+        Ok(match name {
+            "value" => {
+                let bytes = if is_latin1 {
+                    string.chars().map(|c| c as u8).collect::<Vec<_>>()
+                } else {
+                    string.encode_utf16().flat_map(u16::to_ne_bytes).collect()
+                };
+
+                RuntimeType::Array(
+                    self.env.heap.new_array_with(
+                        JvmTypeDescriptor::Byte,
+                        bytes
+                            .into_iter()
+                            .map(|b| RuntimeType::Int(b as i8 as JvmInt))
+                            .collect(),
+                    ),
+                )
+            }
+            "coder" => RuntimeType::Int(if is_latin1 { 0 } else { 1 }),
+            // The hash code is computed again on every call
+            "hash" | "hashIsZero" => RuntimeType::Int(0),
+            name => bail!("no field {name} in java/lang/String"),
+        })
+    }
+
+    /// Invokes a method handle, its arguments (and receiver) being on the operand stack
+    fn invoke_method_handle(
+        &self,
+        thread: &mut JvmThread,
+        handle: &ConstantMethodHandle,
+    ) -> anyhow::Result<()> {
+        match handle {
+            ConstantMethodHandle::InvokeStatic(Either::Left(m)) => {
+                self.invoke_static_method(thread, &m.class.name, &m.name, &m.ty)
+            }
+            ConstantMethodHandle::InvokeStatic(Either::Right(m)) => {
+                self.invoke_static_method(thread, &m.class.name, &m.name, &m.ty)
+            }
+            ConstantMethodHandle::InvokeVirtual(m) => self.invoke_virtual_method(thread, m.clone()),
+            ConstantMethodHandle::InvokeInterface(m) => {
+                self.invoke_interface_method(thread, m.clone())
+            }
+            ConstantMethodHandle::InvokeSpecial(m) => {
+                let (class_name, name, ty) = m.as_ref().either(
+                    |m| (&m.class.name, &m.name, &m.ty),
+                    |m| (&m.class.name, &m.name, &m.ty),
+                );
+
+                let class = self
+                    .env
+                    .get_class_or_interface(class_name)
+                    .ok_or_else(|| anyhow!("no class found for {class_name}"))?;
+                let (class, method) = class
+                    .find_instance_method(name, ty)
+                    .ok_or_else(|| no_instance_method_error(&class, name, ty))?;
+
+                thread.jmp_jvm_method(class, &method)
+            }
+            ConstantMethodHandle::NewInvokeSpecial(m) => {
+                let class = self.resolve_class(&m.class.name)?;
+
+                if class.is_abstract() {
+                    throw!("java/lang/InstantiationError", "{}", class.name);
+                }
+
+                let method = class
+                    .get_instance_method("<init>", m.ty.clone())
+                    .ok_or_else(|| no_instance_method_error(&class, "<init>", &m.ty))?;
+
+                self.init_static(thread, &class)?;
+
+                // The object is left on the operand stack once initialized
+                let args = pop_values(thread, m.ty.parameter_types.len())?;
+                let object = RuntimeType::Class(self.env.heap.new_object(class.clone()));
+
+                thread.push_operand_stack(object.clone());
+                thread.push_operand_stack(object);
+
+                for arg in args {
+                    thread.push_operand_stack(arg);
+                }
+
+                thread.jmp_jvm_method(class, &method)
+            }
+            handle => bail!("invocation of method handle {handle:?} is not supported"),
+        }
+    }
+
+    /// Creates the MethodHandles.Lookup object given to bootstrap methods. As for exceptions
+    /// raised by the interpreter, its constructor is not run: only its lookup class is set.
+    fn new_lookup(&self, class: &Class) -> anyhow::Result<RuntimeType> {
+        let lookup_class = self.resolve_class("java/lang/invoke/MethodHandles$Lookup")?;
+        let lookup = self.env.heap.new_object(lookup_class);
+
+        lookup
+            .get()
+            .ok_or_else(|| anyhow!("lookup collected right after its allocation"))?
+            .write_field(
+                "java/lang/invoke/MethodHandles$Lookup",
+                "lookupClass",
+                RuntimeType::ClassMirror(Arc::new(JvmTypeDescriptor::Class(
+                    class.name.to_string(),
+                ))),
+            )?;
+
+        Ok(RuntimeType::Class(lookup))
+    }
+
+    /// Checks the arguments of a method, packing the trailing ones into an array when the
+    /// method has a variable arity (its last parameter being an array not directly given)
+    fn pack_varargs(
+        &self,
+        parameters: &[JvmTypeDescriptor],
+        mut args: Vec<RuntimeType>,
+    ) -> anyhow::Result<Vec<RuntimeType>> {
+        if let Some(JvmTypeDescriptor::Array(component)) = parameters.last() {
+            let is_packed = args.len() == parameters.len()
+                && args
+                    .last()
+                    .is_some_and(|v| matches!(v, RuntimeType::Array(_)));
+
+            if !is_packed && args.len() + 1 >= parameters.len() {
+                let trailing = args
+                    .split_off(parameters.len() - 1)
+                    .into_iter()
+                    .map(|v| v.store_as(component))
+                    .collect::<anyhow::Result<Vec<_>>>()?;
+
+                args.push(RuntimeType::Array(
+                    self.env
+                        .heap
+                        .new_array_with(component.as_ref().clone(), trailing),
+                ));
+            }
+        }
+
+        if args.len() != parameters.len() {
+            bail!(
+                "{} arguments given to a method taking {}",
+                args.len(),
+                parameters.len()
+            );
+        }
+
+        args.into_iter()
+            .zip(parameters)
+            .map(|(v, ty)| v.store_as(ty))
+            .collect()
+    }
+
+    /// Invokes an interface method selected from the class of the receiver, its receiver and
+    /// arguments being on the operand stack
+    fn invoke_interface_method(
+        &self,
+        thread: &mut JvmThread,
+        method_ref: ConstantInterfaceMethodref,
+    ) -> anyhow::Result<()> {
+        let (name, ty) = (method_ref.name.clone(), method_ref.ty.clone());
+
+        if self.env.get_class(&method_ref.class.name).is_some() {
+            throw!(
+                "java/lang/IncompatibleClassChangeError",
+                "{} is not an interface",
+                method_ref.class.name
+            );
+        }
+
+        let interface = self.resolve_interface(&method_ref.class.name)?;
+
+        // Interface methods are looked up in the interface, then in java/lang/Object, then in
+        // the superinterfaces (JVMS §5.4.3.4)
+        let resolved = interface
+            .get_instance_method(&name, ty.clone())
+            .or_else(|| {
+                self.env
+                    .get_class("java/lang/Object")
+                    .and_then(|object| object.get_instance_method(&name, ty.clone()))
+                    .filter(|m| !m.is_private())
+            })
+            .or_else(|| {
+                interface
+                    .maximally_specific_methods(&name, &ty)
+                    .into_iter()
+                    .next()
+                    .map(|(_, m)| m)
+            })
+            .ok_or_else(|| {
+                JavaException::new(
+                    "java/lang/NoSuchMethodError",
+                    format!("{}.{name} ({ty:?})", interface.name),
+                )
+            })?;
+
+        let receiver_class =
+            self.receiver_class(thread.peek_operand_stack(ty.parameter_types.len())?)?;
+
+        if !receiver_class.implements(&interface.name) {
+            throw!(
+                "java/lang/IncompatibleClassChangeError",
+                "{} does not implement {}",
+                receiver_class.name,
+                interface.name
+            );
+        }
+
+        let (class, method) = if resolved.is_private() {
+            (interface.as_class().clone(), resolved)
+        } else {
+            receiver_class.select_method(&name, &ty)?
+        };
+
+        trace!("invokeinterface, calling {}:{name} ({ty:?})", class.name);
+        self.jmp_method(thread, class, &method)?;
+
+        Ok(())
+    }
+
+    /// Invokes a static method, its arguments being on the operand stack
+    fn invoke_static_method(
+        &self,
+        thread: &mut JvmThread,
+        class_name: &str,
+        name: &str,
+        ty: &JvmMethodDescriptor,
+    ) -> anyhow::Result<()> {
+        if class_name == "java/lang/System" && self.invoke_system_native(thread, name, ty)? {
+            return Ok(());
+        }
+
+        let target_class = self.resolve_class(class_name)?;
+        let method = target_class
+            .get_static_method(name, ty.clone())
+            .ok_or_else(|| {
+                anyhow!(
+                    "no method {ty:?} named {name} found in {}",
+                    target_class.name
+                )
+            })?;
+
+        self.init_static(thread, &target_class)?;
+
+        if method.is_native() && self.invoke_builtin_native(thread, &target_class, &method)? {
+            return Ok(());
+        }
+
+        trace!(
+            "invokestatic, calling {}:{name} ({ty:?}) (native: {})",
+            target_class.name,
+            method.is_native()
+        );
+        thread.jmp_jvm_method(target_class.clone(), &method)?;
+
+        Ok(())
+    }
+
+    /// Evaluates natively the static methods of java/lang/System provided by the launcher, as
+    /// System cannot be initialized without its natives. Returns whether the method was one of
+    /// them.
+    fn invoke_system_native(
+        &self,
+        thread: &mut JvmThread,
+        name: &str,
+        ty: &JvmMethodDescriptor,
+    ) -> anyhow::Result<bool> {
+        let is_string = |ty: &JvmTypeDescriptor| matches!(ty, JvmTypeDescriptor::Class(name) if name == "java/lang/String");
+        let property = |key: &str| {
+            self.env
+                .system_properties
+                .get(key)
+                .map(|v| RuntimeType::InternedString(Arc::new(v.clone())))
+        };
+
+        match (name, ty.parameter_types.as_slice()) {
+            ("exit", [JvmTypeDescriptor::Int]) => {
+                let status = pop_int(thread)?;
+
+                debug!("System.exit({status})");
+
+                return Err(ProgramExit { status }.into());
+            }
+            ("getProperty", [key]) if is_string(key) => {
+                let key = pop_property_key(thread)?;
+
+                thread.push_operand_stack(
+                    property(&key).unwrap_or(RuntimeType::Class(ObjectRef::new_null())),
+                );
+            }
+            ("getProperty", [key, default]) if is_string(key) && is_string(default) => {
+                let default = pop_reference(thread)?;
+                let key = pop_property_key(thread)?;
+
+                thread.push_operand_stack(property(&key).unwrap_or(default));
+            }
+            (
+                "arraycopy",
+                [
+                    _,
+                    JvmTypeDescriptor::Int,
+                    _,
+                    JvmTypeDescriptor::Int,
+                    JvmTypeDescriptor::Int,
+                ],
+            ) => {
+                let length = pop_int(thread)?;
+                let dest_pos = pop_int(thread)?;
+                let dest = pop_reference(thread)?;
+                let src_pos = pop_int(thread)?;
+                let src = pop_reference(thread)?;
+
+                self.arraycopy(&src, src_pos, &dest, dest_pos, length)?;
+            }
+            ("lineSeparator", []) => {
+                thread.push_operand_stack(
+                    property("line.separator")
+                        .unwrap_or_else(|| RuntimeType::InternedString(Arc::new("\n".into()))),
+                );
+            }
+            _ => return Ok(false),
+        }
+
+        Ok(true)
+    }
+
+    /// Evaluates natively the native methods of the JDK run by the constructors of exceptions,
+    /// the methods of String and the initializers of the classes they use, the receiver and
+    /// arguments being on the operand stack. Returns whether the method was one of them.
+    fn invoke_builtin_native(
+        &self,
+        thread: &mut JvmThread,
+        class: &Class,
+        method: &Method,
+    ) -> anyhow::Result<bool> {
+        match (
+            class.name.as_str(),
+            method.name().as_str(),
+            method.parameters(),
+        ) {
+            // The natives are evaluated here instead of being registered
+            (_, "registerNatives", []) => {}
+            ("java/lang/Object", "getClass", []) => {
+                let receiver = pop_reference(thread)?;
+
+                thread.push_operand_stack(RuntimeType::ClassMirror(Arc::new(type_of_reference(
+                    &receiver,
+                )?)));
+            }
+            // No stack trace is recorded, the receiver being left on the operand stack as the
+            // result
+            ("java/lang/Throwable", "fillInStackTrace", [JvmTypeDescriptor::Int]) => {
+                pop_int(thread)?;
+            }
+            ("java/lang/Class", "getPrimitiveClass", [_]) => {
+                let name = pop_reference(thread)?;
+                let ty = match name.as_native_string().map(|name| name.as_str()) {
+                    Some("boolean") => JvmTypeDescriptor::Boolean,
+                    Some("byte") => JvmTypeDescriptor::Byte,
+                    Some("char") => JvmTypeDescriptor::Char,
+                    Some("short") => JvmTypeDescriptor::Short,
+                    Some("int") => JvmTypeDescriptor::Int,
+                    Some("long") => JvmTypeDescriptor::Long,
+                    Some("float") => JvmTypeDescriptor::Float,
+                    Some("double") => JvmTypeDescriptor::Double,
+                    _ => bail!("unsupported primitive class {name:?}"),
+                };
+
+                thread.push_operand_stack(RuntimeType::ClassMirror(Arc::new(ty)));
+            }
+            ("java/lang/Float", "floatToRawIntBits", [JvmTypeDescriptor::Float]) => {
+                let value = pop_float(thread)?;
+
+                thread.push_operand_stack(RuntimeType::Int(value.to_bits() as JvmInt));
+            }
+            ("java/lang/Float", "intBitsToFloat", [JvmTypeDescriptor::Int]) => {
+                let bits = pop_int(thread)?;
+
+                thread.push_operand_stack(RuntimeType::Float(JvmFloat::from_bits(bits as u32)));
+            }
+            ("java/lang/Double", "doubleToRawLongBits", [JvmTypeDescriptor::Double]) => {
+                let value = pop_double(thread)?;
+
+                thread.push_operand_stack(RuntimeType::Long(value.to_bits() as JvmLong));
+            }
+            ("java/lang/Double", "longBitsToDouble", [JvmTypeDescriptor::Long]) => {
+                let bits = pop_long(thread)?;
+
+                thread.push_operand_stack(RuntimeType::Double(JvmDouble::from_bits(bits as u64)));
+            }
+            // Arrays are laid out as by HotSpot with compressed references, for the constants of
+            // Unsafe (though Unsafe cannot access memory)
+            ("jdk/internal/misc/Unsafe", "arrayBaseOffset0", [_]) => {
+                pop_reference(thread)?;
+                pop_reference(thread)?;
+
+                thread.push_operand_stack(RuntimeType::Int(16));
+            }
+            ("jdk/internal/misc/Unsafe", "arrayIndexScale0", [_]) => {
+                let scale = match pop_reference(thread)? {
+                    RuntimeType::ClassMirror(ty) => match ty.as_ref() {
+                        JvmTypeDescriptor::Array(component) => match component.as_ref() {
+                            JvmTypeDescriptor::Boolean | JvmTypeDescriptor::Byte => 1,
+                            JvmTypeDescriptor::Char | JvmTypeDescriptor::Short => 2,
+                            JvmTypeDescriptor::Long | JvmTypeDescriptor::Double => 8,
+                            _ => 4,
+                        },
+                        ty => bail!("index scale of the class of {ty:?}, not an array"),
+                    },
+                    v => bail!("unexpected value (class expected): {v:?}"),
+                };
+
+                pop_reference(thread)?;
+                thread.push_operand_stack(RuntimeType::Int(scale));
+            }
+            // The UTF16 coder of strings uses the native byte order
+            ("java/lang/StringUTF16", "isBigEndian", []) => {
+                thread.push_operand_stack(RuntimeType::Int(cfg!(target_endian = "big").into()));
+            }
+            _ => return Ok(false),
+        }
+
+        Ok(true)
+    }
+
+    /// Copies components from an array to another one, as System.arraycopy does
+    fn arraycopy(
+        &self,
+        src: &RuntimeType,
+        src_pos: JvmInt,
+        dest: &RuntimeType,
+        dest_pos: JvmInt,
+        length: JvmInt,
+    ) -> anyhow::Result<()> {
+        let (src, dest) = (arraycopy_operand(src)?, arraycopy_operand(dest)?);
+        let is_reference = |ty: &JvmTypeDescriptor| {
+            matches!(
+                ty,
+                JvmTypeDescriptor::Class(_) | JvmTypeDescriptor::Array(_)
+            )
+        };
+
+        if src.compound_type != dest.compound_type
+            && !(is_reference(&src.compound_type) && is_reference(&dest.compound_type))
+        {
+            throw!(
+                "java/lang/ArrayStoreException",
+                "arraycopy: type mismatch: can not copy {}[] into {}[]",
+                src.compound_type.binary_name(),
+                dest.compound_type.binary_name()
+            );
+        }
+
+        if src_pos < 0
+            || dest_pos < 0
+            || length < 0
+            || src_pos > src.length() - length
+            || dest_pos > dest.length() - length
+        {
+            throw!(
+                "java/lang/ArrayIndexOutOfBoundsException",
+                "arraycopy: {length} components from {src_pos} to {dest_pos} out of bounds for \
+                 lengths {} and {}",
+                src.length(),
+                dest.length()
+            );
+        }
+
+        // The components are read first, as both arrays may be the same one
+        let values = (src_pos..src_pos + length)
+            .map(|i| src.load(i))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        for (index, value) in (dest_pos..).zip(values) {
+            // The components preceding one of the wrong type are copied
+            if value.is_reference() && !self.env.is_instance_of(&value, &dest.compound_type)? {
+                throw!(
+                    "java/lang/ArrayStoreException",
+                    "arraycopy: element type mismatch at index {index}"
+                );
+            }
+
+            dest.store(index, value)?;
+        }
+
+        Ok(())
+    }
+
+    /// Invokes an instance method selected from the class of the receiver, its receiver and
+    /// arguments being on the operand stack
+    fn invoke_virtual_method(
+        &self,
+        thread: &mut JvmThread,
+        method_ref: ConstantMethodref,
+    ) -> anyhow::Result<()> {
+        let (name, ty) = (method_ref.name, method_ref.ty);
+
+        // Class mirrors have no fields to run Class.desiredAssertionStatus and Class.getName on:
+        // assertions are disabled by default, and the name is the one of the mirrored type
+        if ty.parameter_types.is_empty()
+            && let RuntimeType::ClassMirror(mirrored) = thread.peek_operand_stack(0)?
+            && let Some(result) = match name.as_str() {
+                "desiredAssertionStatus" => Some(RuntimeType::Int(0)),
+                "getName" => Some(RuntimeType::InternedString(Arc::new(
+                    mirrored.binary_name(),
+                ))),
+                _ => None,
+            }
+        {
+            thread.pop_operand_stack()?;
+            thread.push_operand_stack(result);
+
+            return Ok(());
+        }
+
+        // Strings represented natively have no fields to run String.intern on: the interned
+        // instance is the one with the same content
+        if name.as_str() == "intern"
+            && ty.parameter_types.is_empty()
+            && let Some(string) = thread.peek_operand_stack(0)?.as_native_string()
+        {
+            let interned = RuntimeType::InternedString(string.clone());
+
+            thread.pop_operand_stack()?;
+            thread.push_operand_stack(interned);
+
+            return Ok(());
+        }
+
+        if self
+            .env
+            .get_interface(method_ref.class.name.as_str())
+            .is_some()
+        {
+            throw!(
+                "java/lang/IncompatibleClassChangeError",
+                "{} is an interface",
+                method_ref.class.name
+            );
+        }
+
+        let resolved_class = self.resolve_class(&method_ref.class.name)?;
+        let (declaring_class, resolved) = resolved_class
+            .resolve_method(&name, &ty)
+            .ok_or_else(|| no_instance_method_error(&resolved_class, &name, &ty))?;
+
+        let receiver_class =
+            self.receiver_class(thread.peek_operand_stack(ty.parameter_types.len())?)?;
+
+        let (class, method) = if resolved.is_private() {
+            (declaring_class, resolved)
+        } else {
+            receiver_class.select_method(&name, &ty)?
+        };
+
+        trace!("invokevirtual, calling {}:{name} ({ty:?})", class.name);
+        self.jmp_method(thread, class, &method)?;
+
+        Ok(())
+    }
+
+    /// Jumps to a selected instance method, the methods of synthetic classes forwarding the
+    /// invocation to their method handle with the values captured by the receiver
+    fn jmp_method(
+        &self,
+        thread: &mut JvmThread,
+        class: Class,
+        method: &Method,
+    ) -> anyhow::Result<()> {
+        if method.is_native() && self.invoke_builtin_native(thread, &class, method)? {
+            return Ok(());
+        }
+
+        let Some(forwarding) = method.forwarding() else {
+            return thread.jmp_jvm_method(class, method);
+        };
+
+        let adaptations = &forwarding.adaptations;
+
+        // The wrapper classes are initialized while the values are still on the operand stack
+        for adaptation in adaptations.parameters.iter().chain([&adaptations.ret]) {
+            if let Adaptation::Box(ty) = adaptation {
+                self.init_static(thread, &self.resolve_class(wrapper_class_name(ty)?)?)?;
+            }
+        }
+
+        let args = pop_values(thread, method.parameters().len())?;
+        let receiver = pop_object(thread)?;
+
+        let captured = forwarding
+            .captured_fields
+            .iter()
+            .map(|field| receiver.read_field(&class.name, field))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        for (value, adaptation) in captured
+            .into_iter()
+            .chain(args)
+            .zip(&*adaptations.parameters)
+        {
+            let value = self.adapt(value, adaptation)?;
+            thread.push_operand_stack(value);
+        }
+
+        let depth = thread.stack.len();
+
+        self.invoke_method_handle(thread, &forwarding.target)?;
+
+        if adaptations.ret == Adaptation::Identity {
+            return Ok(());
+        }
+
+        // The result is converted on return from the implementation, or right away when it is
+        // evaluated natively
+        if thread.stack.len() > depth {
+            thread
+                .current_frame_mut()?
+                .return_adaptations
+                .push(adaptations.ret.clone());
+        } else {
+            let value = thread.pop_operand_stack()?;
+            let value = self.adapt(value, &adaptations.ret)?;
+            thread.push_operand_stack(value);
+        }
+
+        Ok(())
+    }
+
+    /// Converts a value passed to or returned by the implementation of a lambda. The wrapper
+    /// classes of boxed values are expected to be initialized.
+    fn adapt(&self, value: RuntimeType, adaptation: &Adaptation) -> anyhow::Result<RuntimeType> {
+        match adaptation {
+            Adaptation::Identity => Ok(value),
+            Adaptation::Widen(ty) => widen(value, ty),
+            Adaptation::Box(ty) => {
+                let class = self.resolve_class(wrapper_class_name(ty)?)?;
+                let object = self.env.heap.new_object(class.clone());
+
+                object
+                    .get()
+                    .ok_or_else(|| anyhow!("boxed value collected right after its allocation"))?
+                    .write_field(&class.name, "value", value.store_as(ty)?)?;
+
+                Ok(RuntimeType::Class(object))
+            }
+            Adaptation::Unbox(ty) => {
+                if value.is_null() {
+                    throw!(
+                        "java/lang/NullPointerException",
+                        "cannot unbox null to {}",
+                        primitive_type_name(ty)?
+                    );
+                }
+
+                let RuntimeType::Class(object) = value else {
+                    bail!("unsupported unboxing of {value:?}");
+                };
+                let instance = object
+                    .get()
+                    .ok_or_else(|| anyhow!("unboxing of a collected object"))?;
+                let class_name = &instance.class_type.name;
+
+                match boxed_primitive_type(class_name) {
+                    Some(primitive) if primitive == *ty || is_widening(&primitive, ty) => {
+                        widen(instance.read_field(class_name, "value")?, ty)
+                    }
+                    _ => throw!(
+                        "java/lang/ClassCastException",
+                        "class {} cannot be unboxed to {}",
+                        class_name.replace('/', "."),
+                        primitive_type_name(ty)?
+                    ),
+                }
+            }
+        }
+    }
+
+    /// Returns from the current method and pushes its result onto the operand stack of the
+    /// invoker. Int values are narrowed to the declared return type of the method, as done by
+    /// ireturn, then converted as expected by the lambdas the method implements.
+    fn return_value(&self, thread: &mut JvmThread, value: RuntimeType) -> anyhow::Result<()> {
+        let frame = thread.current_frame()?;
+        let Some(ret_type) = frame.method.ret_type().clone() else {
+            bail!("cannot return {value:?} from a void method");
+        };
+
+        let mut value = value
+            .store_as(&ret_type)
+            .context("invalid method return value")?;
+
+        for adaptation in frame.return_adaptations.clone() {
+            value = self.adapt(value, &adaptation)?;
+        }
+
+        thread.ret()?;
+
+        if thread.stack.is_empty() {
+            thread.result = Some(value);
+        } else {
+            thread.push_operand_stack(value);
+        }
+
+        Ok(())
+    }
+
+    /// The monitor of a value used by monitorenter or monitorexit
+    fn monitor_of(&self, value: &RuntimeType) -> anyhow::Result<Arc<Monitor>> {
+        if value.is_null() {
+            throw!(
+                "java/lang/NullPointerException",
+                "cannot enter or exit the monitor of null"
+            );
+        }
+
+        Ok(match value {
+            RuntimeType::Class(object) => object
+                .get()
+                .ok_or_else(|| anyhow!("monitor of a collected object"))?
+                .monitor
+                .get(),
+            RuntimeType::Array(array) => array
+                .get()
+                .ok_or_else(|| anyhow!("monitor of a collected array"))?
+                .monitor
+                .get(),
+            RuntimeType::ClassMirror(ty) => match ty.as_ref() {
+                JvmTypeDescriptor::Class(name) => self
+                    .env
+                    .get_class_or_interface(name)
+                    .ok_or_else(|| anyhow!("no class found for {name}"))?
+                    .monitor
+                    .get(),
+                ty => bail!("unsupported monitor of the class of {ty:?}"),
+            },
+            v => bail!("unsupported monitor of {v:?}"),
+        })
+    }
+
+    fn resolve_class(&self, class: &str) -> anyhow::Result<Class> {
+        self.env.resolve_type_name(class)?;
+
+        let Some(class) = self.env.get_class(class) else {
+            throw!(
+                "java/lang/IncompatibleClassChangeError",
+                "{class} is an interface"
+            );
+        };
+
+        Ok(class)
+    }
+
+    /// Resolves the static field of a field reference, in the referenced class or interface or
+    /// in its supertypes
+    fn resolve_static_field(
+        &self,
+        field_ref: &ConstantFieldref,
+    ) -> anyhow::Result<(Class, ClassField)> {
+        let name = field_ref.class.name.as_str();
+
+        self.env.resolve_type_name(name)?;
+
+        let class = self
+            .env
+            .get_class_or_interface(name)
+            .ok_or_else(|| anyhow!("{name} loaded but not found"))?;
+
+        if let Some(resolved) = class.resolve_static_field(&field_ref.name, &field_ref.ty) {
+            return Ok(resolved);
+        }
+
+        if class.resolve_instance_field(&field_ref.name).is_some() {
+            throw!(
+                "java/lang/IncompatibleClassChangeError",
+                "expected static field {name}.{}",
+                field_ref.name
+            );
+        }
+
+        throw!("java/lang/NoSuchFieldError", "{name}.{}", field_ref.name)
+    }
+
+    fn resolve_interface(&self, interface: &str) -> anyhow::Result<Interface> {
+        self.env.resolve_type_name(interface)?;
+
+        let Some(interface) = self.env.get_interface(interface) else {
+            throw!(
+                "java/lang/IncompatibleClassChangeError",
+                "{interface} is not an interface"
+            );
+        };
+
+        Ok(interface)
+    }
+
+    /// Resolves a loadable constant into the value it stands for
+    fn load_constant(&self, constant: LoadableJvmConstant) -> anyhow::Result<RuntimeType> {
+        Ok(match constant {
+            LoadableJvmConstant::Integer(v) => RuntimeType::Int(v),
+            LoadableJvmConstant::Float(v) => RuntimeType::Float(v),
+            LoadableJvmConstant::String(v) => RuntimeType::InternedString(v),
+            LoadableJvmConstant::Class(class) => {
+                let ty = type_of_class_name(&class.name)?;
+
+                if let JvmTypeDescriptor::Class(name) = ty.element_type() {
+                    self.env.resolve_type_name(name)?;
+                }
+
+                RuntimeType::ClassMirror(Arc::new(ty))
+            }
+            LoadableJvmConstant::MethodType { descriptor } => {
+                for ty in descriptor
+                    .parameter_types
+                    .iter()
+                    .chain(descriptor.return_type.iter())
+                {
+                    if let JvmTypeDescriptor::Class(name) = ty.element_type() {
+                        self.env.resolve_type_name(name)?;
+                    }
+                }
+
+                RuntimeType::MethodType(Arc::new(descriptor))
+            }
+            LoadableJvmConstant::MethodHandle(handle) => {
+                let class_name = match &handle {
+                    ConstantMethodHandle::GetField(f)
+                    | ConstantMethodHandle::GetStatic(f)
+                    | ConstantMethodHandle::PutField(f)
+                    | ConstantMethodHandle::PutStatic(f) => &f.class.name,
+                    ConstantMethodHandle::InvokeVirtual(m)
+                    | ConstantMethodHandle::NewInvokeSpecial(m)
+                    | ConstantMethodHandle::InvokeStatic(Either::Left(m))
+                    | ConstantMethodHandle::InvokeSpecial(Either::Left(m)) => &m.class.name,
+                    ConstantMethodHandle::InvokeStatic(Either::Right(m))
+                    | ConstantMethodHandle::InvokeSpecial(Either::Right(m))
+                    | ConstantMethodHandle::InvokeInterface(m) => &m.class.name,
+                };
+
+                self.env.resolve_type_name(class_name)?;
+
+                RuntimeType::MethodHandle(Arc::new(handle))
+            }
+            LoadableJvmConstant::Long(v) => RuntimeType::Long(v),
+            LoadableJvmConstant::Double(v) => RuntimeType::Double(v),
+            LoadableJvmConstant::Dynamic { name, .. } => {
+                bail!("dynamically-computed constant {name} is not supported")
+            }
+        })
+    }
+
+    /// Resolves the class, interface or array type referenced at the given constant pool index
+    fn resolve_type(&self, thread: &JvmThread, cp_index: u16) -> anyhow::Result<JvmTypeDescriptor> {
+        let class_ref = thread
+            .current_frame()?
+            .current_class
+            .constant_pool
+            .get_class(cp_index)
+            .ok_or_else(|| anyhow!("no class at {cp_index}"))?;
+
+        let ty = type_of_class_name(&class_ref.name)?;
+
+        if let JvmTypeDescriptor::Class(name) = ty.element_type() {
+            self.env.resolve_type_name(name)?;
+        }
+
+        Ok(ty)
+    }
+
+    /// The class of the receiver of an instance method invocation
+    fn receiver_class(&self, receiver: &RuntimeType) -> anyhow::Result<Class> {
+        match receiver {
+            v if v.is_null() => throw!("java/lang/NullPointerException", "method invoked on null"),
+            RuntimeType::Class(object) => object
+                .get()
+                .map(|o| o.class_type.clone())
+                .ok_or_else(|| anyhow!("method invoked on a collected object")),
+            // Arrays only have the methods of their superclass, java/lang/Object
+            RuntimeType::Array(_) => self.resolve_class("java/lang/Object"),
+            RuntimeType::InternedString(_)
+            | RuntimeType::String(_)
+            | RuntimeType::ClassMirror(_)
+            | RuntimeType::MethodType(_)
+            | RuntimeType::MethodHandle(_) => self.resolve_class(builtin_class_name(receiver)?),
+            v => bail!("unexpected value (reference expected): {v:?}"),
+        }
+    }
+
+    /// Initializes a class unless already done, the thread being suspended while its
+    /// initializer runs
+    fn init_static(&self, thread: &JvmThread, class: &Class) -> anyhow::Result<()> {
+        if class.is_initialized() {
+            return Ok(());
+        }
+
+        let _suspension = thread.suspend(self.env);
+
+        JvmThread::initialize_class(self.env, class)
+    }
+}
+
+fn pop_int(thread: &mut JvmThread) -> anyhow::Result<JvmInt> {
+    match thread.pop_operand_stack()? {
+        RuntimeType::Int(v) => Ok(v),
+        v => bail!("unexpected value (int expected): {v:?}"),
+    }
+}
+
+/// Pops the two operands of a binary int instruction, returned in the (value1, value2) order
+/// of the spec (value2 being the one on top of the operand stack).
+fn pop_int_pair(thread: &mut JvmThread) -> anyhow::Result<(JvmInt, JvmInt)> {
+    let r = thread.pop_operand_stack()?;
+    let l = thread.pop_operand_stack()?;
+
+    match (l, r) {
+        (RuntimeType::Int(l), RuntimeType::Int(r)) => Ok((l, r)),
+        (l, r) => bail!("expected two ints, got {l:?} and {r:?}"),
+    }
+}
+
+fn pop_long(thread: &mut JvmThread) -> anyhow::Result<JvmLong> {
+    match thread.pop_operand_stack()? {
+        RuntimeType::Long(v) => Ok(v),
+        v => bail!("unexpected value (long expected): {v:?}"),
+    }
+}
+
+fn pop_long_pair(thread: &mut JvmThread) -> anyhow::Result<(JvmLong, JvmLong)> {
+    let r = thread.pop_operand_stack()?;
+    let l = thread.pop_operand_stack()?;
+
+    match (l, r) {
+        (RuntimeType::Long(l), RuntimeType::Long(r)) => Ok((l, r)),
+        (l, r) => bail!("expected two longs, got {l:?} and {r:?}"),
+    }
+}
+
+fn pop_float(thread: &mut JvmThread) -> anyhow::Result<JvmFloat> {
+    match thread.pop_operand_stack()? {
+        RuntimeType::Float(v) => Ok(v),
+        v => bail!("unexpected value (float expected): {v:?}"),
+    }
+}
+
+fn pop_float_pair(thread: &mut JvmThread) -> anyhow::Result<(JvmFloat, JvmFloat)> {
+    let r = thread.pop_operand_stack()?;
+    let l = thread.pop_operand_stack()?;
+
+    match (l, r) {
+        (RuntimeType::Float(l), RuntimeType::Float(r)) => Ok((l, r)),
+        (l, r) => bail!("expected two floats, got {l:?} and {r:?}"),
+    }
+}
+
+fn pop_double(thread: &mut JvmThread) -> anyhow::Result<JvmDouble> {
+    match thread.pop_operand_stack()? {
+        RuntimeType::Double(v) => Ok(v),
+        v => bail!("unexpected value (double expected): {v:?}"),
+    }
+}
+
+fn pop_double_pair(thread: &mut JvmThread) -> anyhow::Result<(JvmDouble, JvmDouble)> {
+    let r = thread.pop_operand_stack()?;
+    let l = thread.pop_operand_stack()?;
+
+    match (l, r) {
+        (RuntimeType::Double(l), RuntimeType::Double(r)) => Ok((l, r)),
+        (l, r) => bail!("expected two doubles, got {l:?} and {r:?}"),
+    }
+}
+
+/// d2i (and f2i, as every float is exactly representable as a double) as specified by the JVMS:
+/// NaN becomes 0, and values out of the int range saturate to its bounds.
+fn double_to_int(value: JvmDouble) -> JvmInt {
+    if value.is_nan() {
+        0
+    } else if value >= JvmInt::MAX as JvmDouble {
+        JvmInt::MAX
+    } else if value <= JvmInt::MIN as JvmDouble {
+        JvmInt::MIN
+    } else {
+        value.trunc() as JvmInt
+    }
+}
+
+/// d2l (and f2l), with the same rounding rules as [`double_to_int`] but with the long range.
+fn double_to_long(value: JvmDouble) -> JvmLong {
+    if value.is_nan() {
+        0
+    } else if value >= JvmLong::MAX as JvmDouble {
+        JvmLong::MAX
+    } else if value <= JvmLong::MIN as JvmDouble {
+        JvmLong::MIN
+    } else {
+        value.trunc() as JvmLong
+    }
+}
+
+fn pop_reference(thread: &mut JvmThread) -> anyhow::Result<RuntimeType> {
+    let value = thread.pop_operand_stack()?;
+
+    if !value.is_reference() {
+        bail!("unexpected value (reference expected): {value:?}");
+    }
+
+    Ok(value)
+}
+
+/// The content of a java/lang/String object, from its value bytes: one per character for the
+/// LATIN1 coder, UTF-16 code units in native byte order for the UTF16 one
+fn string_of_object(instance: &ClassInstance) -> anyhow::Result<String> {
+    let RuntimeType::Array(value) = instance.read_field("java/lang/String", "value")? else {
+        bail!("invalid value of a java/lang/String");
+    };
+    let value = value
+        .get()
+        .ok_or_else(|| anyhow!("string conversion of a collected string"))?;
+
+    let bytes = (0..value.length())
+        .map(|i| match value.load(i)? {
+            RuntimeType::Int(byte) => Ok(byte as u8),
+            v => bail!("unexpected byte of a java/lang/String: {v:?}"),
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    match instance.read_field("java/lang/String", "coder")? {
+        RuntimeType::Int(0) => Ok(bytes.iter().map(|&b| char::from(b)).collect()),
+        RuntimeType::Int(1) => Ok(char::decode_utf16(
+            bytes
+                .chunks_exact(2)
+                .map(|unit| u16::from_ne_bytes([unit[0], unit[1]])),
+        )
+        .map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+        .collect()),
+        v => bail!("invalid coder of a java/lang/String: {v:?}"),
+    }
+}
+
+/// Pops the key of a system property, which cannot be null or empty
+fn pop_property_key(thread: &mut JvmThread) -> anyhow::Result<String> {
+    match pop_reference(thread)? {
+        v if v.is_null() => throw!("java/lang/NullPointerException", "key can't be null"),
+        v => match v.as_native_string() {
+            Some(key) if key.is_empty() => {
+                throw!("java/lang/IllegalArgumentException", "key can't be empty")
+            }
+            Some(key) => Ok(key.to_string()),
+            None => bail!("unexpected value (string expected): {v:?}"),
+        },
+    }
+}
+
+/// The error raised when no instance method could be resolved in a class
+fn no_instance_method_error(class: &Class, name: &str, ty: &JvmMethodDescriptor) -> JavaException {
+    let mut current = Some(class);
+
+    while let Some(c) = current {
+        if c.get_static_method(name, ty.clone()).is_some() {
+            return JavaException::new(
+                "java/lang/IncompatibleClassChangeError",
+                format!("{}.{name} ({ty:?}) is static", c.name),
+            );
+        }
+
+        current = c.super_class.as_ref();
+    }
+
+    JavaException::new(
+        "java/lang/NoSuchMethodError",
+        format!("{}.{name} ({ty:?})", class.name),
+    )
+}
+
+/// The class of the instances represented by dedicated runtime values instead of objects
+pub(super) fn builtin_class_name(value: &RuntimeType) -> anyhow::Result<&'static str> {
+    Ok(match value {
+        RuntimeType::InternedString(_) | RuntimeType::String(_) => "java/lang/String",
+        RuntimeType::ClassMirror(_) => "java/lang/Class",
+        RuntimeType::MethodType(_) => "java/lang/invoke/MethodType",
+        RuntimeType::MethodHandle(_) => "java/lang/invoke/MethodHandle",
+        v => bail!("{v:?} is not a builtin instance"),
+    })
+}
+
+/// The primitive type wrapped by instances of a boxing class (java/lang/Integer...)
+fn boxed_primitive_type(class_name: &str) -> Option<JvmTypeDescriptor> {
+    Some(match class_name {
+        "java/lang/Boolean" => JvmTypeDescriptor::Boolean,
+        "java/lang/Byte" => JvmTypeDescriptor::Byte,
+        "java/lang/Character" => JvmTypeDescriptor::Char,
+        "java/lang/Short" => JvmTypeDescriptor::Short,
+        "java/lang/Integer" => JvmTypeDescriptor::Int,
+        "java/lang/Long" => JvmTypeDescriptor::Long,
+        "java/lang/Float" => JvmTypeDescriptor::Float,
+        "java/lang/Double" => JvmTypeDescriptor::Double,
+        _ => return None,
+    })
+}
+
+/// The wrapper class boxing the values of a primitive type
+fn wrapper_class_name(ty: &JvmTypeDescriptor) -> anyhow::Result<&'static str> {
+    Ok(match ty {
+        JvmTypeDescriptor::Boolean => "java/lang/Boolean",
+        JvmTypeDescriptor::Byte => "java/lang/Byte",
+        JvmTypeDescriptor::Char => "java/lang/Character",
+        JvmTypeDescriptor::Short => "java/lang/Short",
+        JvmTypeDescriptor::Int => "java/lang/Integer",
+        JvmTypeDescriptor::Long => "java/lang/Long",
+        JvmTypeDescriptor::Float => "java/lang/Float",
+        JvmTypeDescriptor::Double => "java/lang/Double",
+        ty => bail!("no wrapper class for {ty:?}"),
+    })
+}
+
+/// The name of a primitive type in the Java language
+fn primitive_type_name(ty: &JvmTypeDescriptor) -> anyhow::Result<&'static str> {
+    Ok(match ty {
+        JvmTypeDescriptor::Boolean => "boolean",
+        JvmTypeDescriptor::Byte => "byte",
+        JvmTypeDescriptor::Char => "char",
+        JvmTypeDescriptor::Short => "short",
+        JvmTypeDescriptor::Int => "int",
+        JvmTypeDescriptor::Long => "long",
+        JvmTypeDescriptor::Float => "float",
+        JvmTypeDescriptor::Double => "double",
+        v => bail!("{v:?} is not a primitive type"),
+    })
+}
+
+/// The number of values at the top of the operand stack (ignoring the first skipped ones) taking
+/// exactly the given number of slots, failing if it would split a category 2 value.
+fn values_for_slots(stack: &[RuntimeType], skipped: usize, slots: usize) -> anyhow::Result<usize> {
+    let mut taken_slots = 0;
+    let mut count = 0;
+
+    for value in stack.iter().rev().skip(skipped) {
+        if taken_slots == slots {
+            break;
+        }
+
+        taken_slots += if value.is_two_slots() { 2 } else { 1 };
+        count += 1;
+    }
+
+    match taken_slots {
+        v if v == slots => Ok(count),
+        v if v > slots => bail!("category 2 value split by a stack instruction"),
+        _ => bail!("not enough values in the operand stack for {slots} slots"),
+    }
+}
+
+/// Duplicates the values at the top of the operand stack taking the first number of slots,
+/// inserting the copy under the values taking the second number of slots (the dup family).
+fn dup_slots(thread: &mut JvmThread, dup_slots: usize, under_slots: usize) -> anyhow::Result<()> {
+    let stack = &mut thread.current_frame_mut()?.operand_stack;
+
+    let dup_count = values_for_slots(stack, 0, dup_slots)?;
+    let under_count = values_for_slots(stack, dup_count, under_slots)?;
+
+    let copy = stack[stack.len() - dup_count..].to_vec();
+    let insert_at = stack.len() - dup_count - under_count;
+
+    stack.splice(insert_at..insert_at, copy);
+
+    Ok(())
+}
+
+/// Pops the values at the top of the operand stack taking the given number of slots
+fn pop_slots(thread: &mut JvmThread, slots: usize) -> anyhow::Result<()> {
+    let stack = &mut thread.current_frame_mut()?.operand_stack;
+
+    let count = values_for_slots(stack, 0, slots)?;
+    stack.truncate(stack.len() - count);
+
+    Ok(())
+}
+
+/// Pops the given number of values, returning them in the order they were pushed
+fn pop_values(thread: &mut JvmThread, count: usize) -> anyhow::Result<Vec<RuntimeType>> {
+    let stack = &mut thread.current_frame_mut()?.operand_stack;
+
+    if stack.len() < count {
+        bail!(
+            "expected {count} values in operand stack, but got {}",
+            stack.len()
+        );
+    }
+
+    Ok(stack.split_off(stack.len() - count))
+}
+
+/// The type of a method handle, its receiver being its first parameter for instance methods
+fn method_handle_type(handle: &ConstantMethodHandle) -> anyhow::Result<JvmMethodDescriptor> {
+    let (receiver, ty) = match handle {
+        ConstantMethodHandle::InvokeStatic(m) => (None, m.as_ref().either(|m| &m.ty, |m| &m.ty)),
+        ConstantMethodHandle::InvokeVirtual(m) => (Some(&m.class.name), &m.ty),
+        ConstantMethodHandle::InvokeInterface(m) => (Some(&m.class.name), &m.ty),
+        ConstantMethodHandle::InvokeSpecial(m) => m.as_ref().either(
+            |m| (Some(&m.class.name), &m.ty),
+            |m| (Some(&m.class.name), &m.ty),
+        ),
+        ConstantMethodHandle::NewInvokeSpecial(m) => {
+            return Ok(JvmMethodDescriptor {
+                parameter_types: m.ty.parameter_types.clone(),
+                return_type: Some(JvmTypeDescriptor::Class(m.class.name.to_string())),
+            });
+        }
+        handle => bail!("unsupported method handle {handle:?}"),
+    };
+
+    Ok(JvmMethodDescriptor {
+        parameter_types: receiver
+            .map(|name| JvmTypeDescriptor::Class(name.to_string()))
+            .into_iter()
+            .chain(ty.parameter_types.iter().cloned())
+            .collect(),
+        return_type: ty.return_type.clone(),
+    })
+}
+
+/// The conversion of a value of a type passed where another is expected by a lambda, if any:
+/// references are only checked at their use, while primitive types are widened, boxed or unboxed
+fn adaptation(from: &JvmTypeDescriptor, to: &JvmTypeDescriptor) -> Option<Adaptation> {
+    match (from.is_reference(), to.is_reference()) {
+        (true, true) => Some(Adaptation::Identity),
+        (false, false) if from == to => Some(Adaptation::Identity),
+        (false, false) => is_widening(from, to).then(|| Adaptation::Widen(to.clone())),
+        (false, true) => Some(Adaptation::Box(from.clone())),
+        // Other references (like java/lang/Number) are only known to be wrappers when unboxed
+        (true, false) => match from {
+            JvmTypeDescriptor::Class(name) => match boxed_primitive_type(name) {
+                Some(primitive) => (primitive == *to || is_widening(&primitive, to))
+                    .then(|| Adaptation::Unbox(to.clone())),
+                None => Some(Adaptation::Unbox(to.clone())),
+            },
+            _ => None,
+        },
+    }
+}
+
+/// Whether a primitive type is converted to another by a widening primitive conversion
+fn is_widening(from: &JvmTypeDescriptor, to: &JvmTypeDescriptor) -> bool {
+    use JvmTypeDescriptor::*;
+
+    matches!(
+        (from, to),
+        (Byte, Short | Int | Long | Float | Double)
+            | (Short | Char, Int | Long | Float | Double)
+            | (Int, Long | Float | Double)
+            | (Long, Float | Double)
+            | (Float, Double)
+    )
+}
+
+/// Widens a primitive value to a type, the types of int values being checked by the caller
+fn widen(value: RuntimeType, to: &JvmTypeDescriptor) -> anyhow::Result<RuntimeType> {
+    Ok(match (value, to) {
+        (RuntimeType::Int(v), JvmTypeDescriptor::Long) => RuntimeType::Long(v.into()),
+        (RuntimeType::Int(v), JvmTypeDescriptor::Float) => RuntimeType::Float(v as JvmFloat),
+        (RuntimeType::Int(v), JvmTypeDescriptor::Double) => RuntimeType::Double(v.into()),
+        (RuntimeType::Long(v), JvmTypeDescriptor::Float) => RuntimeType::Float(v as JvmFloat),
+        (RuntimeType::Long(v), JvmTypeDescriptor::Double) => RuntimeType::Double(v as JvmDouble),
+        (RuntimeType::Float(v), JvmTypeDescriptor::Double) => RuntimeType::Double(v.into()),
+        (v, to) => v.store_as(to)?,
+    })
+}
+
+/// Pops an array reference, failing on null
+fn pop_array(thread: &mut JvmThread) -> anyhow::Result<Arc<Array>> {
+    match thread.pop_operand_stack()? {
+        RuntimeType::Array(array) => array.get().ok_or_else(|| {
+            JavaException::new(
+                "java/lang/NullPointerException",
+                "Cannot access a null array",
+            )
+            .into()
+        }),
+        v => bail!("unexpected value (array expected): {v:?}"),
+    }
+}
+
+/// An array given to System.arraycopy, which cannot be null
+fn arraycopy_operand(value: &RuntimeType) -> anyhow::Result<Arc<Array>> {
+    match value {
+        v if v.is_null() => throw!("java/lang/NullPointerException", "arraycopy of null"),
+        RuntimeType::Array(array) => array
+            .get()
+            .ok_or_else(|| anyhow!("arraycopy of a collected array")),
+        v => throw!(
+            "java/lang/ArrayStoreException",
+            "arraycopy: {} is not an array",
+            type_of_reference(v)?.binary_name()
+        ),
+    }
+}
+
+/// Pops the number of components of an array to create, failing on negative ones
+fn pop_array_size(thread: &mut JvmThread) -> anyhow::Result<JvmInt> {
+    match pop_int(thread)? {
+        count if count < 0 => throw!("java/lang/NegativeArraySizeException", "{count}"),
+        count => Ok(count),
+    }
+}
+
+/// Loads a component of an array whose compound type is accepted by the instruction
+fn array_load(
+    thread: &mut JvmThread,
+    accepts: impl Fn(&JvmTypeDescriptor) -> bool,
+    expected: &str,
+) -> anyhow::Result<()> {
+    let index = pop_int(thread)?;
+    let array = pop_array(thread)?;
+
+    if !accepts(&array.compound_type) {
+        bail!(
+            "unexpected array of {:?} (array of {expected} expected)",
+            array.compound_type
+        );
+    }
+
+    let value = array.load(index)?;
+
+    thread.push_operand_stack(value);
+
+    Ok(())
+}
+
+/// Stores a component in an array whose compound type is accepted by the instruction,
+/// narrowing ints for boolean, byte, char and short arrays.
+fn array_store(
+    thread: &mut JvmThread,
+    accepts: impl Fn(&JvmTypeDescriptor) -> bool,
+    expected: &str,
+) -> anyhow::Result<()> {
+    let value = thread.pop_operand_stack()?;
+    let index = pop_int(thread)?;
+    let array = pop_array(thread)?;
+
+    if !accepts(&array.compound_type) {
+        bail!(
+            "unexpected array of {:?} (array of {expected} expected)",
+            array.compound_type
+        );
+    }
+
+    array.store(index, value.store_as(&array.compound_type)?)
+}
+
+/// The type named by a class constant, which is a descriptor for array classes
+pub(super) fn type_of_class_name(name: &str) -> anyhow::Result<JvmTypeDescriptor> {
+    if name.starts_with('[') {
+        JvmTypeDescriptor::from_str(name)
+    } else {
+        Ok(JvmTypeDescriptor::Class(name.to_string()))
+    }
+}
+
+/// The type of the value of a non-null reference
+fn type_of_reference(value: &RuntimeType) -> anyhow::Result<JvmTypeDescriptor> {
+    Ok(match value {
+        RuntimeType::Class(object) => JvmTypeDescriptor::Class(
+            object
+                .get()
+                .ok_or_else(|| anyhow!("no type for a null reference"))?
+                .class_type
+                .name
+                .to_string(),
+        ),
+        RuntimeType::Array(array) => JvmTypeDescriptor::Array(Box::new(
+            array
+                .get()
+                .ok_or_else(|| anyhow!("no type for a null reference"))?
+                .compound_type
+                .clone(),
+        )),
+        v => JvmTypeDescriptor::Class(builtin_class_name(v)?.to_string()),
+    })
+}
+
+/// Pops an object reference, failing on null
+fn pop_object(thread: &mut JvmThread) -> anyhow::Result<Arc<ClassInstance>> {
+    match thread.pop_operand_stack()? {
+        RuntimeType::Class(object) => object.get().ok_or_else(|| {
+            JavaException::new(
+                "java/lang/NullPointerException",
+                "Cannot access a field of a null object",
+            )
+            .into()
+        }),
+        v => bail!("unexpected value (object expected): {v:?}"),
+    }
+}
+
+/// Jumps to an address relative to the one of the branching instruction.
+fn branch(thread: &mut JvmThread, op_pc: usize, offset: JvmInt) -> anyhow::Result<()> {
+    let target = op_pc
+        .checked_add_signed(offset as isize)
+        .ok_or_else(|| anyhow!("branch offset {offset} from {op_pc} is out of code memory"))?;
+
+    thread.jmp_to(target);
+
+    Ok(())
+}
+
+/*
+    Instructions:
+    - aaload:               COMPLETED
+    - aastore:              COMPLETED
+    - aconst_null:          COMPLETED
+    - aload:                COMPLETED
+    - aload_<n>:            COMPLETED
+    - anewarray:            COMPLETED
+    - areturn:              COMPLETED
+    - arraylength:          COMPLETED
+    - astore:               COMPLETED
+    - astore_<n>:           COMPLETED
+    - athrow:               COMPLETED
+    - baload:               COMPLETED
+    - bastore:              COMPLETED
+    - bipush:               COMPLETED
+    - caload:               COMPLETED
+    - castore:              COMPLETED
+    - checkcast:            COMPLETED
+    - d2f:                  COMPLETED
+    - d2i:                  COMPLETED
+    - d2l:                  COMPLETED
+    - dadd:                 COMPLETED
+    - daload:               COMPLETED
+    - dastore:              COMPLETED
+    - dcmp<op>:             COMPLETED
+    - dconst_<d>:           COMPLETED
+    - ddiv:                 COMPLETED
+    - dload:                COMPLETED
+    - dload_<n>:            COMPLETED
+    - dmul:                 COMPLETED
+    - dneg:                 COMPLETED
+    - drem:                 COMPLETED
+    - dreturn:              COMPLETED
+    - dstore:               COMPLETED
+    - dstore_<n>:           COMPLETED
+    - dsub:                 COMPLETED
+    - dup:                  COMPLETED
+    - dup_x1:               COMPLETED
+    - dup_x2:               COMPLETED
+    - dup2:                 COMPLETED
+    - dup2_x1:              COMPLETED
+    - dup2_x2:              COMPLETED
+    - f2d:                  COMPLETED
+    - f2i:                  COMPLETED
+    - f2l:                  COMPLETED
+    - fadd:                 COMPLETED
+    - faload:               COMPLETED
+    - fastore:              COMPLETED
+    - fcmp<op>:             COMPLETED
+    - fconst_<f>:           COMPLETED
+    - fdiv:                 COMPLETED
+    - fload:                COMPLETED
+    - fload_<n>:            COMPLETED
+    - fmul:                 COMPLETED
+    - fneg:                 COMPLETED
+    - frem:                 COMPLETED
+    - freturn:              COMPLETED
+    - fstore:               COMPLETED
+    - fstore_<n>:           COMPLETED
+    - fsub:                 COMPLETED
+    - getfield:             COMPLETED
+    - getstatic:            COMPLETED
+    - goto:                 COMPLETED
+    - goto_w:               COMPLETED
+    - i2b:                  COMPLETED
+    - i2c:                  COMPLETED
+    - i2d:                  COMPLETED
+    - i2f:                  COMPLETED
+    - i2l:                  COMPLETED
+    - i2s:                  COMPLETED
+    - iadd:                 COMPLETED
+    - iaload:               COMPLETED
+    - iand:                 COMPLETED
+    - iastore:              COMPLETED
+    - iconst_<i>:           COMPLETED
+    - idiv:                 COMPLETED
+    - if_acmp<cond>:        COMPLETED
+    - if_icmp<cond>:        COMPLETED
+    - if<cond>:             COMPLETED
+    - ifnonnull:            COMPLETED
+    - ifnull:               COMPLETED
+    - iinc:                 COMPLETED
+    - iload:                COMPLETED
+    - iload_<n>:            COMPLETED
+    - imul:                 COMPLETED
+    - ineg:                 COMPLETED
+    - instanceof:           COMPLETED
+    - invokedynamic:        COMPLETED
+    - invokeinterface:      COMPLETED
+    - invokespecial:        COMPLETED
+    - invokestatic:         PARTIAL
+    - invokevirtual:        COMPLETED
+    - ior:                  COMPLETED
+    - irem:                 COMPLETED
+    - ireturn:              COMPLETED
+    - ishl:                 COMPLETED
+    - ishr:                 COMPLETED
+    - istore:               COMPLETED
+    - istore_<n>:           COMPLETED
+    - isub:                 COMPLETED
+    - iushr:                COMPLETED
+    - ixor:                 COMPLETED
+    - jsr:                  COMPLETED
+    - jsr_w:                COMPLETED
+    - l2d:                  COMPLETED
+    - l2f:                  COMPLETED
+    - l2i:                  COMPLETED
+    - ladd:                 COMPLETED
+    - laload:               COMPLETED
+    - land:                 COMPLETED
+    - lastore:              COMPLETED
+    - lcmp:                 COMPLETED
+    - lconst_<l>:           COMPLETED
+    - ldc:                  COMPLETED
+    - ldc_w:                COMPLETED
+    - ldc2_w:               COMPLETED
+    - ldiv:                 COMPLETED
+    - lload:                COMPLETED
+    - lload_<n>:            COMPLETED
+    - lmul:                 COMPLETED
+    - lneg:                 COMPLETED
+    - lookupswitch:         COMPLETED
+    - lor:                  COMPLETED
+    - lrem:                 COMPLETED
+    - lreturn:              COMPLETED
+    - lshl:                 COMPLETED
+    - lshr:                 COMPLETED
+    - lstore:               COMPLETED
+    - lstore_<n>:           COMPLETED
+    - lsub:                 COMPLETED
+    - lushr:                COMPLETED
+    - lxor:                 COMPLETED
+    - monitorenter:         COMPLETED
+    - monitorexit:          COMPLETED
+    - multianewarray:       COMPLETED
+    - new:                  COMPLETED
+    - newarray:             COMPLETED
+    - nop:                  COMPLETED
+    - pop:                  COMPLETED
+    - pop2:                 COMPLETED
+    - putfield:             COMPLETED
+    - putstatic:            TODO
+    - ret:                  DONE
+    - return:               DONE
+    - saload:               COMPLETED
+    - sastore:              COMPLETED
+    - sipush:               COMPLETED
+    - swap:                 COMPLETED
+    - tableswitch:          COMPLETED
+    - wide:                 COMPLETED
+*/
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::{
+        class::{
+            JvmUnit, parse_class_file,
+            tests::{ClassBytes, CodeBytes},
+        },
+        exec::{JvmExecEnv, class::ClassMembers},
+    };
+
+    /// The exception classes thrown by the tested instructions, after their superclass
+    const EXCEPTION_CLASSES: &[(&str, &str)] = &[
+        ("java/lang/Throwable", "java/lang/Object"),
+        ("java/lang/Exception", "java/lang/Throwable"),
+        ("java/lang/RuntimeException", "java/lang/Exception"),
+        ("java/lang/Error", "java/lang/Throwable"),
+        ("java/lang/LinkageError", "java/lang/Error"),
+        (
+            "java/lang/IncompatibleClassChangeError",
+            "java/lang/LinkageError",
+        ),
+        (
+            "java/lang/IllegalAccessError",
+            "java/lang/IncompatibleClassChangeError",
+        ),
+        (
+            "java/lang/ExceptionInInitializerError",
+            "java/lang/LinkageError",
+        ),
+        ("java/lang/NoClassDefFoundError", "java/lang/LinkageError"),
+        (
+            "java/lang/ArithmeticException",
+            "java/lang/RuntimeException",
+        ),
+        (
+            "java/lang/IllegalStateException",
+            "java/lang/RuntimeException",
+        ),
+        (
+            "java/lang/NullPointerException",
+            "java/lang/RuntimeException",
+        ),
+        (
+            "java/lang/IllegalMonitorStateException",
+            "java/lang/RuntimeException",
+        ),
+        (
+            "java/lang/IndexOutOfBoundsException",
+            "java/lang/RuntimeException",
+        ),
+        (
+            "java/lang/ArrayIndexOutOfBoundsException",
+            "java/lang/IndexOutOfBoundsException",
+        ),
+    ];
+
+    /// An environment with java/lang/Object, the exception classes and the given classes (after
+    /// their superclass). Classes of version 49 are not verified, the tests focusing on the
+    /// execution of instructions.
+    fn test_env(classes: &[ClassBytes]) -> JvmExecEnv {
+        let env = JvmExecEnv::new();
+        let mut object = ClassBytes::new("java/lang/Object", 61);
+        // return
+        object.method(0x0001, "<init>", "()V", code(&[0xb1], &[]));
+
+        let mut units = vec![object];
+
+        for (name, super_class) in EXCEPTION_CLASSES {
+            let mut class = ClassBytes::new(name, 61);
+            class.extends(super_class);
+
+            let [high, low] = class.method_ref(super_class, "<init>", "()V").to_be_bytes();
+            // aload_0, invokespecial <init>, return
+            let mut init = vec![0x2a, 0xb7, high, low, 0xb1];
+
+            if *name == "java/lang/Throwable" {
+                class.field(0x0002, "detailMessage", "Ljava/lang/String;");
+                class.field(0x0002, "cause", "Ljava/lang/Throwable;");
+                // private native
+                class.method(0x0102, "fillInStackTrace", "(I)Ljava/lang/Throwable;", None);
+
+                let [high, low] = class
+                    .method_ref(name, "fillInStackTrace", "(I)Ljava/lang/Throwable;")
+                    .to_be_bytes();
+                // aload_0, iconst_0, invokevirtual fillInStackTrace, pop, before the return
+                init.splice(4..4, [0x2a, 0x03, 0xb6, high, low, 0x57]);
+            }
+
+            class.method(0x0001, "<init>", "()V", code(&init, &[]));
+            units.push(class);
+        }
+
+        for class in units.iter().chain(classes) {
+            let class_file = parse_class_file("", &class.bytes()).unwrap();
+            env.add_unit(JvmUnit::from_class_file(class_file).unwrap())
+                .unwrap();
+        }
+
+        env
+    }
+
+    /// The code of a method, with room for 4 operands and 4 locals
+    fn code<'a>(code: &'a [u8], exception_table: &'a [[u16; 4]]) -> Option<CodeBytes<'a>> {
+        Some(CodeBytes {
+            max_stack: 4,
+            max_locals: 4,
+            code,
+            exception_table,
+            stack_map: &[],
+        })
+    }
+
+    /// A static method of the class Test, run on a thread of its own
+    fn run_static(
+        env: &JvmExecEnv,
+        name: &str,
+        descriptor: &str,
+        args: Vec<RuntimeType>,
+    ) -> anyhow::Result<Option<RuntimeType>> {
+        let class = env.get_class("Test").unwrap();
+        let method = class
+            .get_static_method(name, JvmMethodDescriptor::from_str(descriptor).unwrap())
+            .unwrap();
+
+        JvmThread::invoke(env, class, &method, args)
+    }
+
+    /// The result of a static method of the class Test returning an int
+    fn run_int(env: &JvmExecEnv, name: &str, descriptor: &str, args: Vec<RuntimeType>) -> JvmInt {
+        match run_static(env, name, descriptor, args).unwrap() {
+            Some(RuntimeType::Int(v)) => v,
+            v => panic!("not an int: {v:?}"),
+        }
+    }
+
+    /// The exception thrown by a method, as displayed when not caught
+    fn thrown(result: anyhow::Result<Option<RuntimeType>>) -> String {
+        let err = result.unwrap_err();
+
+        err.downcast_ref::<JavaException>()
+            .unwrap_or_else(|| panic!("not a java exception: {err:#}"))
+            .to_string()
+    }
+
+    /// A java/lang/String class with the fields of the one of the JDK
+    fn string_class() -> Class {
+        let field = |name: &str, ty: JvmTypeDescriptor| ClassField {
+            name: Arc::new(name.to_string()),
+            value: RuntimeType::default_of(&ty),
+            ty,
+            is_final: true,
+        };
+
+        Class::new(
+            None,
+            vec![],
+            Arc::new(String::from("java/lang/String")),
+            61,
+            Default::default(),
+            ClassMembers {
+                static_fields: HashMap::new(),
+                fields: Box::new([
+                    field(
+                        "value",
+                        JvmTypeDescriptor::Array(Box::new(JvmTypeDescriptor::Byte)),
+                    ),
+                    field("coder", JvmTypeDescriptor::Byte),
+                ]),
+                methods: HashMap::new(),
+                is_abstract: false,
+                jnb_type: None,
+            },
+        )
+    }
+
+    fn new_string(env: &JvmExecEnv, bytes: &[u8], coder: JvmInt) -> ObjectRef {
+        let string = env.heap.new_object(string_class());
+        let value = env.heap.new_array_with(
+            JvmTypeDescriptor::Byte,
+            bytes
+                .iter()
+                .map(|&b| RuntimeType::Int(b as i8 as JvmInt))
+                .collect(),
+        );
+
+        let instance = string.get().unwrap();
+        instance
+            .write_field("java/lang/String", "value", RuntimeType::Array(value))
+            .unwrap();
+        instance
+            .write_field("java/lang/String", "coder", RuntimeType::Int(coder))
+            .unwrap();
+
+        string
+    }
+
+    #[test]
+    fn latin1_string_objects_are_converted_natively() {
+        let env = JvmExecEnv::new();
+        let string = new_string(&env, b"caf\xe9", 0);
+
+        let result = JvmProcessUnit::jpu_new(&env).object_to_string(string);
+
+        assert_eq!(result.unwrap(), "café");
+    }
+
+    #[test]
+    fn utf16_string_objects_are_converted_natively() {
+        let env = JvmExecEnv::new();
+        let bytes = "h€\u{1f600}"
+            .encode_utf16()
+            .flat_map(u16::to_ne_bytes)
+            .collect::<Vec<_>>();
+        let string = new_string(&env, &bytes, 1);
+
+        let result = JvmProcessUnit::jpu_new(&env).object_to_string(string);
+
+        assert_eq!(result.unwrap(), "h€\u{1f600}");
+    }
+
+    #[test]
+    fn string_objects_with_an_invalid_coder_are_rejected() {
+        let env = JvmExecEnv::new();
+        let string = new_string(&env, b"abc", 2);
+
+        assert!(
+            JvmProcessUnit::jpu_new(&env)
+                .object_to_string(string)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn final_static_fields_are_only_written_by_the_initializer_of_their_class() {
+        let mut class = ClassBytes::new("Test", 49);
+        let [high, low] = class.field_ref("Test", "X", "I").to_be_bytes();
+        class.field(0x0018, "X", "I");
+
+        // iconst_1, putstatic X, return
+        class.method(
+            0x0008,
+            "<clinit>",
+            "()V",
+            code(&[0x04, 0xb3, high, low, 0xb1], &[]),
+        );
+        // getstatic X, ireturn
+        class.method(0x0009, "get", "()I", code(&[0xb2, high, low, 0xac], &[]));
+        // iconst_2, putstatic X, return
+        class.method(
+            0x0009,
+            "set",
+            "()V",
+            code(&[0x05, 0xb3, high, low, 0xb1], &[]),
+        );
+
+        let env = test_env(&[class]);
+
+        assert_eq!(run_int(&env, "get", "()I", vec![]), 1);
+        assert_eq!(
+            thrown(run_static(&env, "set", "()V", vec![])),
+            "java.lang.IllegalAccessError: final field Test.X set from Test.set"
+        );
+        assert_eq!(run_int(&env, "get", "()I", vec![]), 1);
+    }
+
+    #[test]
+    fn static_references_are_checked_against_the_type_of_the_field() {
+        let mut marker = ClassBytes::new("Marker", 61);
+        // ACC_PUBLIC, ACC_INTERFACE and ACC_ABSTRACT
+        marker.access_flags = 0x0601;
+
+        let mut class = ClassBytes::new("Test", 49);
+        class.field(0x0008, "test", "LTest;");
+        class.field(0x0008, "marker", "LMarker;");
+
+        let env = test_env(&[marker, class]);
+        let class = env.get_class("Test").unwrap();
+        let object = RuntimeType::Class(
+            env.heap
+                .new_object(env.get_class("java/lang/Object").unwrap()),
+        );
+        let test = RuntimeType::Class(env.heap.new_object(class.clone()));
+        let statics = class.lock_statics();
+
+        statics.set(&env, "test", test).unwrap();
+        statics
+            .set(&env, "test", RuntimeType::Class(ObjectRef::new_null()))
+            .unwrap();
+        assert!(statics.set(&env, "test", object.clone()).is_err());
+        // As for the verifier, interface types stand for any reference
+        statics.set(&env, "marker", object).unwrap();
+    }
+
+    #[test]
+    fn thrown_exceptions_are_caught_by_the_matching_handler() {
+        let mut class = ClassBytes::new("Test", 49);
+        let [class_high, class_low] = class.class("java/lang/IllegalStateException").to_be_bytes();
+        let [init_high, init_low] = class
+            .method_ref("java/lang/IllegalStateException", "<init>", "()V")
+            .to_be_bytes();
+        let arithmetic = class.class("java/lang/ArithmeticException");
+        let runtime = class.class("java/lang/RuntimeException");
+
+        // new IllegalStateException, dup, invokespecial <init>, athrow,
+        // pop, iconst_2, ireturn (handler at 8), pop, iconst_1, ireturn (handler at 11)
+        let throw = [
+            0xbb, class_high, class_low, 0x59, 0xb7, init_high, init_low, 0xbf, 0x57, 0x05, 0xac,
+            0x57, 0x04, 0xac,
+        ];
+        class.method(
+            0x0009,
+            "caught",
+            "()I",
+            code(&throw, &[[0, 8, 8, arithmetic], [0, 8, 11, runtime]]),
+        );
+        class.method(
+            0x0009,
+            "uncaught",
+            "()I",
+            code(&throw, &[[0, 8, 8, arithmetic]]),
+        );
+
+        let env = test_env(&[class]);
+
+        assert_eq!(run_int(&env, "caught", "()I", vec![]), 1);
+        assert_eq!(
+            thrown(run_static(&env, "uncaught", "()I", vec![])),
+            "java.lang.IllegalStateException"
+        );
+    }
+
+    /// A java/lang/String class with the fields of the one of the JDK, and its length method
+    fn string_class_bytes() -> ClassBytes {
+        let mut class = ClassBytes::new("java/lang/String", 49);
+        let [value_high, value_low] = class
+            .field_ref("java/lang/String", "value", "[B")
+            .to_be_bytes();
+        let [coder_high, coder_low] = class
+            .field_ref("java/lang/String", "coder", "B")
+            .to_be_bytes();
+
+        class.field(0x0012, "value", "[B");
+        class.field(0x0012, "coder", "B");
+        class.field(0x0002, "hash", "I");
+
+        // aload_0, getfield value, arraylength, aload_0, getfield coder, ishr, ireturn
+        let length = [
+            0x2a, 0xb4, value_high, value_low, 0xbe, 0x2a, 0xb4, coder_high, coder_low, 0x7a, 0xac,
+        ];
+        class.method(0x0001, "length", "()I", code(&length, &[]));
+
+        class
+    }
+
+    #[test]
+    fn string_methods_run_on_strings_represented_natively() {
+        let mut class = ClassBytes::new("Test", 49);
+        let [high, low] = class
+            .method_ref("java/lang/String", "length", "()I")
+            .to_be_bytes();
+        // aload_0, invokevirtual length, ireturn
+        class.method(
+            0x0009,
+            "length",
+            "(Ljava/lang/String;)I",
+            code(&[0x2a, 0xb6, high, low, 0xac], &[]),
+        );
+
+        let env = test_env(&[string_class_bytes(), class]);
+        let length = |string: &str| {
+            let string = RuntimeType::String(Arc::new(string.to_string()));
+
+            run_int(&env, "length", "(Ljava/lang/String;)I", vec![string])
+        };
+
+        assert_eq!(length("café"), 4);
+        assert_eq!(length("h€\u{1f600}"), 4);
+        assert_eq!(length(""), 0);
+    }
+
+    #[test]
+    fn overlapping_components_are_copied_by_arraycopy() {
+        let mut class = ClassBytes::new("Test", 49);
+        let [high, low] = class
+            .method_ref(
+                "java/lang/System",
+                "arraycopy",
+                "(Ljava/lang/Object;ILjava/lang/Object;II)V",
+            )
+            .to_be_bytes();
+        // aload_0, iconst_0, aload_0, iconst_1, iconst_2, invokestatic arraycopy,
+        // aload_0, iconst_2, iaload, ireturn
+        let copy = [
+            0x2a, 0x03, 0x2a, 0x04, 0x05, 0xb8, high, low, 0x2a, 0x05, 0x2e, 0xac,
+        ];
+        class.method(0x0009, "copy", "([I)I", code(&copy, &[]));
+
+        let env = test_env(&[class]);
+        let array = |values: &[JvmInt]| {
+            RuntimeType::Array(env.heap.new_array_with(
+                JvmTypeDescriptor::Int,
+                values.iter().map(|&v| RuntimeType::Int(v)).collect(),
+            ))
+        };
+
+        assert_eq!(run_int(&env, "copy", "([I)I", vec![array(&[1, 2, 3])]), 2);
+        assert_eq!(
+            thrown(run_static(&env, "copy", "([I)I", vec![array(&[1, 2])])),
+            "java.lang.ArrayIndexOutOfBoundsException: arraycopy: 2 components from 0 to 1 out \
+             of bounds for lengths 2 and 2"
+        );
+    }
+
+    #[test]
+    fn string_methods_run_on_string_literals() {
+        let mut class = ClassBytes::new("Test", 49);
+        let [utf8_high, utf8_low] = class.utf8("h€llo").to_be_bytes();
+        let literal = class.constant(&[8, utf8_high, utf8_low]) as u8;
+        let [high, low] = class
+            .method_ref("java/lang/String", "length", "()I")
+            .to_be_bytes();
+        // ldc "h€llo", invokevirtual length, ireturn
+        class.method(
+            0x0009,
+            "length",
+            "()I",
+            code(&[0x12, literal, 0xb6, high, low, 0xac], &[]),
+        );
+
+        let env = test_env(&[string_class_bytes(), class]);
+
+        assert_eq!(run_int(&env, "length", "()I", vec![]), 5);
+    }
+
+    /// An environment where the class Test creates lambdas implementing the interface Fn, whose
+    /// method takes and returns an Object, instantiated to take and return an Integer.
+    /// `twice(Integer)` applies a method reference to `twice(I)I`, while `add(I, Integer)` applies
+    /// a lambda capturing the int to `add(II)I`, both returning the value of the result.
+    fn lambda_env() -> JvmExecEnv {
+        let mut function = ClassBytes::new("Fn", 49);
+        // ACC_PUBLIC, ACC_INTERFACE and ACC_ABSTRACT
+        function.access_flags = 0x0601;
+        function.method(
+            0x0401,
+            "apply",
+            "(Ljava/lang/Object;)Ljava/lang/Object;",
+            None,
+        );
+
+        let mut integer = ClassBytes::new("java/lang/Integer", 49);
+        integer.field(0x0012, "value", "I");
+
+        let mut class = ClassBytes::new("Test", 49);
+        // iload_0, iconst_2, imul, ireturn
+        class.method(
+            0x0009,
+            "twice",
+            "(I)I",
+            code(&[0x1a, 0x05, 0x68, 0xac], &[]),
+        );
+        // iload_0, iload_1, iadd, ireturn
+        class.method(0x0009, "add", "(II)I", code(&[0x1a, 0x1b, 0x60, 0xac], &[]));
+
+        let metafactory = class.method_ref(
+            "java/lang/invoke/LambdaMetafactory",
+            "metafactory",
+            "(Ljava/lang/invoke/MethodHandles$Lookup;Ljava/lang/String;\
+            Ljava/lang/invoke/MethodType;Ljava/lang/invoke/MethodType;\
+            Ljava/lang/invoke/MethodHandle;Ljava/lang/invoke/MethodType;)\
+            Ljava/lang/invoke/CallSite;",
+        );
+        // REF_invokeStatic
+        let bootstrap = class.method_handle(6, metafactory);
+        let erased = class.method_type("(Ljava/lang/Object;)Ljava/lang/Object;");
+        let instantiated = class.method_type("(Ljava/lang/Integer;)Ljava/lang/Integer;");
+
+        let [apply_high, apply_low] = class
+            .interface_method_ref("Fn", "apply", "(Ljava/lang/Object;)Ljava/lang/Object;")
+            .to_be_bytes();
+        let [cast_high, cast_low] = class.class("java/lang/Integer").to_be_bytes();
+        let [value_high, value_low] = class
+            .field_ref("java/lang/Integer", "value", "I")
+            .to_be_bytes();
+
+        for (name, descriptor, implementation, captured) in [
+            ("twice", "(Ljava/lang/Integer;)I", "(I)I", "()LFn;"),
+            ("add", "(ILjava/lang/Integer;)I", "(II)I", "(I)LFn;"),
+        ] {
+            let target = class.method_ref("Test", name, implementation);
+            let target = class.method_handle(6, target);
+            let [site_high, site_low] = class
+                .invoke_dynamic(
+                    bootstrap,
+                    &[erased, target, instantiated],
+                    "apply",
+                    captured,
+                )
+                .to_be_bytes();
+
+            // The captured int and the argument are loaded around the creation of the lambda
+            let mut invoke = if captured == "()LFn;" {
+                // invokedynamic, aload_0
+                vec![0xba, site_high, site_low, 0, 0, 0x2a]
+            } else {
+                // iload_0, invokedynamic, aload_1
+                vec![0x1a, 0xba, site_high, site_low, 0, 0, 0x2b]
+            };
+            // invokeinterface apply, checkcast Integer, getfield value, ireturn
+            invoke.extend([
+                0xb9, apply_high, apply_low, 2, 0, 0xc0, cast_high, cast_low, 0xb4, value_high,
+                value_low, 0xac,
+            ]);
+
+            class.method(0x0009, name, descriptor, code(&invoke, &[]));
+        }
+
+        test_env(&[function, integer, class])
+    }
+
+    fn new_integer(env: &JvmExecEnv, value: JvmInt) -> RuntimeType {
+        let integer = env
+            .heap
+            .new_object(env.get_class("java/lang/Integer").unwrap());
+        integer
+            .get()
+            .unwrap()
+            .write_field("java/lang/Integer", "value", RuntimeType::Int(value))
+            .unwrap();
+
+        RuntimeType::Class(integer)
+    }
+
+    #[test]
+    fn method_references_box_and_unbox_their_values() {
+        let env = lambda_env();
+        let twice = |value| run_int(&env, "twice", "(Ljava/lang/Integer;)I", vec![value]);
+
+        assert_eq!(twice(new_integer(&env, 21)), 42);
+        assert_eq!(twice(new_integer(&env, -4)), -8);
+        assert_eq!(
+            thrown(run_static(
+                &env,
+                "twice",
+                "(Ljava/lang/Integer;)I",
+                vec![RuntimeType::Class(ObjectRef::new_null())]
+            )),
+            "java.lang.NullPointerException: cannot unbox null to int"
+        );
+    }
+
+    #[test]
+    fn capturing_lambdas_pass_the_captured_values_first() {
+        let env = lambda_env();
+        let add = |captured, value| {
+            let args = vec![RuntimeType::Int(captured), new_integer(&env, value)];
+
+            run_int(&env, "add", "(ILjava/lang/Integer;)I", args)
+        };
+
+        assert_eq!(add(40, 2), 42);
+        assert_eq!(add(-1, 1), 0);
+    }
+
+    #[test]
+    fn integer_division_takes_the_dividend_first() {
+        let mut class = ClassBytes::new("Test", 49);
+        // iload_0, iload_1, idiv, ireturn
+        class.method(0x0009, "div", "(II)I", code(&[0x1a, 0x1b, 0x6c, 0xac], &[]));
+        // iload_0, iload_1, irem, ireturn
+        class.method(0x0009, "rem", "(II)I", code(&[0x1a, 0x1b, 0x70, 0xac], &[]));
+
+        let env = test_env(&[class]);
+        let run = |name, l, r| {
+            run_int(
+                &env,
+                name,
+                "(II)I",
+                vec![RuntimeType::Int(l), RuntimeType::Int(r)],
+            )
+        };
+
+        assert_eq!(run("div", 7, 2), 3);
+        assert_eq!(run("div", -7, 2), -3);
+        assert_eq!(run("div", JvmInt::MIN, -1), JvmInt::MIN);
+        assert_eq!(run("rem", 7, 2), 1);
+        assert_eq!(run("rem", -7, 2), -1);
+        assert_eq!(run("rem", 7, -2), 1);
+        assert_eq!(run("rem", JvmInt::MIN, -1), 0);
+        assert_eq!(
+            thrown(run_static(
+                &env,
+                "div",
+                "(II)I",
+                vec![RuntimeType::Int(1), RuntimeType::Int(0)]
+            )),
+            "java.lang.ArithmeticException: / by zero"
+        );
+    }
+
+    #[test]
+    fn long_comparison_takes_the_first_operand_first() {
+        let mut class = ClassBytes::new("Test", 49);
+        // lload_0, lload_2, lcmp, ireturn
+        class.method(0x0009, "cmp", "(JJ)I", code(&[0x1e, 0x20, 0x94, 0xac], &[]));
+
+        let env = test_env(&[class]);
+        let cmp = |l, r| {
+            run_int(
+                &env,
+                "cmp",
+                "(JJ)I",
+                vec![RuntimeType::Long(l), RuntimeType::Long(r)],
+            )
+        };
+
+        assert_eq!(cmp(1, 2), -1);
+        assert_eq!(cmp(2, 1), 1);
+        assert_eq!(cmp(-5, -5), 0);
+        assert_eq!(cmp(JvmLong::MIN, JvmLong::MAX), -1);
+    }
+
+    /// A method switching on its int argument after the given number of nops, returning 10, 11
+    /// and 12 for the cases 1, 2 and 3 (matched by a tableswitch or a lookupswitch), and -1 by
+    /// default
+    fn switch_code(nops: usize, is_lookup: bool) -> Vec<u8> {
+        let mut code = vec![0x00; nops];
+        // iload_0
+        code.push(0x1a);
+
+        let op_pc = code.len();
+        code.push(if is_lookup { 0xab } else { 0xaa });
+        // The operands are aligned on 4 bytes from the start of the code
+        code.resize(code.len() + 3 - op_pc % 4, 0);
+
+        let operands_end = code.len() + if is_lookup { 8 + 3 * 8 } else { 12 + 3 * 4 };
+        let offset = |block: usize| ((operands_end + 3 * block - op_pc) as i32).to_be_bytes();
+
+        code.extend(offset(3));
+
+        if is_lookup {
+            code.extend(3i32.to_be_bytes());
+
+            for case in 0..3 {
+                code.extend((case as i32 + 1).to_be_bytes());
+                code.extend(offset(case));
+            }
+        } else {
+            code.extend(1i32.to_be_bytes());
+            code.extend(3i32.to_be_bytes());
+
+            for case in 0..3 {
+                code.extend(offset(case));
+            }
+        }
+
+        // bipush, ireturn
+        for value in [10, 11, 12, -1i8] {
+            code.extend([0x10, value as u8, 0xac]);
+        }
+
+        code
+    }
+
+    #[test]
+    fn switch_operands_are_aligned_from_the_start_of_the_code() {
+        let mut class = ClassBytes::new("Test", 49);
+        let mut names = vec![];
+
+        for nops in 0..4 {
+            for is_lookup in [false, true] {
+                let name = format!("{}{nops}", if is_lookup { "lookup" } else { "table" });
+                class.method(
+                    0x0009,
+                    &name,
+                    "(I)I",
+                    code(&switch_code(nops, is_lookup), &[]),
+                );
+                names.push(name);
+            }
+        }
+
+        let env = test_env(&[class]);
+
+        for name in &names {
+            let switch = |value| run_int(&env, name, "(I)I", vec![RuntimeType::Int(value)]);
+
+            assert_eq!(switch(1), 10, "{name}");
+            assert_eq!(switch(2), 11, "{name}");
+            assert_eq!(switch(3), 12, "{name}");
+            assert_eq!(switch(0), -1, "{name}");
+            assert_eq!(switch(4), -1, "{name}");
+        }
+    }
+
+    #[test]
+    fn monitors_are_only_exited_by_their_owner() {
+        let mut class = ClassBytes::new("Test", 49);
+        // aload_0, monitorenter, aload_0, monitorexit, iconst_1, ireturn
+        let balanced = [0x2a, 0xc2, 0x2a, 0xc3, 0x04, 0xac];
+        class.method(
+            0x0009,
+            "balanced",
+            "(Ljava/lang/Object;)I",
+            code(&balanced, &[]),
+        );
+        // aload_0, monitorexit, iconst_1, ireturn
+        let unowned = [0x2a, 0xc3, 0x04, 0xac];
+        class.method(
+            0x0009,
+            "unowned",
+            "(Ljava/lang/Object;)I",
+            code(&unowned, &[]),
+        );
+        // aload_0, monitorenter, iconst_1, ireturn
+        let unbalanced = [0x2a, 0xc2, 0x04, 0xac];
+        class.method(
+            0x0009,
+            "unbalanced",
+            "(Ljava/lang/Object;)I",
+            code(&unbalanced, &[]),
+        );
+
+        let env = test_env(&[class]);
+        let object = env
+            .heap
+            .new_object(env.get_class("java/lang/Object").unwrap());
+        let run = |name| {
+            let args = vec![RuntimeType::Class(object.clone())];
+
+            run_static(&env, name, "(Ljava/lang/Object;)I", args)
+        };
+
+        assert!(matches!(run("balanced"), Ok(Some(RuntimeType::Int(1)))));
+        assert_eq!(
+            thrown(run("unowned")),
+            "java.lang.IllegalMonitorStateException: current thread is not owner"
+        );
+        assert_eq!(
+            thrown(run("unbalanced")),
+            "java.lang.IllegalMonitorStateException: unbalanced monitors on return from unbalanced"
+        );
+    }
+
+    #[test]
+    fn classes_whose_initializer_failed_are_not_initialized_again() {
+        let mut failing = ClassBytes::new("Failing", 49);
+        failing.field(0x0009, "value", "I");
+        // iconst_1, iconst_0, idiv, putstatic value, return
+        let [high, low] = failing.field_ref("Failing", "value", "I").to_be_bytes();
+        let initializer = [0x04, 0x03, 0x6c, 0xb3, high, low, 0xb1];
+        failing.method(0x0008, "<clinit>", "()V", code(&initializer, &[]));
+
+        let mut class = ClassBytes::new("Test", 49);
+        let [high, low] = class.field_ref("Failing", "value", "I").to_be_bytes();
+        // getstatic value, ireturn
+        class.method(0x0009, "value", "()I", code(&[0xb2, high, low, 0xac], &[]));
+
+        let env = test_env(&[failing, class]);
+
+        assert_eq!(
+            thrown(run_static(&env, "value", "()I", vec![])),
+            "java.lang.ExceptionInInitializerError\n\
+             Caused by: java.lang.ArithmeticException: / by zero"
+        );
+        assert_eq!(
+            thrown(run_static(&env, "value", "()I", vec![])),
+            "java.lang.NoClassDefFoundError: Could not initialize class Failing"
+        );
+    }
+}
@@ -1,14 +1,23 @@
 use std::sync::Arc;
 
-use crate::types::JvmTypeDescriptor;
+use crate::{
+    class::{
+        JvmVisibility,
+        attributes::{ExceptionTableEntry, StackMapFrame},
+        constant_pool::ConstantMethodHandle,
+    },
+    types::JvmTypeDescriptor,
+};
 
-use super::{heap::ObjectRef, runtime_type::RuntimeType};
+use super::{heap::ObjectRef, invoke::LambdaAdaptations, runtime_type::RuntimeType};
 
 #[derive(Debug, Clone)]
 pub struct Method {
     return_type: Option<JvmTypeDescriptor>,
     parameters: Vec<JvmTypeDescriptor>,
     name: Arc<String>,
+    vis: JvmVisibility,
+    is_synchronized: bool,
     spec: MethodSpec,
 }
 
@@ -17,6 +26,7 @@ pub enum MethodSpec {
     Normal(NormalMethod),
     Abstract(AbstractMethod),
     Native(NativeMethod),
+    Forwarding(ForwardingMethod),
 }
 
 impl Method {
@@ -24,21 +34,15 @@ impl Method {
         return_type: Option<JvmTypeDescriptor>,
         parameters: Vec<JvmTypeDescriptor>,
         name: Arc<String>,
-        is_static: bool,
-        cp_start: usize,
-        cp_end: usize,
-        local_count: usize,
+        code: NormalMethod,
     ) -> Self {
         Self {
             return_type,
             parameters,
             name,
-            spec: MethodSpec::Normal(NormalMethod {
-                is_static,
-                cp_start,
-                cp_end,
-                local_count,
-            }),
+            vis: JvmVisibility::Public,
+            is_synchronized: false,
+            spec: MethodSpec::Normal(code),
         }
     }
 
@@ -51,6 +55,8 @@ impl Method {
             return_type,
             parameters,
             name,
+            vis: JvmVisibility::Public,
+            is_synchronized: false,
             spec: MethodSpec::Abstract(AbstractMethod {}),
         }
     }
@@ -65,18 +71,65 @@ impl Method {
             return_type,
             parameters,
             name,
+            vis: JvmVisibility::Public,
+            is_synchronized: false,
             spec: MethodSpec::Native(NativeMethod { is_static }),
         }
     }
 
+    /// Creates a method of a synthetic class, forwarding its invocations to a method handle
+    pub fn new_forwarding(
+        return_type: Option<JvmTypeDescriptor>,
+        parameters: Vec<JvmTypeDescriptor>,
+        name: Arc<String>,
+        target: Arc<ConstantMethodHandle>,
+        captured_fields: Arc<[Arc<String>]>,
+        adaptations: LambdaAdaptations,
+    ) -> Self {
+        Self {
+            return_type,
+            parameters,
+            name,
+            vis: JvmVisibility::Public,
+            is_synchronized: false,
+            spec: MethodSpec::Forwarding(ForwardingMethod {
+                target,
+                captured_fields,
+                adaptations,
+            }),
+        }
+    }
+
+    pub fn with_visibility(mut self, vis: JvmVisibility) -> Self {
+        self.vis = vis;
+        self
+    }
+
+    pub fn with_synchronized(mut self, is_synchronized: bool) -> Self {
+        self.is_synchronized = is_synchronized;
+        self
+    }
+
     pub fn is_static(&self) -> bool {
         match &self.spec {
             MethodSpec::Normal(method) => method.is_static,
             MethodSpec::Native(method) => method.is_static,
-            MethodSpec::Abstract(_) => false,
+            MethodSpec::Abstract(_) | MethodSpec::Forwarding(_) => false,
         }
     }
 
+    pub fn is_abstract(&self) -> bool {
+        matches!(self.spec, MethodSpec::Abstract(_))
+    }
+
+    pub fn is_synchronized(&self) -> bool {
+        self.is_synchronized
+    }
+
+    pub fn is_private(&self) -> bool {
+        matches!(self.vis, JvmVisibility::Private)
+    }
+
     pub fn is_native(&self) -> bool {
         matches!(self.spec, MethodSpec::Native(_));
         match self.spec {
@@ -85,6 +138,17 @@ impl Method {
         }
     }
 
+    pub fn forwarding(&self) -> Option<&ForwardingMethod> {
+        match &self.spec {
+            MethodSpec::Forwarding(method) => Some(method),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &Arc<String> {
+        &self.name
+    }
+
     pub fn parameters(&self) -> &[JvmTypeDescriptor] {
         &self.parameters
     }
@@ -100,20 +164,55 @@ impl Method {
         }
     }
 
+    /// The address following the last instruction of the method
+    pub fn end_pc(&self) -> Option<usize> {
+        match &self.spec {
+            MethodSpec::Normal(normal_method) => Some(normal_method.cp_end),
+            _ => None,
+        }
+    }
+
     pub fn local_count(&self) -> usize {
         match &self.spec {
             MethodSpec::Normal(m) => m.local_count,
             _ => 0,
         }
     }
+
+    pub fn max_stack(&self) -> usize {
+        match &self.spec {
+            MethodSpec::Normal(m) => m.max_stack,
+            _ => 0,
+        }
+    }
+
+    /// The exception handlers of the method, whose pcs are relative to its start
+    pub fn exception_table(&self) -> &[ExceptionTableEntry] {
+        match &self.spec {
+            MethodSpec::Normal(m) => &m.exception_table,
+            _ => &[],
+        }
+    }
+
+    /// The entries of the StackMapTable attribute of the method, checked by the verifier
+    pub fn stack_map(&self) -> &[StackMapFrame] {
+        match &self.spec {
+            MethodSpec::Normal(m) => &m.stack_map,
+            _ => &[],
+        }
+    }
 }
 
+/// A method with bytecode, copied in the code of the environment between cp_start and cp_end
 #[derive(Debug, Clone)]
 pub struct NormalMethod {
-    is_static: bool,
-    cp_start: usize,
-    cp_end: usize,
-    local_count: usize,
+    pub is_static: bool,
+    pub cp_start: usize,
+    pub cp_end: usize,
+    pub local_count: usize,
+    pub max_stack: usize,
+    pub exception_table: Arc<[ExceptionTableEntry]>,
+    pub stack_map: Arc<[StackMapFrame]>,
 }
 
 #[derive(Debug, Clone)]
@@ -124,6 +223,16 @@ pub struct NativeMethod {
     is_static: bool,
 }
 
+/// A method of a synthetic class (like the ones implementing lambdas), invoking its target
+/// method handle with the values captured in the fields of the receiver followed by its own
+/// arguments, converted to the parameter types of the target
+#[derive(Debug, Clone)]
+pub struct ForwardingMethod {
+    pub target: Arc<ConstantMethodHandle>,
+    pub captured_fields: Arc<[Arc<String>]>,
+    pub adaptations: LambdaAdaptations,
+}
+
 #[derive(Debug, Clone)]
 pub enum ReturnResult {
     Complete(RuntimeType),
@@ -1,20 +1,26 @@
 use std::{
     collections::{HashMap, HashSet},
     iter::once,
-    sync::Arc,
+    str::FromStr,
+    sync::{Arc, atomic::AtomicUsize},
 };
 
-use class::{Class, ClassField, ConstantPool};
+use anyhow::{Context, anyhow, bail};
+use class::{Class, ClassField, ClassMembers, ConstantPool};
 use either::Either;
+use exception::{JavaException, throw};
 use heap::JvmHeap;
 use interface::Interface;
+use jpu::builtin_class_name;
 use log::debug;
-use method::Method;
+use method::{Method, NormalMethod};
+use parking_lot::{Mutex, RwLock};
 use runtime_type::RuntimeType;
+use thread::SuspendedThreads;
 
 use crate::{
     class::{
-        JvmClass, JvmUnit, JvmUnitField, JvmUnitMethod, JvmUnitType,
+        ClassFormatError, JvmClass, JvmUnit, JvmUnitField, JvmUnitMethod, JvmUnitType,
         constant_pool::{ConstantMethodHandle, LoadableJvmConstant},
     },
     native::jnb::JnbObjectType,
@@ -23,66 +29,309 @@ use crate::{
 
 pub mod array;
 pub mod class;
+pub mod exception;
 pub mod heap;
 pub mod interface;
+pub mod invoke;
 pub mod jpu;
 pub mod method;
+pub mod monitor;
 pub mod runtime_type;
 pub mod thread;
+pub mod verifier;
+
+/// Finds and parses the units of the classes and interfaces to load, by binary name
+/// (java/lang/Object)
+pub trait UnitLoader: Send + Sync {
+    fn load_unit(&self, name: &str) -> anyhow::Result<JvmUnit>;
+}
 
 #[derive(Default)]
 pub struct JvmExecEnv {
-    pub classes: HashMap<String, Class>,
-    pub interfaces: HashMap<String, Interface>,
+    /// Loaded classes, including the synthetic ones created while running
+    pub classes: RwLock<HashMap<String, Class>>,
+    pub interfaces: RwLock<HashMap<String, Interface>>,
     pub heap: JvmHeap,
     // pub threads: Vec<JvmThread>,
-    pub start_class: Option<Class>,
-    pub code: Vec<u8>,
+    pub code: RwLock<Vec<u8>>,
+
+    loader: Option<Box<dyn UnitLoader>>,
+    /// The system properties, as returned by System.getProperty
+    pub system_properties: HashMap<String, String>,
+    /// State of the loading of classes, also preventing concurrent loadings
+    loading: Mutex<LoadingState>,
+    /// Number of synthetic classes created, used to name them uniquely
+    pub synthetic_class_count: AtomicUsize,
+    /// The threads suspended while another one runs, whose frames are roots of the garbage
+    /// collector
+    pub suspended_threads: SuspendedThreads,
+}
+
+#[derive(Debug, Default)]
+struct LoadingState {
+    /// Units loaded but waiting for their superclass or superinterfaces to be loaded
+    partial_classes: Vec<PartialClass>,
+    /// Units referenced by the loaded ones (in descriptors or constants), only loaded on their
+    /// first use
+    differed_units: HashSet<String>,
+    /// Units which could not be loaded, along with the reason, as resolution is not retried
+    /// (JVMS §5.4.3)
+    failed_units: HashMap<String, LoadFailure>,
+}
+
+/// Why a unit could not be loaded
+#[derive(Debug, Clone)]
+enum LoadFailure {
+    /// The class file is malformed, which is thrown as a ClassFormatError
+    Format(ClassFormatError),
+    Other(String),
+}
 
-    pub partial_classes: Vec<PartialClass>,
-    pub required_units: HashSet<String>,
-    pub differed_units: HashSet<String>,
+impl LoadFailure {
+    fn to_error(&self) -> anyhow::Error {
+        match self {
+            Self::Format(err) => {
+                JavaException::new("java/lang/ClassFormatError", err.to_string()).into()
+            }
+            Self::Other(reason) => anyhow!("{reason}"),
+        }
+    }
 }
 
 impl JvmExecEnv {
     pub fn new() -> Self {
-        Self {
-            classes: HashMap::new(),
-            interfaces: HashMap::new(),
-            heap: JvmHeap::new(),
-            // threads: Vec::new(),
-            start_class: None,
-            code: Vec::new(),
-            partial_classes: Vec::new(),
-            required_units: HashSet::new(),
-            differed_units: HashSet::new(),
-        }
+        Self::default()
     }
 
-    pub fn missing_units(&self) -> HashSet<String> {
-        let mut res = self
-            .partial_classes
-            .iter()
-            .map(|c| {
-                let missing = c.missing_unit_names();
-                debug!("Missing unit names for {}: {:?}", c.name, missing);
+    /// Sets the loader of the classes and interfaces, called when they are first used
+    pub fn with_loader(mut self, loader: impl UnitLoader + 'static) -> Self {
+        self.loader = Some(Box::new(loader));
+        self
+    }
+
+    /// Sets the system properties, as defined by the launcher
+    pub fn with_system_properties(mut self, system_properties: HashMap<String, String>) -> Self {
+        self.system_properties = system_properties;
+        self
+    }
 
-                missing
+    /// Gets a class, loading it if needed
+    pub fn get_class(&self, name: &str) -> Option<Class> {
+        self.load_quietly(name);
+        self.classes.read().get(name).cloned()
+    }
+
+    /// Gets an interface, loading it if needed
+    pub fn get_interface(&self, name: &str) -> Option<Interface> {
+        self.load_quietly(name);
+        self.interfaces.read().get(name).cloned()
+    }
+
+    /// Runs the garbage collector, with the frames of the suspended threads and the static fields
+    /// of the loaded classes and interfaces as roots besides the given ones
+    pub fn collect_garbage(&self, roots: impl IntoIterator<Item = RuntimeType>) {
+        let suspended = self.suspended_threads.references();
+        let statics = self
+            .classes
+            .read()
+            .values()
+            .chain(self.interfaces.read().values().map(Interface::as_class))
+            .flat_map(|class| {
+                let mut references = class.static_references();
+                references.extend(class.call_site_errors());
+                references
             })
-            .fold(HashSet::new(), |mut v, next| {
-                v.extend(next);
-                v
-            });
+            .collect::<Vec<_>>();
 
-        debug!("required_units: {:?}", self.required_units);
+        self.heap
+            .collect(roots.into_iter().chain(suspended).chain(statics));
+    }
+
+    /// Gets a class or interface, loading it if needed
+    pub fn get_class_or_interface(&self, name: &str) -> Option<Class> {
+        self.get_class(name)
+            .or_else(|| self.get_interface(name).map(|i| i.as_class().clone()))
+    }
 
-        res.extend(self.required_units.iter().cloned());
+    /// Whether values of a reference type are also values of another reference type
+    pub fn is_subtype(
+        &self,
+        from: &JvmTypeDescriptor,
+        to: &JvmTypeDescriptor,
+    ) -> anyhow::Result<bool> {
+        Class::is_type_assignable(from, to, &|name| self.get_class_or_interface(name))
+    }
+
+    /// Whether a reference value is null or an instance of the given type
+    pub fn is_instance_of(
+        &self,
+        value: &RuntimeType,
+        ty: &JvmTypeDescriptor,
+    ) -> anyhow::Result<bool> {
+        match value {
+            v if v.is_null() => Ok(true),
+            RuntimeType::Class(object) => Ok(object
+                .get()
+                .is_some_and(|object| object.class_type.is_subtype_of(ty))),
+            RuntimeType::InternedString(_)
+            | RuntimeType::String(_)
+            | RuntimeType::ClassMirror(_)
+            | RuntimeType::MethodType(_)
+            | RuntimeType::MethodHandle(_) => self.is_subtype(
+                &JvmTypeDescriptor::Class(builtin_class_name(value)?.to_string()),
+                ty,
+            ),
+            RuntimeType::Array(array) => match array.get() {
+                Some(array) => self.is_subtype(
+                    &JvmTypeDescriptor::Array(Box::new(array.compound_type.clone())),
+                    ty,
+                ),
+                None => Ok(true),
+            },
+            v => bail!("unexpected value (reference expected): {v:?}"),
+        }
+    }
+
+    /// The classes and interfaces referenced by the loaded ones but never used, so not loaded
+    pub fn differed_units(&self) -> HashSet<String> {
+        self.loading.lock().differed_units.clone()
+    }
+
+    fn is_loaded(&self, name: &str) -> bool {
+        self.classes.read().contains_key(name) || self.interfaces.read().contains_key(name)
+    }
+
+    /// Loads a class or interface along with its superclasses and superinterfaces, unless it is
+    /// already loaded (JVMS §5.3)
+    pub fn load(&self, name: &str) -> anyhow::Result<()> {
+        if self.is_loaded(name) {
+            return Ok(());
+        }
+
+        let mut state = self.loading.lock();
+
+        // The unit may have been loaded by another thread in the meantime
+        if self.is_loaded(name) {
+            return Ok(());
+        }
+
+        self.load_locked(&mut state, vec![name.to_string()])
+    }
+
+    /// Loads the named class or interface to resolve a reference to it, failing with a
+    /// NoClassDefFoundError if it cannot be found, or with a ClassFormatError if its class file is
+    /// malformed
+    pub fn resolve_type_name(&self, name: &str) -> anyhow::Result<()> {
+        if let Err(err) = self.load(name) {
+            // Malformed class files are reported as such (ClassFormatError)
+            if err.is::<JavaException>() {
+                return Err(err);
+            }
+
+            debug!("unable to load {name}: {err:#}");
+            throw!("java/lang/NoClassDefFoundError", "{name}");
+        }
+
+        Ok(())
+    }
+
+    /// Adds an already parsed unit, loading its superclasses and superinterfaces if needed
+    pub fn add_unit(&self, jvm_unit: JvmUnit) -> anyhow::Result<()> {
+        let mut state = self.loading.lock();
+
+        self.define_unit(&mut state, jvm_unit);
+        self.load_locked(&mut state, vec![])
+    }
+
+    fn load_quietly(&self, name: &str) {
+        if let Err(err) = self.load(name) {
+            debug!("unable to load {name}: {err:#}");
+        }
+    }
+
+    /// Loads the given units, then every unit needed to complete the partial classes
+    fn load_locked(
+        &self,
+        state: &mut LoadingState,
+        mut pending: Vec<String>,
+    ) -> anyhow::Result<()> {
+        let result = 'load: loop {
+            for name in pending {
+                if let Err(err) = self.load_unit(state, &name) {
+                    break 'load Err(err);
+                }
+            }
+
+            self.try_complete(state);
+
+            pending = state.missing_units(self);
+
+            if pending.is_empty() {
+                break match state.partial_classes.first() {
+                    Some(partial) => {
+                        Err(anyhow!("class circularity while loading {}", partial.name))
+                    }
+                    None => Ok(()),
+                };
+            }
+        };
+
+        // Classes depending on a unit which could not be loaded are dropped
+        if result.is_err() {
+            state.partial_classes.clear();
+        }
 
-        res
+        result
     }
 
-    pub fn add_unit(&mut self, jvm_unit: JvmUnit, eager_loading: bool) -> bool {
+    fn load_unit(&self, state: &mut LoadingState, name: &str) -> anyhow::Result<()> {
+        if let Some(failure) = state.failed_units.get(name) {
+            return Err(failure.to_error());
+        }
+
+        let loader = self
+            .loader
+            .as_ref()
+            .ok_or_else(|| anyhow!("no class loader to load {name}"))?;
+
+        let unit = loader
+            .load_unit(name)
+            .and_then(|unit| {
+                if unit.this_class.name.as_str() != name {
+                    bail!("wrong name: {}", unit.this_class.name);
+                }
+
+                Ok(unit)
+            })
+            .with_context(|| format!("while loading {name}"));
+
+        match unit {
+            Ok(unit) => {
+                self.define_unit(state, unit);
+                Ok(())
+            }
+            Err(err) => {
+                let Some(format_error) = err.downcast_ref::<ClassFormatError>().cloned() else {
+                    state
+                        .failed_units
+                        .insert(name.to_string(), LoadFailure::Other(format!("{err:#}")));
+                    return Err(err);
+                };
+
+                let failure = LoadFailure::Format(format_error);
+                let error = failure.to_error();
+
+                state.failed_units.insert(name.to_string(), failure);
+                Err(error)
+            }
+        }
+    }
+
+    /// Defines the class or interface of a unit, as a partial class until its superclass and
+    /// superinterfaces are loaded. The other classes it references are only recorded.
+    fn define_unit(&self, state: &mut LoadingState, jvm_unit: JvmUnit) {
         let class_name = jvm_unit.this_class.name;
+        let major_version = jvm_unit.major_version;
 
         let parse_field = |f: &JvmUnitField| ClassField {
             name: f.name.clone(),
@@ -92,14 +341,23 @@ impl JvmExecEnv {
                 .and_then(|_| f.constant_value.clone())
                 .map(RuntimeType::from)
                 .unwrap_or(RuntimeType::default_of(&f.ty)),
+            ty: f.ty.clone(),
             is_final: f.is_final,
         };
 
+        debug!("defining {class_name}");
+
+        state.differed_units.remove(class_name.as_str());
+
+        let mut differ = |name: &str| {
+            if name != class_name.as_str() && !self.is_loaded(name) {
+                state.differed_units.insert(name.to_string());
+            }
+        };
+
         for field in jvm_unit.fields.iter() {
             if let JvmTypeDescriptor::Class(c) = &field.ty {
-                if c != class_name.as_ref() {
-                    self.required_units.insert(c.clone());
-                }
+                differ(c);
             }
         }
 
@@ -110,19 +368,20 @@ impl JvmExecEnv {
                 .chain(once(descriptor.return_type.as_ref()).flatten())
             {
                 if let JvmTypeDescriptor::Class(c) = ty {
-                    if c != class_name.as_ref() {
-                        if eager_loading {
-                            self.required_units.insert(c.clone());
-                        } else {
-                            self.differed_units.insert(c.clone());
-                        }
-                    }
+                    differ(c);
                 }
             }
         }
 
         for constant in &jvm_unit.loadable_constant_pool {
             let v = match constant.1 {
+                // Array classes are not loaded from units, only their element type is
+                LoadableJvmConstant::Class(c) if c.name.starts_with('[') => {
+                    match JvmTypeDescriptor::from_str(&c.name).map(|ty| ty.element_type().clone()) {
+                        Ok(JvmTypeDescriptor::Class(name)) => Some(Arc::new(name)),
+                        _ => None,
+                    }
+                }
                 LoadableJvmConstant::Class(c) => Some(c.name.clone()),
                 LoadableJvmConstant::MethodHandle(
                     ConstantMethodHandle::GetField(f)
@@ -144,13 +403,7 @@ impl JvmExecEnv {
             };
 
             if let Some(c) = v {
-                if c != class_name {
-                    if eager_loading {
-                        self.required_units.insert(c.as_ref().clone());
-                    } else {
-                        self.differed_units.insert(c.as_ref().clone());
-                    }
-                }
+                differ(&c);
             }
         }
 
@@ -175,7 +428,7 @@ impl JvmExecEnv {
             let name = m.name;
             let entry = methods.entry(name.as_ref().clone()).or_default();
 
-            entry.push(if m.is_abstract {
+            let method = if m.is_abstract {
                 Method::new_abstract(m.descriptor.return_type, m.descriptor.parameter_types, name)
             } else if m.is_native {
                 Method::new_native(
@@ -185,34 +438,55 @@ impl JvmExecEnv {
                     m.is_static,
                 )
             } else {
-                let cp_start = self.code.len();
-                self.code.extend_from_slice(&m.code.unwrap().code);
-                let cp_end = self.code.len();
+                let code = m.code.unwrap();
+
+                let mut env_code = self.code.write();
+                let cp_start = env_code.len();
+                env_code.extend_from_slice(&code.code);
+                let cp_end = env_code.len();
 
                 Method::new_normal(
                     m.descriptor.return_type,
                     m.descriptor.parameter_types,
                     name,
-                    m.is_static,
-                    cp_start,
-                    cp_end,
-                    m.local_count,
+                    NormalMethod {
+                        is_static: m.is_static,
+                        cp_start,
+                        cp_end,
+                        local_count: m.local_count,
+                        max_stack: code.max_stack as usize,
+                        exception_table: code.exception_table.into(),
+                        stack_map: code
+                            .stack_map_table
+                            .into_iter()
+                            .flat_map(|t| t.entries)
+                            .collect(),
+                    },
                 )
-            });
+            };
+
+            entry.push(
+                method
+                    .with_visibility(m.vis)
+                    .with_synchronized(m.is_synchronized),
+            );
         }
 
         match jvm_unit.unit_type {
             JvmUnitType::Class(JvmClass { is_abstract, .. }) => {
-                self.partial_classes.push(PartialClass {
+                state.partial_classes.push(PartialClass {
                     super_class: jvm_unit
                         .super_class
                         .map(|s| Either::Left(s.name.as_ref().clone())),
                     name: class_name,
+                    major_version,
                     constant_pool: ConstantPool::new(
                         jvm_unit.loadable_constant_pool,
                         jvm_unit.field_refs,
                         jvm_unit.method_refs,
                         jvm_unit.interface_method_refs,
+                        jvm_unit.dynamic_invokes,
+                        jvm_unit.bootstrap_methods,
                     ),
                     static_fields,
                     fields,
@@ -226,26 +500,54 @@ impl JvmExecEnv {
                         .map(|i| Either::Left(i.name))
                         .collect(),
                     is_abstract,
+                    is_interface: false,
                     jnb: None,
                 });
             }
             JvmUnitType::Interface(_) => {
-                self.interfaces.insert(
-                    class_name.as_ref().clone(),
-                    Interface::new(class_name, static_fields),
-                );
+                // Interfaces are backed by classes, but without any superclass
+                state.partial_classes.push(PartialClass {
+                    super_class: None,
+                    name: class_name,
+                    major_version,
+                    constant_pool: ConstantPool::new(
+                        jvm_unit.loadable_constant_pool,
+                        jvm_unit.field_refs,
+                        jvm_unit.method_refs,
+                        jvm_unit.interface_method_refs,
+                        jvm_unit.dynamic_invokes,
+                        jvm_unit.bootstrap_methods,
+                    ),
+                    static_fields,
+                    fields: Box::new([]),
+                    methods: methods
+                        .drain()
+                        .map(|(k, v)| (k, v.into_boxed_slice()))
+                        .collect(),
+                    interfaces: jvm_unit
+                        .interfaces
+                        .into_iter()
+                        .map(|i| Either::Left(i.name))
+                        .collect(),
+                    is_abstract: true,
+                    is_interface: true,
+                    jnb: None,
+                });
             }
             JvmUnitType::Record(mut rec) => {
-                self.partial_classes.push(PartialClass {
+                state.partial_classes.push(PartialClass {
                     super_class: jvm_unit
                         .super_class
                         .map(|s| Either::Left(s.name.as_ref().clone())),
                     name: class_name,
+                    major_version,
                     constant_pool: ConstantPool::new(
                         jvm_unit.loadable_constant_pool,
                         jvm_unit.field_refs,
                         jvm_unit.method_refs,
                         jvm_unit.interface_method_refs,
+                        jvm_unit.dynamic_invokes,
+                        jvm_unit.bootstrap_methods,
                     ),
                     static_fields,
                     fields: rec
@@ -254,6 +556,7 @@ impl JvmExecEnv {
                         // TODO: check if this is the proper way to handle records components (or if should do the same as with classes)
                         .map(|c| ClassField {
                             value: RuntimeType::default_of(&c.descriptor),
+                            ty: c.descriptor,
                             name: c.name,
                             is_final: true,
                         })
@@ -269,62 +572,71 @@ impl JvmExecEnv {
                         .map(|i| Either::Left(i.name))
                         .collect(),
                     is_abstract: false,
+                    is_interface: false,
                     jnb: None,
                 });
             }
             JvmUnitType::Module(_) => (), // TODO: Modules
         }
-
-        self.try_complete()
     }
 
-    fn try_complete(&mut self) -> bool {
+    /// Turns the partial classes whose superclass and superinterfaces are loaded into classes
+    /// and interfaces
+    fn try_complete(&self, state: &mut LoadingState) {
         loop {
-            if self.partial_classes.is_empty() {
+            if state.partial_classes.is_empty() {
                 break;
             }
 
-            let last_partial_count = self.partial_classes.len();
+            let last_partial_count = state.partial_classes.len();
             let mut still_incomplete = vec![];
 
-            for (idx, content) in self.partial_classes.drain(..).enumerate() {
-                match content.try_complete(&self.classes, &self.interfaces) {
+            for content in state.partial_classes.drain(..) {
+                let is_interface = content.is_interface;
+
+                let completed = content.try_complete(&self.classes.read(), &self.interfaces.read());
+
+                match completed {
                     Either::Left(incomplete) => still_incomplete.push(incomplete),
+                    Either::Right(complete) if is_interface => {
+                        self.interfaces
+                            .write()
+                            .insert(complete.name.as_ref().clone(), Interface::new(complete));
+                    }
                     Either::Right(complete) => {
                         self.classes
-                            .insert(complete.name.as_ref().clone(), complete.clone());
-
-                        if idx == 0 && self.start_class.is_none() {
-                            self.start_class = Some(complete);
-                        }
+                            .write()
+                            .insert(complete.name.as_ref().clone(), complete);
                     }
                 }
             }
 
-            self.partial_classes = still_incomplete;
+            state.partial_classes = still_incomplete;
 
-            if self.partial_classes.len() == last_partial_count {
+            if state.partial_classes.len() == last_partial_count {
                 break;
             }
         }
+    }
+}
 
-        let mut still_missing = HashSet::new();
-
-        for missing in self.required_units.drain() {
-            if !self.classes.contains_key(&missing)
-                && !self.interfaces.contains_key(&missing)
-                && !self
-                    .partial_classes
-                    .iter()
-                    .any(|c| c.name.as_ref() == &missing)
-            {
-                still_missing.insert(missing);
-            }
-        }
+impl LoadingState {
+    /// The superclasses and superinterfaces of the partial classes which are not loaded yet
+    fn missing_units(&self, env: &JvmExecEnv) -> Vec<String> {
+        let mut missing = self
+            .partial_classes
+            .iter()
+            .flat_map(PartialClass::missing_unit_names)
+            .filter(|name| {
+                !env.is_loaded(name)
+                    && !self.partial_classes.iter().any(|c| c.name.as_str() == name)
+            })
+            .collect::<Vec<_>>();
 
-        self.required_units = still_missing;
+        missing.sort();
+        missing.dedup();
 
-        self.partial_classes.is_empty() && self.required_units.is_empty()
+        missing
     }
 }
 
@@ -332,12 +644,14 @@ impl JvmExecEnv {
 pub struct PartialClass {
     super_class: Option<Either<String, Class>>,
     name: Arc<String>,
+    major_version: u16,
     constant_pool: ConstantPool,
     static_fields: HashMap<String, ClassField>,
     fields: Box<[ClassField]>,
     methods: HashMap<String, Box<[Method]>>,
     interfaces: Vec<Either<Arc<String>, Interface>>,
     is_abstract: bool,
+    is_interface: bool,
     jnb: Option<Box<dyn JnbObjectType>>,
 }
 
@@ -389,12 +703,15 @@ impl PartialClass {
                     .map(|i| i.unwrap_right())
                     .collect(),
                 self.name,
+                self.major_version,
                 self.constant_pool,
-                self.static_fields,
-                self.fields,
-                self.methods,
-                self.is_abstract,
-                self.jnb,
+                ClassMembers {
+                    static_fields: self.static_fields,
+                    fields: self.fields,
+                    methods: self.methods,
+                    is_abstract: self.is_abstract,
+                    jnb_type: self.jnb,
+                },
             ))
         } else {
             Either::Left(self)
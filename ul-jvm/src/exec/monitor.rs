@@ -0,0 +1,62 @@
+use std::{
+    sync::{Arc, OnceLock},
+    thread::{self, ThreadId},
+};
+
+use parking_lot::{Condvar, Mutex};
+
+/// A reentrant monitor, as held by monitorenter and synchronized methods (JVMS §2.11.10)
+#[derive(Debug, Default)]
+pub struct Monitor {
+    state: Mutex<MonitorState>,
+    released: Condvar,
+}
+
+#[derive(Debug, Default)]
+struct MonitorState {
+    owner: Option<ThreadId>,
+    entry_count: usize,
+}
+
+impl Monitor {
+    /// Enters the monitor, waiting for other threads to release it
+    pub fn enter(&self) {
+        let current = thread::current().id();
+        let mut state = self.state.lock();
+
+        while state.owner.is_some_and(|owner| owner != current) {
+            self.released.wait(&mut state);
+        }
+
+        state.owner = Some(current);
+        state.entry_count += 1;
+    }
+
+    /// Exits the monitor, returning false when it is not owned by the current thread
+    pub fn exit(&self) -> bool {
+        let mut state = self.state.lock();
+
+        if state.owner != Some(thread::current().id()) {
+            return false;
+        }
+
+        state.entry_count -= 1;
+
+        if state.entry_count == 0 {
+            state.owner = None;
+            self.released.notify_one();
+        }
+
+        true
+    }
+}
+
+/// The monitor of an object, only inflated once it is used
+#[derive(Debug, Default)]
+pub struct LazyMonitor(OnceLock<Arc<Monitor>>);
+
+impl LazyMonitor {
+    pub fn get(&self) -> Arc<Monitor> {
+        self.0.get_or_init(Default::default).clone()
+    }
+}
@@ -1,6 +1,12 @@
+use std::sync::Arc;
+
+use anyhow::bail;
+
 use crate::{
-    class::constant_pool::{ConstantJvmUtf8, LoadableJvmConstant},
-    types::{JvmDouble, JvmFloat, JvmInt, JvmLong, JvmTypeDescriptor, NativeJvmType},
+    class::constant_pool::{ConstantJvmUtf8, ConstantMethodHandle, LoadableJvmConstant},
+    types::{
+        JvmDouble, JvmFloat, JvmInt, JvmLong, JvmMethodDescriptor, JvmTypeDescriptor, NativeJvmType,
+    },
 };
 
 use super::heap::{ArrayRef, ObjectRef};
@@ -13,7 +19,17 @@ pub enum RuntimeType {
     Double(JvmDouble),
     Array(ArrayRef),
     Class(ObjectRef),
+    /// A string literal or the result of String.intern, the same instance for the same content
     InternedString(ConstantJvmUtf8),
+    /// A java/lang/String instance created at run time (by a string concatenation, or for the
+    /// arguments of main), distinct from any other one
+    String(Arc<String>),
+    /// The java/lang/Class instance of a type, as loaded by ldc
+    ClassMirror(Arc<JvmTypeDescriptor>),
+    /// A java/lang/invoke/MethodType instance, as loaded by ldc
+    MethodType(Arc<JvmMethodDescriptor>),
+    /// A java/lang/invoke/MethodHandle instance, as loaded by ldc
+    MethodHandle(Arc<ConstantMethodHandle>),
     ReturnAddress(usize),
 }
 
@@ -21,6 +37,57 @@ impl RuntimeType {
     pub fn is_two_slots(&self) -> bool {
         matches!(self, Self::Long(_) | Self::Double(_))
     }
+
+    pub fn is_reference(&self) -> bool {
+        matches!(
+            self,
+            Self::Array(_)
+                | Self::Class(_)
+                | Self::InternedString(_)
+                | Self::String(_)
+                | Self::ClassMirror(_)
+                | Self::MethodType(_)
+                | Self::MethodHandle(_)
+        )
+    }
+
+    /// Whether the value references an array or object allocated in the heap
+    pub fn is_heap_reference(&self) -> bool {
+        matches!(self, Self::Array(_) | Self::Class(_)) && !self.is_null()
+    }
+
+    /// The content of a string instance represented natively, interned or not
+    pub fn as_native_string(&self) -> Option<&Arc<String>> {
+        match self {
+            Self::InternedString(v) | Self::String(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    pub fn is_null(&self) -> bool {
+        match self {
+            Self::Array(v) => v.is_null(),
+            Self::Class(v) => v.is_null(),
+            _ => false,
+        }
+    }
+
+    /// Reference equality, as checked by if_acmp<cond>. Both values are expected to be references.
+    pub fn same_reference(&self, other: &Self) -> bool {
+        match (self, other) {
+            (l, r) if l.is_null() || r.is_null() => l.is_null() && r.is_null(),
+            (Self::Array(l), Self::Array(r)) => l.ptr_eq(r),
+            (Self::Class(l), Self::Class(r)) => l.ptr_eq(r),
+            // Interned strings with the same content are the same instance
+            (Self::InternedString(l), Self::InternedString(r)) => l == r,
+            (Self::String(l), Self::String(r)) => Arc::ptr_eq(l, r),
+            // Types have a single Class instance, and loading a constant gives the same instance
+            (Self::ClassMirror(l), Self::ClassMirror(r)) => l == r,
+            (Self::MethodType(l), Self::MethodType(r)) => l == r,
+            (Self::MethodHandle(l), Self::MethodHandle(r)) => Arc::ptr_eq(l, r),
+            _ => false,
+        }
+    }
 }
 
 impl From<LoadableJvmConstant> for RuntimeType {
@@ -32,11 +99,9 @@ impl From<LoadableJvmConstant> for RuntimeType {
             LoadableJvmConstant::Float(v) => Self::Float(v),
             LoadableJvmConstant::Long(v) => Self::Long(v),
             LoadableJvmConstant::Double(v) => Self::Double(v),
-            LoadableJvmConstant::MethodHandle(_) => {
-                todo!("MethodHandle from constant not yet implemented")
-            }
-            LoadableJvmConstant::MethodType { .. } => {
-                todo!("MethodType from constant not yet implemented")
+            LoadableJvmConstant::MethodHandle(handle) => Self::MethodHandle(Arc::new(handle)),
+            LoadableJvmConstant::MethodType { descriptor } => {
+                Self::MethodType(Arc::new(descriptor))
             }
             LoadableJvmConstant::Dynamic { .. } => {
                 todo!("Dynamic from constant not yet implemented")
@@ -54,14 +119,55 @@ impl RuntimeType {
             | JvmTypeDescriptor::Short
             | JvmTypeDescriptor::Boolean => Self::Int(0),
             JvmTypeDescriptor::Long => Self::Long(0),
-            JvmTypeDescriptor::Double => Self::Float(0f32),
-            JvmTypeDescriptor::Float => Self::Double(0f64),
+            JvmTypeDescriptor::Double => Self::Double(0f64),
+            JvmTypeDescriptor::Float => Self::Float(0f32),
             JvmTypeDescriptor::Class(_) => Self::Class(ObjectRef::new_null()),
             JvmTypeDescriptor::Array(_) => Self::Array(ArrayRef::new_null()),
         }
     }
 
+    /// Checks that this value can be stored in something of the given type (a field, the result
+    /// of a method...), narrowing ints to boolean, byte, char and short as required by the JVMS.
+    pub fn store_as(self, ty: &JvmTypeDescriptor) -> anyhow::Result<Self> {
+        Ok(match (ty, self) {
+            (JvmTypeDescriptor::Boolean, Self::Int(v)) => Self::Int(v & 1),
+            (JvmTypeDescriptor::Byte, Self::Int(v)) => Self::Int(v as i8 as JvmInt),
+            (JvmTypeDescriptor::Char, Self::Int(v)) => Self::Int(v as u16 as JvmInt),
+            (JvmTypeDescriptor::Short, Self::Int(v)) => Self::Int(v as i16 as JvmInt),
+            (JvmTypeDescriptor::Int, v @ Self::Int(_))
+            | (JvmTypeDescriptor::Long, v @ Self::Long(_))
+            | (JvmTypeDescriptor::Float, v @ Self::Float(_))
+            | (JvmTypeDescriptor::Double, v @ Self::Double(_)) => v,
+            (JvmTypeDescriptor::Class(_) | JvmTypeDescriptor::Array(_), v) if v.is_reference() => v,
+            (ty, v) => bail!("{v:?} cannot be stored as {ty:?}"),
+        })
+    }
+
     pub fn try_into_native<N: NativeJvmType>(&self) -> Option<N> {
         N::try_from_rt(self)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn interned_strings_are_the_same_reference_for_the_same_content() {
+        let literal = RuntimeType::InternedString(Arc::new(String::from("v3")));
+        let interned = RuntimeType::InternedString(Arc::new(String::from("v3")));
+
+        assert!(literal.same_reference(&interned));
+    }
+
+    #[test]
+    fn strings_created_at_run_time_are_distinct_instances() {
+        let concatenated = RuntimeType::String(Arc::new(String::from("v3")));
+        let other = RuntimeType::String(Arc::new(String::from("v3")));
+        let literal = RuntimeType::InternedString(Arc::new(String::from("v3")));
+
+        assert!(concatenated.same_reference(&concatenated.clone()));
+        assert!(!concatenated.same_reference(&other));
+        assert!(!concatenated.same_reference(&literal));
+    }
+}
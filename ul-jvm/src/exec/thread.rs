@@ -1,27 +1,101 @@
-use std::io::Write;
+use std::{
+    collections::HashMap,
+    io::Write,
+    sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    },
+};
 
 use anyhow::{anyhow, bail};
-use log::{info, trace};
+use log::{debug, info, trace};
+use parking_lot::Mutex;
 
-use crate::types::{JvmInt, JvmMethodDescriptor};
+use crate::types::{JvmDouble, JvmFloat, JvmInt, JvmLong, JvmMethodDescriptor};
 
 use super::{
-    JvmExecEnv, class::Class, jpu::JvmProcessUnit, method::Method, runtime_type::RuntimeType,
+    JvmExecEnv,
+    class::Class,
+    exception::{JavaException, throw},
+    heap::ObjectRef,
+    interface::Interface,
+    invoke::Adaptation,
+    jpu::{Condition, JvmProcessUnit},
+    method::Method,
+    monitor::Monitor,
+    runtime_type::RuntimeType,
 };
 
 #[derive(Debug)]
 pub struct JvmThread {
     pub pc: usize,
     pub stack: Vec<StackFrame>,
-    pub operand_stack: Vec<RuntimeType>,
-    skip_static_init: bool,
+    /// The value returned by the method the thread started with
+    pub result: Option<RuntimeType>,
 }
 
 #[derive(Debug)]
 pub struct StackFrame {
     pub return_pc: usize,
+    /// Address of the first instruction of the running method, as branch offsets and switch
+    /// paddings are relative to it
+    pub code_start: usize,
     pub current_class: Class,
+    pub method: Method,
     pub locals: Box<[Option<RuntimeType>]>,
+    pub operand_stack: Vec<RuntimeType>,
+    /// Monitor held for the whole invocation of a synchronized method
+    pub method_monitor: Option<Arc<Monitor>>,
+    /// Monitors entered by monitorenter in this frame and not exited yet
+    pub monitors: Vec<Arc<Monitor>>,
+    /// Conversions of the returned value, in order, when the method implements lambdas
+    pub return_adaptations: Vec<Adaptation>,
+}
+
+/// The live threads other than the running one: the ones not started yet, and the ones waiting
+/// for a nested run (a class initializer, a method invoked on a thread of its own) to end
+#[derive(Debug, Default)]
+pub struct SuspendedThreads {
+    /// The heap references of the frames of each suspended thread, by suspension
+    references: Mutex<HashMap<usize, Vec<RuntimeType>>>,
+    suspension_count: AtomicUsize,
+}
+
+impl SuspendedThreads {
+    /// The heap references of the frames of every suspended thread
+    pub fn references(&self) -> Vec<RuntimeType> {
+        self.references.lock().values().flatten().cloned().collect()
+    }
+}
+
+/// A thread registered as suspended until this is dropped
+pub struct Suspension<'a> {
+    threads: &'a SuspendedThreads,
+    id: usize,
+}
+
+impl Drop for Suspension<'_> {
+    fn drop(&mut self) {
+        self.threads.references.lock().remove(&self.id);
+    }
+}
+
+impl StackFrame {
+    /// Releases every monitor held by the frame, returning false if the locking was not
+    /// structured: monitors entered by monitorenter not exited, or the monitor of the method
+    /// not held anymore (JVMS §2.11.10)
+    fn release_monitors(&mut self) -> bool {
+        let is_structured = self.monitors.is_empty();
+
+        for monitor in self.monitors.drain(..) {
+            monitor.exit();
+        }
+
+        match self.method_monitor.take() {
+            Some(monitor) => monitor.exit() && is_structured,
+            None => is_structured,
+        }
+    }
 }
 
 impl JvmThread {
@@ -29,18 +103,16 @@ impl JvmThread {
         let mut instance = Self {
             pc: 0,
             stack: vec![],
-            skip_static_init: false,
-            operand_stack: vec![],
+            result: None,
         };
 
-        instance.call_intro(
-            class,
-            method
-                .start_pc()
-                .expect("native or abstract methods cannot be the first to be called "),
-            method.local_count(),
+        assert!(
+            method.start_pc().is_some(),
+            "native or abstract methods cannot be the first to be called"
         );
 
+        instance.call_intro(class, method);
+
         instance
     }
 
@@ -57,17 +129,25 @@ impl JvmThread {
         Ok(local.clone())
     }
 
+    /// Stores a value in the local storage. When the value is a two-slot one (long or double),
+    /// the caller is expected to forbid the next local by itself.
     pub fn store_to_local(&mut self, index: usize, value: RuntimeType) -> anyhow::Result<()> {
         let frame = self.current_frame_mut()?;
-        let Some(local) = frame
-            .locals
-            .get_mut(index)
-            .ok_or_else(|| anyhow!("{index} out of bound for local storage"))?
-        else {
-            bail!("{index} is unusable");
-        };
 
-        *local = value;
+        if index >= frame.locals.len() {
+            bail!("{index} out of bound for local storage");
+        }
+
+        // Overwriting the second half of a long or a double makes the whole value unusable
+        if index > 0
+            && frame.locals[index - 1]
+                .as_ref()
+                .is_some_and(RuntimeType::is_two_slots)
+        {
+            frame.locals[index - 1] = None;
+        }
+
+        frame.locals[index] = Some(value);
 
         Ok(())
     }
@@ -99,11 +179,21 @@ impl JvmThread {
     }
 
     pub fn ret(&mut self) -> anyhow::Result<()> {
+        // The exception is thrown by the return instruction, the monitors being released
+        if let Some(frame) = self.stack.last_mut()
+            && !frame.release_monitors()
+        {
+            throw!(
+                "java/lang/IllegalMonitorStateException",
+                "unbalanced monitors on return from {}",
+                frame.method.name()
+            );
+        }
+
         let Some(previous_frame) = self.stack.pop() else {
             return Ok(());
         };
 
-        self.operand_stack.clear();
         self.jmp_to(previous_frame.return_pc);
 
         Ok(())
@@ -120,91 +210,548 @@ impl JvmThread {
     }
 
     pub fn pop_operand_stack(&mut self) -> anyhow::Result<RuntimeType> {
-        self.operand_stack
+        self.current_frame_mut()?
+            .operand_stack
             .pop()
             .ok_or_else(|| anyhow!("tried to pop an empty operand stack"))
     }
 
+    /// Reads the operand stack without popping it, depth 0 being its top
+    pub fn peek_operand_stack(&self, depth: usize) -> anyhow::Result<&RuntimeType> {
+        let operand_stack = &self.current_frame()?.operand_stack;
+
+        operand_stack
+            .len()
+            .checked_sub(depth + 1)
+            .and_then(|idx| operand_stack.get(idx))
+            .ok_or_else(|| {
+                anyhow!("tried to peek {depth} values deep in a too small operand stack")
+            })
+    }
+
     pub fn push_operand_stack(&mut self, value: RuntimeType) {
-        self.operand_stack.push(value);
+        self.stack
+            .last_mut()
+            .expect("operand stack pushed outside of any frame")
+            .operand_stack
+            .push(value);
     }
 
     pub fn run(&mut self, env: &JvmExecEnv) -> anyhow::Result<()> {
         info!("starting thread");
 
-        let jpu = JvmProcessUnit::jpu_new(env, self.skip_static_init);
+        let jpu = JvmProcessUnit::jpu_new(env);
+
+        self.execute(env, &jpu)
+    }
+
+    /// Registers this thread as suspended while others run, the references held by its frames
+    /// (at this point, so including the operands popped afterwards by the suspended instruction)
+    /// staying roots of the garbage collector until the suspension is dropped
+    pub fn suspend<'a>(&self, env: &'a JvmExecEnv) -> Suspension<'a> {
+        let threads = &env.suspended_threads;
+        let id = threads.suspension_count.fetch_add(1, Ordering::Relaxed);
+
+        threads.references.lock().insert(id, self.references());
+
+        Suspension { threads, id }
+    }
+
+    fn execute(&mut self, env: &JvmExecEnv, jpu: &JvmProcessUnit) -> anyhow::Result<()> {
+        self.enter_method_monitor()?;
 
         while !self.stack.is_empty() {
-            let op_code = self.pop_ubyte(env)?;
+            // The other threads are suspended, their frames being registered as roots
+            if env.heap.should_collect() {
+                env.collect_garbage(self.references());
+            }
 
-            trace!("current op-code: 0x{op_code:02x}");
+            let op_pc = self.pc;
 
-            match op_code {
-                v @ 0x02 | v @ 0x03 | v @ 0x04 | v @ 0x05 | v @ 0x06 | v @ 0x07 | v @ 0x08 => {
-                    jpu.bipush(self, (v as JvmInt) - 0x03)?
-                }
-                0x10 => {
-                    let sbyte = self.pop_sbyte(env)?;
-                    jpu.bipush(self, sbyte as JvmInt)?;
-                }
-                0xb2 => {
-                    let short = self.pop_ushort(env)?;
-                    jpu.getstatic(self, short)?
-                }
-                0xb8 => {
-                    let short = self.pop_ushort(env)?;
-                    jpu.invokestatic(self, short)?;
-                }
-                0x14 => {
-                    let short = self.pop_ushort(env)?;
-                    jpu.ld2c_w(self, short)?;
-                }
-                0x16 => {
-                    let local_index = self.pop_ubyte(env)?;
-                    jpu.lload(self, local_index)?;
-                }
-                v @ 0x1e | v @ 0x1f | v @ 0x20 | v @ 0x21 => jpu.lload(self, v - 0x1e)?,
-                0x37 => {
-                    let local_index = self.pop_ubyte(env)?;
-                    jpu.lstore(self, local_index)?;
-                }
-                v @ 0x3f | v @ 0x40 | v @ 0x41 | v @ 0x42 => {
-                    jpu.lstore(self, v - 0x3f)?;
-                }
-                0x39 => {
-                    let local_index = self.pop_ubyte(env)?;
-                    jpu.dstore(self, local_index)?;
-                }
-                0x15 => {
-                    let local_index = self.pop_ubyte(env)?;
+            if let Err(err) = self.step(env, jpu, op_pc) {
+                // Errors other than Java exceptions stop the thread
+                let exception = err.downcast::<JavaException>()?;
+
+                // Instantiating the exception may run the initializer of its class
+                let suspension = self.suspend(env);
+                let exception = exception.into_object(env)?;
+                drop(suspension);
+
+                self.throw(exception, op_pc)?;
+            }
+        }
+
+        Ok(())
+    }
 
-                    jpu.iload(self, local_index)?;
+    /// The heap values referenced by the locals and operand stacks of the frames, roots of the
+    /// garbage collector
+    fn references(&self) -> Vec<RuntimeType> {
+        self.stack
+            .iter()
+            .flat_map(|frame| frame.locals.iter().flatten().chain(&frame.operand_stack))
+            .filter(|v| v.is_heap_reference())
+            .cloned()
+            .collect()
+    }
+
+    /// Executes the instruction at the given pc
+    fn step(&mut self, env: &JvmExecEnv, jpu: &JvmProcessUnit, op_pc: usize) -> anyhow::Result<()> {
+        let op_code = self.pop_ubyte(env)?;
+
+        trace!("current op-code: 0x{op_code:02x}");
+
+        match op_code {
+            0x00 => jpu.nop()?,
+            0x01 => jpu.aconst_null(self)?,
+            v @ 0x02 | v @ 0x03 | v @ 0x04 | v @ 0x05 | v @ 0x06 | v @ 0x07 | v @ 0x08 => {
+                jpu.bipush(self, (v as JvmInt) - 0x03)?
+            }
+            v @ 0x09 | v @ 0x0a => jpu.lconst(self, (v as JvmLong) - 0x09)?,
+            v @ 0x0b | v @ 0x0c | v @ 0x0d => jpu.fconst(self, (v - 0x0b) as JvmFloat)?,
+            v @ 0x0e | v @ 0x0f => jpu.dconst(self, (v - 0x0e) as JvmDouble)?,
+            0x10 => {
+                let sbyte = self.pop_sbyte(env)?;
+                jpu.bipush(self, sbyte as JvmInt)?;
+            }
+            0x11 => {
+                let sshort = self.pop_sshort(env)?;
+                jpu.sipush(self, sshort as JvmInt)?;
+            }
+            0x12 => {
+                let byte = self.pop_ubyte(env)?;
+                jpu.ldc(self, byte.into())?;
+            }
+            0x13 => {
+                let short = self.pop_ushort(env)?;
+                jpu.ldc(self, short)?;
+            }
+            0xb2 => {
+                let short = self.pop_ushort(env)?;
+                jpu.getstatic(self, short)?
+            }
+            0xb3 => {
+                let short = self.pop_ushort(env)?;
+                jpu.putstatic(self, short)?;
+            }
+            0xb4 => {
+                let short = self.pop_ushort(env)?;
+                jpu.getfield(self, short)?;
+            }
+            0xb5 => {
+                let short = self.pop_ushort(env)?;
+                jpu.putfield(self, short)?;
+            }
+            0xb7 => {
+                let short = self.pop_ushort(env)?;
+                jpu.invokespecial(self, short)?;
+            }
+            0xb6 => {
+                let short = self.pop_ushort(env)?;
+                jpu.invokevirtual(self, short)?;
+            }
+            0xb9 => {
+                let short = self.pop_ushort(env)?;
+                let count = self.pop_ubyte(env)?;
+                self.pop_ubyte(env)?; // always 0
+                jpu.invokeinterface(self, short, count)?;
+            }
+            0xbc => {
+                let atype = self.pop_ubyte(env)?;
+                jpu.newarray(self, atype)?;
+            }
+            0xbd => {
+                let short = self.pop_ushort(env)?;
+                jpu.anewarray(self, short)?;
+            }
+            0xbe => jpu.arraylength(self)?,
+            0xc0 => {
+                let short = self.pop_ushort(env)?;
+                jpu.checkcast(self, short)?;
+            }
+            0xc1 => {
+                let short = self.pop_ushort(env)?;
+                jpu.instanceof(self, short)?;
+            }
+            0xc2 => jpu.monitorenter(self)?,
+            0xc3 => jpu.monitorexit(self)?,
+            0xc4 => {
+                let op_code = self.pop_ubyte(env)?;
+                let local_index = self.pop_ushort(env)?;
+
+                match op_code {
+                    0x15 => jpu.iload(self, local_index)?,
+                    0x16 => jpu.lload(self, local_index)?,
+                    0x17 => jpu.fload(self, local_index)?,
+                    0x18 => jpu.dload(self, local_index)?,
+                    0x19 => jpu.aload(self, local_index)?,
+                    0x36 => jpu.istore(self, local_index)?,
+                    0x37 => jpu.lstore(self, local_index)?,
+                    0x38 => jpu.fstore(self, local_index)?,
+                    0x39 => jpu.dstore(self, local_index)?,
+                    0x3a => jpu.astore(self, local_index)?,
+                    0xa9 => jpu.ret(self, local_index)?,
+                    0x84 => {
+                        let value = self.pop_sshort(env)?;
+                        jpu.iinc(self, local_index, value)?;
+                    }
+                    v => bail!("invalid op-code 0x{v:02x} modified by wide"),
                 }
-                v @ 0x1a | v @ 0x1b | v @ 0x1c | v @ 0x1d => jpu.iload(self, v - 0x1a)?,
-                0x36 => {
-                    let local_index = self.pop_ubyte(env)?;
+            }
+            0xc5 => {
+                let short = self.pop_ushort(env)?;
+                let dimensions = self.pop_ubyte(env)?;
+                jpu.multianewarray(self, short, dimensions)?;
+            }
+            0xbf => jpu.athrow(self)?,
+            0xba => {
+                let short = self.pop_ushort(env)?;
+                self.pop_ubyte(env)?; // always 0
+                self.pop_ubyte(env)?; // always 0
+                jpu.invokedynamic(self, op_pc, short)?;
+            }
+            0xbb => {
+                let short = self.pop_ushort(env)?;
+                jpu.new_object(self, short)?;
+            }
+            0xb8 => {
+                let short = self.pop_ushort(env)?;
+                jpu.invokestatic(self, short)?;
+            }
+            0x19 => {
+                let local_index = self.pop_ubyte(env)?;
+                jpu.aload(self, local_index.into())?;
+            }
+            v @ 0x2a | v @ 0x2b | v @ 0x2c | v @ 0x2d => jpu.aload(self, (v - 0x2a).into())?,
+            0x3a => {
+                let local_index = self.pop_ubyte(env)?;
+                jpu.astore(self, local_index.into())?;
+            }
+            v @ 0x4b | v @ 0x4c | v @ 0x4d | v @ 0x4e => jpu.astore(self, (v - 0x4b).into())?,
+            0x2e => jpu.iaload(self)?,
+            0x2f => jpu.laload(self)?,
+            0x30 => jpu.faload(self)?,
+            0x31 => jpu.daload(self)?,
+            0x32 => jpu.aaload(self)?,
+            0x33 => jpu.baload(self)?,
+            0x34 => jpu.caload(self)?,
+            0x35 => jpu.saload(self)?,
+            0x4f => jpu.iastore(self)?,
+            0x50 => jpu.lastore(self)?,
+            0x51 => jpu.fastore(self)?,
+            0x52 => jpu.dastore(self)?,
+            0x53 => jpu.aastore(self)?,
+            0x54 => jpu.bastore(self)?,
+            0x55 => jpu.castore(self)?,
+            0x56 => jpu.sastore(self)?,
+            0x57 => jpu.pop(self)?,
+            0x58 => jpu.pop2(self)?,
+            0x59 => jpu.dup(self)?,
+            0x5a => jpu.dup_x1(self)?,
+            0x5b => jpu.dup_x2(self)?,
+            0x5c => jpu.dup2(self)?,
+            0x5d => jpu.dup2_x1(self)?,
+            0x5e => jpu.dup2_x2(self)?,
+            0x5f => jpu.swap(self)?,
+            0x14 => {
+                let short = self.pop_ushort(env)?;
+                jpu.ld2c_w(self, short)?;
+            }
+            0x16 => {
+                let local_index = self.pop_ubyte(env)?;
+                jpu.lload(self, local_index.into())?;
+            }
+            v @ 0x1e | v @ 0x1f | v @ 0x20 | v @ 0x21 => jpu.lload(self, (v - 0x1e).into())?,
+            0x17 => {
+                let local_index = self.pop_ubyte(env)?;
+                jpu.fload(self, local_index.into())?;
+            }
+            v @ 0x22 | v @ 0x23 | v @ 0x24 | v @ 0x25 => jpu.fload(self, (v - 0x22).into())?,
+            0x18 => {
+                let local_index = self.pop_ubyte(env)?;
+                jpu.dload(self, local_index.into())?;
+            }
+            v @ 0x26 | v @ 0x27 | v @ 0x28 | v @ 0x29 => jpu.dload(self, (v - 0x26).into())?,
+            0x37 => {
+                let local_index = self.pop_ubyte(env)?;
+                jpu.lstore(self, local_index.into())?;
+            }
+            v @ 0x3f | v @ 0x40 | v @ 0x41 | v @ 0x42 => {
+                jpu.lstore(self, (v - 0x3f).into())?;
+            }
+            0x38 => {
+                let local_index = self.pop_ubyte(env)?;
+                jpu.fstore(self, local_index.into())?;
+            }
+            v @ 0x43 | v @ 0x44 | v @ 0x45 | v @ 0x46 => jpu.fstore(self, (v - 0x43).into())?,
+            0x39 => {
+                let local_index = self.pop_ubyte(env)?;
+                jpu.dstore(self, local_index.into())?;
+            }
+            0x15 => {
+                let local_index = self.pop_ubyte(env)?;
+
+                jpu.iload(self, local_index.into())?;
+            }
+            v @ 0x1a | v @ 0x1b | v @ 0x1c | v @ 0x1d => jpu.iload(self, (v - 0x1a).into())?,
+            0x36 => {
+                let local_index = self.pop_ubyte(env)?;
+
+                jpu.istore(self, local_index.into())?;
+            }
+            v @ 0x3b | v @ 0x3c | v @ 0x3d | v @ 0x3e => jpu.istore(self, (v - 0x3b).into())?,
+            v @ 0x47 | v @ 0x48 | v @ 0x49 | v @ 0x4a => jpu.dstore(self, (v - 0x47).into())?,
+            0x60 => jpu.iadd(self)?,
+            0x61 => jpu.ladd(self)?,
+            0x62 => jpu.fadd(self)?,
+            0x63 => jpu.dadd(self)?,
+            0x64 => jpu.isub(self)?,
+            0x65 => jpu.lsub(self)?,
+            0x66 => jpu.fsub(self)?,
+            0x67 => jpu.dsub(self)?,
+            0x68 => jpu.imul(self)?,
+            0x69 => jpu.lmul(self)?,
+            0x6a => jpu.fmul(self)?,
+            0x6b => jpu.dmul(self)?,
+            0x6c => jpu.idiv(self)?,
+            0x6d => jpu.ldiv(self)?,
+            0x6e => jpu.fdiv(self)?,
+            0x6f => jpu.ddiv(self)?,
+            0x70 => jpu.irem(self)?,
+            0x71 => jpu.lrem(self)?,
+            0x72 => jpu.frem(self)?,
+            0x73 => jpu.drem(self)?,
+            0x74 => jpu.ineg(self)?,
+            0x75 => jpu.lneg(self)?,
+            0x76 => jpu.fneg(self)?,
+            0x77 => jpu.dneg(self)?,
+            0x78 => jpu.ishl(self)?,
+            0x79 => jpu.lshl(self)?,
+            0x7a => jpu.ishr(self)?,
+            0x7b => jpu.lshr(self)?,
+            0x7c => jpu.iushr(self)?,
+            0x7d => jpu.lushr(self)?,
+            0x7e => jpu.iand(self)?,
+            0x7f => jpu.land(self)?,
+            0x80 => jpu.ior(self)?,
+            0x81 => jpu.lor(self)?,
+            0x82 => jpu.ixor(self)?,
+            0x83 => jpu.lxor(self)?,
+            0x84 => {
+                let local_index = self.pop_ubyte(env)?;
+                let value = self.pop_sbyte(env)?;
+
+                jpu.iinc(self, local_index.into(), value.into())?;
+            }
+            0x85 => jpu.i2l(self)?,
+            0x86 => jpu.i2f(self)?,
+            0x87 => jpu.i2d(self)?,
+            0x88 => jpu.l2i(self)?,
+            0x89 => jpu.l2f(self)?,
+            0x8a => jpu.l2d(self)?,
+            0x8b => jpu.f2i(self)?,
+            0x8c => jpu.f2l(self)?,
+            0x8d => jpu.f2d(self)?,
+            0x8e => jpu.d2i(self)?,
+            0x8f => jpu.d2l(self)?,
+            0x90 => jpu.d2f(self)?,
+            0x91 => jpu.i2b(self)?,
+            0x92 => jpu.i2c(self)?,
+            0x93 => jpu.i2s(self)?,
+            0x94 => jpu.lcmp(self)?,
+            0x95 => jpu.fcmp(self, -1)?,
+            0x96 => jpu.fcmp(self, 1)?,
+            0x97 => jpu.dcmp(self, -1)?,
+            0x98 => jpu.dcmp(self, 1)?,
+            v @ 0x99..=0x9e => {
+                let offset = self.pop_sshort(env)?;
+                jpu.if_cond(self, op_pc, offset, Condition::from_opcode_offset(v - 0x99))?;
+            }
+            v @ 0x9f..=0xa4 => {
+                let offset = self.pop_sshort(env)?;
+                jpu.if_icmp(self, op_pc, offset, Condition::from_opcode_offset(v - 0x9f))?;
+            }
+            v @ 0xa5 | v @ 0xa6 => {
+                let offset = self.pop_sshort(env)?;
+                jpu.if_acmp(self, op_pc, offset, Condition::from_opcode_offset(v - 0xa5))?;
+            }
+            0xa7 => {
+                let offset = self.pop_sshort(env)?;
+                jpu.goto(self, op_pc, offset as JvmInt)?;
+            }
+            0xa8 => {
+                let offset = self.pop_sshort(env)?;
+                jpu.jsr(self, op_pc, offset as JvmInt)?;
+            }
+            0xa9 => {
+                let byte = self.pop_ubyte(env)?;
+                jpu.ret(self, byte.into())?;
+            }
+            0xaa => {
+                self.skip_switch_padding()?;
 
-                    jpu.istore(self, local_index)?;
+                let default = self.pop_sint(env)?;
+                let low = self.pop_sint(env)?;
+                let high = self.pop_sint(env)?;
+
+                if low > high {
+                    bail!("invalid tableswitch bounds (low = {low}, high = {high})");
                 }
-                v @ 0x3b | v @ 0x3c | v @ 0x3d | v @ 0x3e => jpu.istore(self, v - 0x3b)?,
-                v @ 0x47 | v @ 0x48 | v @ 0x49 | v @ 0x4a => jpu.dstore(self, v - 0x47)?,
-                0x60 => jpu.iadd(self)?,
-                0x6c => jpu.idiv(self)?,
-                0xa9 => {
-                    let byte = self.pop_ubyte(env)?;
-                    jpu.ret(self, byte)?;
+
+                let offsets = (low..=high)
+                    .map(|_| self.pop_sint(env))
+                    .collect::<anyhow::Result<Vec<_>>>()?;
+
+                jpu.tableswitch(self, op_pc, default, low, &offsets)?;
+            }
+            0xab => {
+                self.skip_switch_padding()?;
+
+                let default = self.pop_sint(env)?;
+                let npairs = self.pop_sint(env)?;
+
+                if npairs < 0 {
+                    bail!("negative lookupswitch pair count ({npairs})");
                 }
-                0xb1 => jpu.vreturn(self)?,
-                v => bail!("unknown opcode at 0x{:08X}: 0x{v:02X}", (self.pc - 1)),
+
+                let pairs = (0..npairs)
+                    .map(|_| Ok((self.pop_sint(env)?, self.pop_sint(env)?)))
+                    .collect::<anyhow::Result<Vec<_>>>()?;
+
+                jpu.lookupswitch(self, op_pc, default, &pairs)?;
             }
+            0xac => jpu.ireturn(self)?,
+            0xad => jpu.lreturn(self)?,
+            0xae => jpu.freturn(self)?,
+            0xaf => jpu.dreturn(self)?,
+            0xb0 => jpu.areturn(self)?,
+            0xb1 => jpu.vreturn(self)?,
+            0xc6 => {
+                let offset = self.pop_sshort(env)?;
+                jpu.ifnull(self, op_pc, offset, true)?;
+            }
+            0xc7 => {
+                let offset = self.pop_sshort(env)?;
+                jpu.ifnull(self, op_pc, offset, false)?;
+            }
+            0xc8 => {
+                let offset = self.pop_sint(env)?;
+                jpu.goto(self, op_pc, offset)?;
+            }
+            0xc9 => {
+                let offset = self.pop_sint(env)?;
+                jpu.jsr(self, op_pc, offset)?;
+            }
+            v => bail!("unknown opcode at 0x{:08X}: 0x{v:02X}", (self.pc - 1)),
         }
 
         Ok(())
     }
 
-    pub fn run_clinit_thread(env: &JvmExecEnv, class: Class) -> anyhow::Result<()> {
+    /// Unwinds the stack up to the handler of an exception thrown at the given pc, the exception
+    /// being returned as an error if no handler catches it.
+    fn throw(&mut self, exception: ObjectRef, mut pc: usize) -> anyhow::Result<()> {
+        let exception_class = exception
+            .get()
+            .map(|o| o.class_type.clone())
+            .ok_or_else(|| anyhow!("thrown exception is null or collected"))?;
+
+        loop {
+            let frame = self.current_frame_mut()?;
+            let relative_pc = pc - frame.code_start;
+
+            let handler_pc = frame
+                .method
+                .exception_table()
+                .iter()
+                .find(|entry| {
+                    (entry.start_pc as usize..entry.end_pc as usize).contains(&relative_pc)
+                        && entry
+                            .catch_type
+                            .as_ref()
+                            .is_none_or(|c| exception_class.is_assignable_to(&c.name))
+                })
+                .map(|entry| frame.code_start + entry.handler_pc as usize);
+
+            if let Some(handler_pc) = handler_pc {
+                frame.operand_stack.clear();
+                frame.operand_stack.push(RuntimeType::Class(exception));
+
+                self.jmp_to(handler_pc);
+
+                return Ok(());
+            }
+
+            let Some(mut frame) = self.stack.pop() else {
+                bail!("exception thrown outside of any frame");
+            };
+
+            frame.release_monitors();
+
+            if self.stack.is_empty() {
+                return Err(JavaException::Thrown(exception).into());
+            }
+
+            // The exception is now thrown by the invoke instruction of the caller
+            pc = frame.return_pc - 1;
+        }
+    }
+
+    /// Initializes a class or interface unless already done, as described by the JVMS (§5.5).
+    /// The class is linked (so verified) beforehand. The superclass of a class and its
+    /// superinterfaces declaring default methods are initialized first, then its <clinit> is run
+    /// on a thread of its own, the calling thread having to be suspended meanwhile. Exceptions of
+    /// <clinit> other than errors are wrapped in an ExceptionInInitializerError.
+    pub fn initialize_class(env: &JvmExecEnv, class: &Class) -> anyhow::Result<()> {
+        class.link(env)?;
+
+        if !class.begin_initialization()? {
+            return Ok(());
+        }
+
+        debug!("initializing class {}", class.name);
+
+        let result = Self::initialize_supertypes(env, class)
+            .and_then(|_| Self::run_class_initializer(env, class));
+
+        class.end_initialization(result.is_ok());
+
+        result
+    }
+
+    fn initialize_supertypes(env: &JvmExecEnv, class: &Class) -> anyhow::Result<()> {
+        // The superinterfaces of an interface are not initialized with it
+        if env.get_interface(&class.name).is_some() {
+            return Ok(());
+        }
+
+        if let Some(super_class) = &class.super_class {
+            Self::initialize_class(env, super_class)?;
+        }
+
+        Self::initialize_superinterfaces(env, &class.interfaces)
+    }
+
+    /// Initializes the superinterfaces declaring default methods, each one after its own
+    /// superinterfaces
+    fn initialize_superinterfaces(
+        env: &JvmExecEnv,
+        interfaces: &[Interface],
+    ) -> anyhow::Result<()> {
+        for interface in interfaces {
+            Self::initialize_superinterfaces(env, &interface.interfaces)?;
+
+            if interface.declares_concrete_instance_methods() {
+                Self::initialize_class(env, interface.as_class())?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn run_class_initializer(env: &JvmExecEnv, class: &Class) -> anyhow::Result<()> {
         let Some(method) = class.get_static_method(
-            &String::from("<clinit>"),
+            "<clinit>",
             JvmMethodDescriptor {
                 return_type: None,
                 parameter_types: vec![],
@@ -213,31 +760,73 @@ impl JvmThread {
             return Ok(());
         };
 
-        let _lock = class.lock_statics();
-        if !class.set_initialized_if_needed() {
+        let Err(err) = Self::new(class.clone(), &method).run(env) else {
             return Ok(());
+        };
+
+        let exception = err.downcast::<JavaException>()?.into_object(env)?;
+
+        let is_error = exception
+            .get()
+            .is_some_and(|e| e.class_type.is_assignable_to("java/lang/Error"));
+
+        if is_error {
+            return Err(JavaException::Thrown(exception).into());
+        }
+
+        Err(JavaException::caused_by("java/lang/ExceptionInInitializerError", exception).into())
+    }
+
+    /// Runs a method on its own thread with the given arguments (the receiver first for
+    /// instance methods), returning its result. The calling thread has to be suspended meanwhile.
+    pub fn invoke(
+        env: &JvmExecEnv,
+        class: Class,
+        method: &Method,
+        args: Vec<RuntimeType>,
+    ) -> anyhow::Result<Option<RuntimeType>> {
+        if method.start_pc().is_none() {
+            bail!("cannot invoke native or abstract method {}", method.name());
         }
 
-        let mut instance = Self::new(class.clone(), &method);
+        let mut instance = Self::new(class, method);
+        let mut local_index = 0;
 
-        instance.skip_static_init = true;
-        instance.run(env)
+        for arg in args {
+            let is_two_slots = arg.is_two_slots();
+
+            instance.store_to_local(local_index, arg)?;
+
+            local_index += if is_two_slots { 2 } else { 1 };
+        }
+
+        instance.run(env)?;
+
+        Ok(instance.result)
     }
 
     pub fn jmp_jvm_method(&mut self, class: Class, method: &Method) -> anyhow::Result<()> {
-        if self.operand_stack.len() != method.parameters().len() {
+        if method.start_pc().is_none() {
+            bail!("cannot jump to native or abstract method {}", method.name());
+        }
+
+        // The receiver of instance methods is passed as an implicit first parameter
+        let param_count = method.parameters().len() + usize::from(!method.is_static());
+
+        let caller_stack = &mut self.current_frame_mut()?.operand_stack;
+
+        if caller_stack.len() < param_count {
             bail!(
-                "expected {} parameters in operand stack, but got {}",
-                method.parameters().len(),
-                self.operand_stack.len()
+                "expected {param_count} parameters in operand stack, but got {}",
+                caller_stack.len()
             );
         }
 
-        self.call_intro(class, method.start_pc().unwrap(), method.local_count());
+        let params = caller_stack.split_off(caller_stack.len() - param_count);
 
-        let mut offset = 0;
+        self.call_intro(class, method);
 
-        let params = self.operand_stack.drain(..).collect::<Vec<RuntimeType>>();
+        let mut offset = 0;
 
         for (idx, param) in params.into_iter().enumerate() {
             let doubled_size = param.is_two_slots();
@@ -250,28 +839,65 @@ impl JvmThread {
             }
         }
 
+        self.enter_method_monitor()
+    }
+
+    /// Enters the monitor of the method of the current frame if it is synchronized: the one of
+    /// its class for static methods, of its receiver otherwise
+    fn enter_method_monitor(&mut self) -> anyhow::Result<()> {
+        let frame = self.current_frame()?;
+
+        if !frame.method.is_synchronized() {
+            return Ok(());
+        }
+
+        let monitor = if frame.method.is_static() {
+            frame.current_class.monitor.get()
+        } else {
+            match self.read_local(0)? {
+                RuntimeType::Class(object) => object
+                    .get()
+                    .ok_or_else(|| anyhow!("synchronized method called on null"))?
+                    .monitor
+                    .get(),
+                v => bail!("unsupported receiver of a synchronized method: {v:?}"),
+            }
+        };
+
+        monitor.enter();
+        self.current_frame_mut()?.method_monitor = Some(monitor);
+
         Ok(())
     }
 
-    fn call_intro(&mut self, class: Class, pc: usize, local_count: usize) {
+    fn call_intro(&mut self, class: Class, method: &Method) {
+        let pc = method.start_pc().unwrap();
+
         self.stack.push(StackFrame {
             return_pc: self.pc,
+            code_start: pc,
             current_class: class,
-            locals: vec![Some(RuntimeType::Int(0)); local_count].into_boxed_slice(),
+            method: method.clone(),
+            locals: vec![Some(RuntimeType::Int(0)); method.local_count()].into_boxed_slice(),
+            operand_stack: vec![],
+            method_monitor: None,
+            monitors: vec![],
+            return_adaptations: vec![],
         });
 
         self.pc = pc;
     }
 
     fn pop_ubyte(&mut self, env: &JvmExecEnv) -> anyhow::Result<u8> {
-        let byte = env
+        let byte = *env
             .code
+            .read()
             .get(self.pc)
             .ok_or(anyhow!("pc went out of code memory (pc = {})", self.pc))?;
 
         self.pc += 1;
 
-        Ok(*byte)
+        Ok(byte)
     }
 
     fn pop_sbyte(&mut self, env: &JvmExecEnv) -> anyhow::Result<i8> {
@@ -285,34 +911,129 @@ impl JvmThread {
         ]))
     }
 
-    // fn pop_sint(&mut self, env: &JvmExecEnv) -> anyhow::Result<JvmInt> {
-    //     Ok(i32::from_be_bytes([
-    //         self.pop_ubyte(env)?,
-    //         self.pop_ubyte(env)?,
-    //         self.pop_ubyte(env)?,
-    //         self.pop_ubyte(env)?,
-    //     ]))
-    // }
+    fn pop_sshort(&mut self, env: &JvmExecEnv) -> anyhow::Result<i16> {
+        self.pop_ushort(env).map(|v| v as i16)
+    }
+
+    fn pop_sint(&mut self, env: &JvmExecEnv) -> anyhow::Result<JvmInt> {
+        Ok(i32::from_be_bytes([
+            self.pop_ubyte(env)?,
+            self.pop_ubyte(env)?,
+            self.pop_ubyte(env)?,
+            self.pop_ubyte(env)?,
+        ]))
+    }
+
+    /// Skips the 0-3 padding bytes following tableswitch and lookupswitch, so that the operands
+    /// start at an address that is a multiple of 4 from the start of the method.
+    fn skip_switch_padding(&mut self) -> anyhow::Result<()> {
+        let code_start = self.current_frame()?.code_start;
+
+        while !(self.pc - code_start).is_multiple_of(4) {
+            self.pc += 1;
+        }
+
+        Ok(())
+    }
 
     pub fn dump_to<W: Write>(&self, mut writer: W) -> anyhow::Result<()> {
         writeln!(writer, "========= THREAD DUMP =========")?;
         writeln!(writer, "PC = {}", self.pc)?;
-        writeln!(writer, "SSI? {}", self.skip_static_init)?;
         writeln!(writer, "STACK:")?;
         for (idx, frame) in self.stack.iter().enumerate().rev() {
             writeln!(writer, "- frame {idx}")?;
             writeln!(writer, "  class:     {}", frame.current_class.name)?;
+            writeln!(writer, "  method:    {}", frame.method.name())?;
             writeln!(writer, "  return PC: {}", frame.return_pc)?;
             writeln!(writer, "  locals:")?;
             for (idx, elem) in frame.locals.iter().enumerate() {
                 writeln!(writer, "  - [{idx}]      {elem:?}")?;
             }
-        }
-        writeln!(writer, "OS:")?;
-        for (idx, elem) in self.operand_stack.iter().enumerate().rev() {
-            writeln!(writer, "- [{idx}]      {elem:?}")?;
+            writeln!(writer, "  OS:")?;
+            for (idx, elem) in frame.operand_stack.iter().enumerate().rev() {
+                writeln!(writer, "  - [{idx}]      {elem:?}")?;
+            }
         }
 
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        class::{
+            JvmUnit, parse_class_file,
+            tests::{ClassBytes, CodeBytes},
+        },
+        types::JvmTypeDescriptor,
+    };
+
+    /// A thread about to run the static method run([I)V of a class Test
+    fn new_thread(env: &JvmExecEnv) -> JvmThread {
+        let mut class = ClassBytes::new("Test", 61);
+        class.method(
+            0x0009,
+            "run",
+            "([I)V",
+            Some(CodeBytes {
+                max_stack: 0,
+                max_locals: 1,
+                // return
+                code: &[0xb1],
+                exception_table: &[],
+                stack_map: &[],
+            }),
+        );
+
+        for bytes in [
+            ClassBytes::new("java/lang/Object", 61).bytes(),
+            class.bytes(),
+        ] {
+            let class_file = parse_class_file("", &bytes).unwrap();
+            env.add_unit(JvmUnit::from_class_file(class_file).unwrap())
+                .unwrap();
+        }
+
+        let class = env.get_class("Test").unwrap();
+        let method = class
+            .get_static_method(
+                "run",
+                JvmMethodDescriptor {
+                    parameter_types: vec![JvmTypeDescriptor::Array(Box::new(
+                        JvmTypeDescriptor::Int,
+                    ))],
+                    return_type: None,
+                },
+            )
+            .unwrap();
+
+        JvmThread::new(class, &method)
+    }
+
+    #[test]
+    fn frames_of_suspended_threads_are_roots() {
+        let env = JvmExecEnv::new();
+        let mut thread = new_thread(&env);
+        let array = env.heap.new_array(JvmTypeDescriptor::Int, 1);
+
+        thread
+            .store_to_local(0, RuntimeType::Array(array.clone()))
+            .unwrap();
+
+        let suspension = thread.suspend(&env);
+        let nested = thread.suspend(&env);
+
+        env.collect_garbage([]);
+        assert!(array.get().is_some());
+
+        drop(nested);
+        env.collect_garbage([]);
+        assert!(array.get().is_some());
+
+        drop(suspension);
+        env.collect_garbage([]);
+        assert!(array.get().is_none());
+    }
+}
@@ -0,0 +1,1756 @@
+use std::{collections::BTreeMap, fmt::Display};
+
+use anyhow::anyhow;
+use log::{debug, warn};
+
+use crate::{
+    class::{
+        attributes::{StackMapFrame, VerificationTypeInfo},
+        constant_pool::LoadableJvmConstant,
+    },
+    types::JvmTypeDescriptor,
+};
+
+use super::{JvmExecEnv, class::Class, exception::throw, jpu::type_of_class_name, method::Method};
+
+/// Verifies the methods of a class or interface by type checking (JVMS §4.10.1), throwing a
+/// VerifyError naming the method, the offset and the frames involved for the first rejected one.
+///
+/// Class files older than version 50 have no StackMapTable and need verification by type
+/// inference, which is not implemented, so they are not verified. The JVMS lets version 50 class
+/// files failing type checking fall back to type inference: they are accepted with a warning.
+/// The checks of protected member accesses (JVMS §4.10.1.8) are not done.
+pub fn verify_class(env: &JvmExecEnv, class: &Class) -> anyhow::Result<()> {
+    if class.major_version < 50 {
+        debug!(
+            "{} not verified: class file version {} requires type inference",
+            class.name, class.major_version
+        );
+        return Ok(());
+    }
+
+    debug!("verifying {}", class.name);
+
+    for method in class.declared_methods() {
+        let (Some(start), Some(end)) = (method.start_pc(), method.end_pc()) else {
+            continue;
+        };
+
+        // The code is copied, as classes may be loaded (adding their code) while verifying
+        let code = env.code.read()[start..end].to_vec();
+
+        let Err(err) = MethodVerifier::new(env, class, method, &code).and_then(|v| v.verify())
+        else {
+            continue;
+        };
+
+        let rejection = err.downcast::<Rejection>()?;
+
+        let location = format!(
+            "{}.{}({}){}",
+            class.name.replace('/', "."),
+            method.name(),
+            method
+                .parameters()
+                .iter()
+                .map(ToString::to_string)
+                .collect::<String>(),
+            method
+                .ret_type()
+                .as_ref()
+                .map_or("V".to_string(), ToString::to_string)
+        );
+
+        if class.major_version == 50 {
+            warn!("{location}{rejection} (accepted as a version 50 class file)");
+            continue;
+        }
+
+        throw!("java/lang/VerifyError", "{location}{rejection}");
+    }
+
+    Ok(())
+}
+
+/// A verification type (JVMS §4.10.1.2). Long and double values take two slots, in the local
+/// variables as on the operand stack, the second one being Top.
+#[derive(Debug, Clone, PartialEq)]
+enum VerificationType {
+    Top,
+    Int,
+    Float,
+    Long,
+    Double,
+    Null,
+    /// The receiver of a constructor, before the invocation of another constructor on it
+    UninitializedThis,
+    /// An object created by the new instruction at the given offset and not initialized yet
+    Uninitialized(usize),
+    /// A class, interface or array type
+    Reference(JvmTypeDescriptor),
+}
+
+impl VerificationType {
+    /// The type of the values of a field, parameter or return type: the types smaller than int
+    /// are ints for the verifier
+    fn from_descriptor(ty: &JvmTypeDescriptor) -> Self {
+        match ty {
+            JvmTypeDescriptor::Boolean
+            | JvmTypeDescriptor::Byte
+            | JvmTypeDescriptor::Char
+            | JvmTypeDescriptor::Short
+            | JvmTypeDescriptor::Int => Self::Int,
+            JvmTypeDescriptor::Float => Self::Float,
+            JvmTypeDescriptor::Long => Self::Long,
+            JvmTypeDescriptor::Double => Self::Double,
+            JvmTypeDescriptor::Class(_) | JvmTypeDescriptor::Array(_) => {
+                Self::Reference(ty.clone())
+            }
+        }
+    }
+
+    fn class(name: &str) -> Self {
+        Self::Reference(JvmTypeDescriptor::Class(name.to_string()))
+    }
+
+    fn is_two_slots(&self) -> bool {
+        matches!(self, Self::Long | Self::Double)
+    }
+
+    fn is_reference(&self) -> bool {
+        matches!(
+            self,
+            Self::Null | Self::UninitializedThis | Self::Uninitialized(_) | Self::Reference(_)
+        )
+    }
+
+    /// Appends the slots taken by a value of this type
+    fn push_slots(self, slots: &mut Vec<Self>) {
+        let is_two_slots = self.is_two_slots();
+
+        slots.push(self);
+
+        if is_two_slots {
+            slots.push(Self::Top);
+        }
+    }
+}
+
+impl Display for VerificationType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Top => write!(f, "top"),
+            Self::Int => write!(f, "int"),
+            Self::Float => write!(f, "float"),
+            Self::Long => write!(f, "long"),
+            Self::Double => write!(f, "double"),
+            Self::Null => write!(f, "null"),
+            Self::UninitializedThis => write!(f, "uninitializedThis"),
+            Self::Uninitialized(offset) => write!(f, "uninitialized({offset})"),
+            Self::Reference(JvmTypeDescriptor::Class(name)) => write!(f, "'{name}'"),
+            Self::Reference(ty) => write!(f, "'{ty}'"),
+        }
+    }
+}
+
+/// The types of the local variables and of the operand stack before an instruction
+#[derive(Debug, Clone, PartialEq)]
+struct Frame {
+    /// As many types as max_locals
+    locals: Vec<VerificationType>,
+    /// The bottom of the stack first
+    stack: Vec<VerificationType>,
+    /// Whether the receiver of the constructor is not initialized yet (flagThisUninit)
+    this_uninit: bool,
+}
+
+impl Display for Frame {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let join = |types: &[VerificationType]| {
+            types
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+
+        // The unused locals at the end are left out
+        let used_locals = self
+            .locals
+            .iter()
+            .rposition(|t| *t != VerificationType::Top)
+            .map_or(0, |i| i + 1);
+
+        write!(
+            f,
+            "locals: [{}], stack: [{}]",
+            join(&self.locals[..used_locals]),
+            join(&self.stack)
+        )?;
+
+        if self.this_uninit {
+            write!(f, ", flags: [flagThisUninit]")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Why a method was rejected, with the offset of the instruction and the frames involved when
+/// known
+#[derive(Debug)]
+struct Rejection {
+    reason: String,
+    offset: Option<usize>,
+    current: Option<Frame>,
+    expected: Option<Frame>,
+}
+
+impl Display for Rejection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(offset) = self.offset {
+            write!(f, " @{offset}")?;
+        }
+
+        write!(f, ": {}", self.reason)?;
+
+        if let Some(current) = &self.current {
+            write!(f, "\n  current frame: {current}")?;
+        }
+
+        if let Some(expected) = &self.expected {
+            write!(f, "\n  expected frame: {expected}")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl std::error::Error for Rejection {}
+
+fn reject(reason: impl Into<String>) -> anyhow::Error {
+    Rejection {
+        reason: reason.into(),
+        offset: None,
+        current: None,
+        expected: None,
+    }
+    .into()
+}
+
+/// Rejects a frame which does not match the one recorded in the StackMapTable
+fn reject_frame(reason: impl Into<String>, expected: &Frame) -> anyhow::Error {
+    Rejection {
+        reason: reason.into(),
+        offset: None,
+        current: None,
+        expected: Some(expected.clone()),
+    }
+    .into()
+}
+
+/// Sets the location of a rejection, other errors being left as is
+fn locate(err: anyhow::Error, offset: usize, current: Option<&Frame>) -> anyhow::Error {
+    match err.downcast::<Rejection>() {
+        Ok(mut rejection) => {
+            rejection.offset.get_or_insert(offset);
+
+            if rejection.current.is_none() {
+                rejection.current = current.cloned();
+            }
+
+            rejection.into()
+        }
+        Err(err) => err,
+    }
+}
+
+struct MethodVerifier<'a> {
+    env: &'a JvmExecEnv,
+    class: &'a Class,
+    method: &'a Method,
+    code: &'a [u8],
+    max_locals: usize,
+    max_stack: usize,
+    /// Whether an instruction starts at each offset of the code
+    instruction_starts: Vec<bool>,
+    /// The frames recorded in the StackMapTable, by offset
+    stack_map: BTreeMap<usize, Frame>,
+}
+
+impl<'a> MethodVerifier<'a> {
+    fn new(
+        env: &'a JvmExecEnv,
+        class: &'a Class,
+        method: &'a Method,
+        code: &'a [u8],
+    ) -> anyhow::Result<Self> {
+        let mut verifier = Self {
+            env,
+            class,
+            method,
+            code,
+            max_locals: method.local_count(),
+            max_stack: method.max_stack(),
+            instruction_starts: vec![false; code.len()],
+            stack_map: BTreeMap::new(),
+        };
+
+        if code.is_empty() {
+            return Err(reject("empty code"));
+        }
+
+        let mut offset = 0;
+
+        while offset < code.len() {
+            verifier.instruction_starts[offset] = true;
+            offset += verifier
+                .instruction_length(offset)
+                .map_err(|err| locate(err, offset, None))?;
+        }
+
+        verifier.stack_map = verifier.decode_stack_map()?;
+
+        Ok(verifier)
+    }
+
+    fn verify(&self) -> anyhow::Result<()> {
+        self.check_exception_table()?;
+
+        let mut incoming = Some(self.new_frame(self.initial_locals()?, vec![])?);
+        let mut offset = 0;
+
+        while offset < self.code.len() {
+            if let Some(recorded) = self.stack_map.get(&offset) {
+                if let Some(frame) = &incoming {
+                    self.check_frame(frame, offset)
+                        .map_err(|err| locate(err, offset, Some(frame)))?;
+                }
+
+                incoming = Some(recorded.clone());
+            }
+
+            let Some(frame) = incoming.take() else {
+                return Err(locate(
+                    reject("no stack map frame after an unconditional branch"),
+                    offset,
+                    None,
+                ));
+            };
+
+            incoming = self
+                .check_handlers(offset, &frame)
+                .and_then(|_| self.check_instruction(offset, &frame))
+                .map_err(|err| locate(err, offset, Some(&frame)))?;
+
+            offset += self.instruction_length(offset)?;
+        }
+
+        match incoming {
+            Some(frame) => Err(locate(
+                reject("falling off the end of the code"),
+                offset,
+                Some(&frame),
+            )),
+            None => Ok(()),
+        }
+    }
+
+    fn u8_at(&self, offset: usize) -> anyhow::Result<u8> {
+        self.code
+            .get(offset)
+            .copied()
+            .ok_or_else(|| reject("truncated instruction"))
+    }
+
+    fn u16_at(&self, offset: usize) -> anyhow::Result<u16> {
+        Ok(u16::from_be_bytes([
+            self.u8_at(offset)?,
+            self.u8_at(offset + 1)?,
+        ]))
+    }
+
+    fn i32_at(&self, offset: usize) -> anyhow::Result<i32> {
+        Ok(i32::from_be_bytes([
+            self.u8_at(offset)?,
+            self.u8_at(offset + 1)?,
+            self.u8_at(offset + 2)?,
+            self.u8_at(offset + 3)?,
+        ]))
+    }
+
+    fn is_instruction_start(&self, offset: usize) -> bool {
+        self.instruction_starts.get(offset) == Some(&true)
+    }
+
+    /// The offset of the operands of tableswitch and lookupswitch, after the 0-3 padding bytes
+    fn switch_operands(offset: usize) -> usize {
+        (offset + 4) & !3
+    }
+
+    fn instruction_length(&self, offset: usize) -> anyhow::Result<usize> {
+        let length = match self.u8_at(offset)? {
+            0x10 | 0x12 | 0x15..=0x19 | 0x36..=0x3a | 0xa9 | 0xbc => 2,
+            0x11
+            | 0x13
+            | 0x14
+            | 0x84
+            | 0x99..=0xa8
+            | 0xb2..=0xb8
+            | 0xbb
+            | 0xbd
+            | 0xc0
+            | 0xc1
+            | 0xc6
+            | 0xc7 => 3,
+            0xc5 => 4,
+            0xb9 | 0xba | 0xc8 | 0xc9 => 5,
+            0xc4 => match self.u8_at(offset + 1)? {
+                0x84 => 6,
+                0x15..=0x19 | 0x36..=0x3a | 0xa9 => 4,
+                v => return Err(reject(format!("invalid opcode 0x{v:02x} modified by wide"))),
+            },
+            0xaa => {
+                let operands = Self::switch_operands(offset);
+                let low = self.i32_at(operands + 4)? as i64;
+                let high = self.i32_at(operands + 8)? as i64;
+
+                if low > high {
+                    return Err(reject(format!(
+                        "invalid tableswitch bounds (low = {low}, high = {high})"
+                    )));
+                }
+
+                operands - offset + 12 + 4 * (high - low + 1) as usize
+            }
+            0xab => {
+                let operands = Self::switch_operands(offset);
+                let npairs = self.i32_at(operands + 4)?;
+
+                if npairs < 0 {
+                    return Err(reject(format!(
+                        "negative lookupswitch pair count ({npairs})"
+                    )));
+                }
+
+                operands - offset + 8 + 8 * npairs as usize
+            }
+            0x00..=0xc9 => 1,
+            v => return Err(reject(format!("unknown opcode 0x{v:02x}"))),
+        };
+
+        if offset + length > self.code.len() {
+            return Err(reject("truncated instruction"));
+        }
+
+        Ok(length)
+    }
+
+    /// The types of the receiver and of the parameters, as given by the descriptor of the method
+    fn initial_locals(&self) -> anyhow::Result<Vec<VerificationType>> {
+        let mut locals = vec![];
+
+        if !self.method.is_static() {
+            // The receiver of a constructor is initialized by the one of its superclass, except
+            // for java/lang/Object which has none
+            if self.method.name().as_str() == "<init>"
+                && self.class.name.as_str() != "java/lang/Object"
+            {
+                locals.push(VerificationType::UninitializedThis);
+            } else {
+                locals.push(VerificationType::class(&self.class.name));
+            }
+        }
+
+        for parameter in self.method.parameters() {
+            VerificationType::from_descriptor(parameter).push_slots(&mut locals);
+        }
+
+        Ok(locals)
+    }
+
+    /// Makes a frame out of the types of the locals (without the unused ones at the end) and of
+    /// the operand stack
+    fn new_frame(
+        &self,
+        mut locals: Vec<VerificationType>,
+        stack: Vec<VerificationType>,
+    ) -> anyhow::Result<Frame> {
+        if locals.len() > self.max_locals {
+            return Err(reject(format!(
+                "{} locals used, while max_locals is {}",
+                locals.len(),
+                self.max_locals
+            )));
+        }
+
+        if stack.len() > self.max_stack {
+            return Err(reject(format!(
+                "{} operand stack slots used, while max_stack is {}",
+                stack.len(),
+                self.max_stack
+            )));
+        }
+
+        let this_uninit = locals.contains(&VerificationType::UninitializedThis);
+
+        locals.resize(self.max_locals, VerificationType::Top);
+
+        Ok(Frame {
+            locals,
+            stack,
+            this_uninit,
+        })
+    }
+
+    /// Computes the frames of the StackMapTable, each entry being relative to the previous one
+    /// (the first one to the frame given by the descriptor of the method)
+    fn decode_stack_map(&self) -> anyhow::Result<BTreeMap<usize, Frame>> {
+        let mut frames = BTreeMap::new();
+        let mut locals = self.initial_locals()?;
+        let mut previous_offset = None;
+
+        for entry in self.method.stack_map() {
+            let (offset_delta, stack) = match entry {
+                StackMapFrame::Same { id } => (*id as usize, vec![]),
+                StackMapFrame::SameLocals1StackItemFrame { id, stack } => {
+                    (*id as usize - 64, self.expand(stack)?)
+                }
+                StackMapFrame::SameLocals1StackItemFrameExtended {
+                    offset_delta,
+                    stack,
+                } => (*offset_delta as usize, self.expand(stack)?),
+                StackMapFrame::ChopFrame { id, offset_delta } => {
+                    for _ in 0..(251 - *id) {
+                        let Some(chopped) = locals.pop() else {
+                            return Err(reject("stack map frame chopping too many locals"));
+                        };
+
+                        if chopped == VerificationType::Top
+                            && locals.last().is_some_and(VerificationType::is_two_slots)
+                        {
+                            locals.pop();
+                        }
+                    }
+
+                    (*offset_delta as usize, vec![])
+                }
+                StackMapFrame::SameFrameExtended { offset_delta } => {
+                    (*offset_delta as usize, vec![])
+                }
+                StackMapFrame::AppendFrame {
+                    offset_delta,
+                    locals: appended,
+                    ..
+                } => {
+                    locals.extend(self.expand(appended)?);
+
+                    (*offset_delta as usize, vec![])
+                }
+                StackMapFrame::FullFrame {
+                    offset_delta,
+                    locals: full_locals,
+                    stack,
+                    ..
+                } => {
+                    locals = self.expand(full_locals)?;
+
+                    (*offset_delta as usize, self.expand(stack)?)
+                }
+            };
+
+            let offset = match previous_offset {
+                Some(previous) => previous + offset_delta + 1,
+                None => offset_delta,
+            };
+
+            if !self.is_instruction_start(offset) {
+                return Err(reject(format!(
+                    "stack map frame at offset {offset}, which is not the start of an instruction"
+                )));
+            }
+
+            previous_offset = Some(offset);
+            frames.insert(
+                offset,
+                self.new_frame(locals.clone(), stack)
+                    .map_err(|err| locate(err, offset, None))?,
+            );
+        }
+
+        Ok(frames)
+    }
+
+    /// The slots taken by the types of a stack map frame
+    fn expand(&self, types: &[VerificationTypeInfo]) -> anyhow::Result<Vec<VerificationType>> {
+        let mut slots = vec![];
+
+        for ty in types {
+            let ty = match ty {
+                VerificationTypeInfo::Top => VerificationType::Top,
+                VerificationTypeInfo::Integer => VerificationType::Int,
+                VerificationTypeInfo::Float => VerificationType::Float,
+                VerificationTypeInfo::Long => VerificationType::Long,
+                VerificationTypeInfo::Double => VerificationType::Double,
+                VerificationTypeInfo::Null => VerificationType::Null,
+                VerificationTypeInfo::UninitializedThis => VerificationType::UninitializedThis,
+                VerificationTypeInfo::Object { object } => VerificationType::Reference(
+                    type_of_class_name(&object.name).map_err(|err| reject(format!("{err:#}")))?,
+                ),
+                VerificationTypeInfo::Uninitialized { offset } => {
+                    let offset = *offset as usize;
+
+                    if !self.is_instruction_start(offset) || self.code[offset] != 0xbb {
+                        return Err(reject(format!(
+                            "uninitialized({offset}) in a stack map frame, while there is no new instruction at {offset}"
+                        )));
+                    }
+
+                    VerificationType::Uninitialized(offset)
+                }
+            };
+
+            ty.push_slots(&mut slots);
+        }
+
+        Ok(slots)
+    }
+
+    fn check_exception_table(&self) -> anyhow::Result<()> {
+        let throwable = VerificationType::class("java/lang/Throwable");
+
+        for entry in self.method.exception_table() {
+            let start = entry.start_pc as usize;
+            let end = entry.end_pc as usize;
+            let handler = entry.handler_pc as usize;
+
+            if start >= end
+                || !self.is_instruction_start(start)
+                || !(end == self.code.len() || self.is_instruction_start(end))
+                || !self.is_instruction_start(handler)
+            {
+                return Err(reject(format!(
+                    "invalid exception handler (start = {start}, end = {end}, handler = {handler})"
+                )));
+            }
+
+            if let Some(catch_type) = &entry.catch_type
+                && !self.is_assignable(&VerificationType::class(&catch_type.name), &throwable)?
+            {
+                return Err(reject(format!(
+                    "catch type {} of the exception handler at {handler} is not a Throwable",
+                    catch_type.name
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Checks that the frame of an instruction (with the exception on the operand stack) matches
+    /// the ones of the exception handlers covering it
+    fn check_handlers(&self, offset: usize, frame: &Frame) -> anyhow::Result<()> {
+        for entry in self.method.exception_table() {
+            if !(entry.start_pc as usize..entry.end_pc as usize).contains(&offset) {
+                continue;
+            }
+
+            let catch_type = entry
+                .catch_type
+                .as_ref()
+                .map_or("java/lang/Throwable", |c| c.name.as_str());
+
+            let exception_frame = Frame {
+                locals: frame.locals.clone(),
+                stack: vec![VerificationType::class(catch_type)],
+                this_uninit: frame.this_uninit,
+            };
+
+            self.check_frame(&exception_frame, entry.handler_pc as usize)?;
+        }
+
+        Ok(())
+    }
+
+    /// Checks that a frame is assignable to the one recorded in the StackMapTable at the given
+    /// offset, as needed to jump there (JVMS §4.10.1.4)
+    fn check_frame(&self, frame: &Frame, target: usize) -> anyhow::Result<()> {
+        let Some(recorded) = self.stack_map.get(&target) else {
+            return Err(reject(format!("no stack map frame at {target}")));
+        };
+
+        let mut is_assignable = frame.locals.len() == recorded.locals.len()
+            && frame.stack.len() == recorded.stack.len()
+            && (!frame.this_uninit || recorded.this_uninit);
+
+        for (from, to) in frame
+            .locals
+            .iter()
+            .zip(&recorded.locals)
+            .chain(frame.stack.iter().zip(&recorded.stack))
+        {
+            if !is_assignable {
+                break;
+            }
+
+            is_assignable = self.is_assignable(from, to)?;
+        }
+
+        if !is_assignable {
+            return Err(reject_frame(
+                format!("frame not assignable to the stack map frame at {target}"),
+                recorded,
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// The offset of the target of a branch, checking its frame
+    fn check_branch(&self, frame: &Frame, offset: usize, delta: i32) -> anyhow::Result<()> {
+        let target = offset as i64 + delta as i64;
+
+        if target < 0 || !self.is_instruction_start(target as usize) {
+            return Err(reject(format!(
+                "branch target {target} is not the start of an instruction"
+            )));
+        }
+
+        self.check_frame(frame, target as usize)
+    }
+
+    /// Whether values of a type are also values of another one (JVMS §4.10.1.2)
+    fn is_assignable(
+        &self,
+        from: &VerificationType,
+        to: &VerificationType,
+    ) -> anyhow::Result<bool> {
+        Ok(match (from, to) {
+            (from, to) if from == to => true,
+            (_, VerificationType::Top) => true,
+            (VerificationType::Null, VerificationType::Reference(_)) => true,
+            (VerificationType::Reference(from), VerificationType::Reference(to)) => {
+                self.is_java_assignable(from, to)?
+            }
+            _ => false,
+        })
+    }
+
+    /// Whether values of a reference type are also values of another one. As for the verifier of
+    /// the JVMS, any class is assignable to an interface, this being checked at run time.
+    fn is_java_assignable(
+        &self,
+        from: &JvmTypeDescriptor,
+        to: &JvmTypeDescriptor,
+    ) -> anyhow::Result<bool> {
+        Ok(match (from, to) {
+            (from, to) if from == to => true,
+            (_, JvmTypeDescriptor::Class(to)) if to == "java/lang/Object" => true,
+            (JvmTypeDescriptor::Class(from), JvmTypeDescriptor::Class(to)) => {
+                self.is_interface(to)? || self.load_class(from)?.is_subclass_of(to)
+            }
+            (JvmTypeDescriptor::Array(_), JvmTypeDescriptor::Class(to)) => {
+                matches!(to.as_str(), "java/lang/Cloneable" | "java/io/Serializable")
+            }
+            (JvmTypeDescriptor::Array(from), JvmTypeDescriptor::Array(to))
+                if from.is_reference() && to.is_reference() =>
+            {
+                self.is_java_assignable(from, to)?
+            }
+            _ => false,
+        })
+    }
+
+    fn load_class(&self, name: &str) -> anyhow::Result<Class> {
+        self.env.resolve_type_name(name)?;
+
+        self.env
+            .get_class_or_interface(name)
+            .ok_or_else(|| anyhow!("{name} not found once loaded"))
+    }
+
+    fn is_interface(&self, name: &str) -> anyhow::Result<bool> {
+        self.load_class(name)?;
+
+        Ok(self.env.get_interface(name).is_some())
+    }
+
+    /// The class instantiated by the new instruction at the given offset
+    fn new_class_at(&self, offset: usize) -> anyhow::Result<String> {
+        let cp_index = self.u16_at(offset + 1)?;
+
+        self.class
+            .constant_pool
+            .get_class(cp_index)
+            .map(|c| c.name.as_ref().clone())
+            .ok_or_else(|| reject(format!("no class in constant pool at {cp_index}")))
+    }
+
+    fn push(&self, frame: &mut Frame, ty: VerificationType) -> anyhow::Result<()> {
+        ty.push_slots(&mut frame.stack);
+
+        if frame.stack.len() > self.max_stack {
+            return Err(reject(format!(
+                "operand stack overflow (max_stack is {})",
+                self.max_stack
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Pops a value of any type, taking both slots of long and double values
+    fn pop_any(&self, frame: &mut Frame) -> anyhow::Result<VerificationType> {
+        let Some(top) = frame.stack.pop() else {
+            return Err(reject("operand stack underflow"));
+        };
+
+        if top == VerificationType::Top
+            && frame
+                .stack
+                .last()
+                .is_some_and(VerificationType::is_two_slots)
+        {
+            return Ok(frame.stack.pop().unwrap());
+        }
+
+        Ok(top)
+    }
+
+    /// Pops a value assignable to the given type, returning its actual type
+    fn pop(
+        &self,
+        frame: &mut Frame,
+        expected: &VerificationType,
+    ) -> anyhow::Result<VerificationType> {
+        let actual = self.pop_any(frame)?;
+
+        if !self.is_assignable(&actual, expected)? {
+            return Err(reject(format!(
+                "expected {expected} on the operand stack, found {actual}"
+            )));
+        }
+
+        Ok(actual)
+    }
+
+    /// Pops a reference, initialized or not
+    fn pop_reference(&self, frame: &mut Frame) -> anyhow::Result<VerificationType> {
+        let actual = self.pop_any(frame)?;
+
+        if !actual.is_reference() {
+            return Err(reject(format!(
+                "expected a reference on the operand stack, found {actual}"
+            )));
+        }
+
+        Ok(actual)
+    }
+
+    /// Pops an array, returning the type of its components (None for null)
+    fn pop_array(&self, frame: &mut Frame) -> anyhow::Result<Option<JvmTypeDescriptor>> {
+        match self.pop_any(frame)? {
+            VerificationType::Null => Ok(None),
+            VerificationType::Reference(JvmTypeDescriptor::Array(component)) => {
+                Ok(Some(*component))
+            }
+            v => Err(reject(format!(
+                "expected an array on the operand stack, found {v}"
+            ))),
+        }
+    }
+
+    /// Takes the given number of slots from the top of the operand stack, for the instructions
+    /// handling values regardless of their type (pop, dup, swap...). Long and double values
+    /// cannot be split.
+    fn take_slots(&self, frame: &mut Frame, count: usize) -> anyhow::Result<Vec<VerificationType>> {
+        let stack = &frame.stack;
+
+        if stack.len() < count {
+            return Err(reject("operand stack underflow"));
+        }
+
+        let start = stack.len() - count;
+
+        // A Top slot is either the second one of a long or double value, or an unusable value
+        for i in start..stack.len() {
+            if stack[i] != VerificationType::Top {
+                continue;
+            }
+
+            if i == 0 || !stack[i - 1].is_two_slots() {
+                return Err(reject("unusable top value on the operand stack"));
+            }
+
+            if i == start {
+                return Err(reject(format!(
+                    "{} value split on the operand stack",
+                    stack[i - 1]
+                )));
+            }
+        }
+
+        Ok(frame.stack.split_off(start))
+    }
+
+    fn push_slots(&self, frame: &mut Frame, slots: &[&[VerificationType]]) -> anyhow::Result<()> {
+        for slots in slots {
+            frame.stack.extend_from_slice(slots);
+        }
+
+        if frame.stack.len() > self.max_stack {
+            return Err(reject(format!(
+                "operand stack overflow (max_stack is {})",
+                self.max_stack
+            )));
+        }
+
+        Ok(())
+    }
+
+    fn load(
+        &self,
+        frame: &mut Frame,
+        index: usize,
+        expected: Option<VerificationType>,
+    ) -> anyhow::Result<()> {
+        let Some(actual) = frame.locals.get(index).cloned() else {
+            return Err(reject(format!("local {index} out of bounds")));
+        };
+
+        let ty = match expected {
+            Some(expected) if actual == expected => expected,
+            None if actual.is_reference() => actual,
+            Some(expected) => {
+                return Err(reject(format!(
+                    "expected {expected} in local {index}, found {actual}"
+                )));
+            }
+            None => {
+                return Err(reject(format!(
+                    "expected a reference in local {index}, found {actual}"
+                )));
+            }
+        };
+
+        self.push(frame, ty)
+    }
+
+    fn store(
+        &self,
+        frame: &mut Frame,
+        index: usize,
+        expected: Option<VerificationType>,
+    ) -> anyhow::Result<()> {
+        let ty = match expected {
+            Some(expected) => {
+                self.pop(frame, &expected)?;
+                expected
+            }
+            None => self.pop_reference(frame)?,
+        };
+
+        let mut slots = vec![];
+        ty.push_slots(&mut slots);
+
+        if index + slots.len() > self.max_locals {
+            return Err(reject(format!("local {index} out of bounds")));
+        }
+
+        // Overwriting the second slot of a long or double makes the whole value unusable
+        if index > 0 && frame.locals[index - 1].is_two_slots() {
+            frame.locals[index - 1] = VerificationType::Top;
+        }
+
+        for (i, slot) in slots.into_iter().enumerate() {
+            frame.locals[index + i] = slot;
+        }
+
+        Ok(())
+    }
+
+    fn iinc(&self, frame: &Frame, index: usize) -> anyhow::Result<()> {
+        match frame.locals.get(index) {
+            Some(VerificationType::Int) => Ok(()),
+            Some(actual) => Err(reject(format!(
+                "expected int in local {index}, found {actual}"
+            ))),
+            None => Err(reject(format!("local {index} out of bounds"))),
+        }
+    }
+
+    fn ldc(&self, frame: &mut Frame, cp_index: u16, is_two_slots: bool) -> anyhow::Result<()> {
+        let constant = self
+            .class
+            .constant_pool
+            .get_loadable(cp_index)
+            .ok_or_else(|| reject(format!("no loadable constant at {cp_index}")))?;
+
+        let ty = match constant {
+            LoadableJvmConstant::Integer(_) => VerificationType::Int,
+            LoadableJvmConstant::Float(_) => VerificationType::Float,
+            LoadableJvmConstant::Long(_) => VerificationType::Long,
+            LoadableJvmConstant::Double(_) => VerificationType::Double,
+            LoadableJvmConstant::String(_) => VerificationType::class("java/lang/String"),
+            LoadableJvmConstant::Class(_) => VerificationType::class("java/lang/Class"),
+            LoadableJvmConstant::MethodType { .. } => {
+                VerificationType::class("java/lang/invoke/MethodType")
+            }
+            LoadableJvmConstant::MethodHandle(_) => {
+                VerificationType::class("java/lang/invoke/MethodHandle")
+            }
+            LoadableJvmConstant::Dynamic { ty, .. } => VerificationType::from_descriptor(&ty),
+        };
+
+        if ty.is_two_slots() != is_two_slots {
+            return Err(reject(format!(
+                "{} cannot load the constant at {cp_index}, of type {ty}",
+                if is_two_slots { "ldc2_w" } else { "ldc" }
+            )));
+        }
+
+        self.push(frame, ty)
+    }
+
+    /// Checks an instruction against its incoming frame, returning the frame of the next
+    /// instruction (None after an unconditional branch)
+    fn check_instruction(&self, offset: usize, frame: &Frame) -> anyhow::Result<Option<Frame>> {
+        use VerificationType as T;
+
+        /// The types of the locals of the load and store instructions (None for references)
+        const LOCAL_TYPES: [Option<T>; 5] = [
+            Some(T::Int),
+            Some(T::Long),
+            Some(T::Float),
+            Some(T::Double),
+            None,
+        ];
+        const ARITHMETIC_TYPES: [T; 4] = [T::Int, T::Long, T::Float, T::Double];
+
+        let mut next = frame.clone();
+        let op_code = self.code[offset];
+
+        match op_code {
+            0x00 => (),
+            0x01 => self.push(&mut next, T::Null)?,
+            0x02..=0x08 | 0x10 | 0x11 => self.push(&mut next, T::Int)?,
+            0x09 | 0x0a => self.push(&mut next, T::Long)?,
+            0x0b..=0x0d => self.push(&mut next, T::Float)?,
+            0x0e | 0x0f => self.push(&mut next, T::Double)?,
+            0x12 => self.ldc(&mut next, self.u8_at(offset + 1)?.into(), false)?,
+            0x13 => self.ldc(&mut next, self.u16_at(offset + 1)?, false)?,
+            0x14 => self.ldc(&mut next, self.u16_at(offset + 1)?, true)?,
+            v @ 0x15..=0x19 => {
+                let index = self.u8_at(offset + 1)? as usize;
+                self.load(&mut next, index, LOCAL_TYPES[(v - 0x15) as usize].clone())?
+            }
+            v @ 0x1a..=0x2d => {
+                let index = ((v - 0x1a) % 4) as usize;
+                self.load(
+                    &mut next,
+                    index,
+                    LOCAL_TYPES[((v - 0x1a) / 4) as usize].clone(),
+                )?
+            }
+            v @ 0x2e..=0x35 => {
+                self.pop(&mut next, &T::Int)?;
+                let component = self.pop_array(&mut next)?;
+
+                let ty = match (v, component) {
+                    (0x32, None) => T::Null,
+                    (0x32, Some(component)) if component.is_reference() => T::Reference(component),
+                    (v, None) => T::from_descriptor(&Self::array_component(v)[0]),
+                    (v, Some(component)) if Self::array_component(v).contains(&component) => {
+                        T::from_descriptor(&component)
+                    }
+                    (_, Some(component)) => {
+                        return Err(reject(format!(
+                            "array of {component} loaded by the wrong instruction"
+                        )));
+                    }
+                };
+
+                self.push(&mut next, ty)?;
+            }
+            v @ 0x36..=0x3a => {
+                let index = self.u8_at(offset + 1)? as usize;
+                self.store(&mut next, index, LOCAL_TYPES[(v - 0x36) as usize].clone())?
+            }
+            v @ 0x3b..=0x4e => {
+                let index = ((v - 0x3b) % 4) as usize;
+                self.store(
+                    &mut next,
+                    index,
+                    LOCAL_TYPES[((v - 0x3b) / 4) as usize].clone(),
+                )?
+            }
+            v @ 0x4f..=0x56 => {
+                if v == 0x53 {
+                    self.pop(&mut next, &T::class("java/lang/Object"))?;
+                } else {
+                    self.pop(&mut next, &T::from_descriptor(&Self::array_component(v)[0]))?;
+                }
+
+                self.pop(&mut next, &T::Int)?;
+
+                match (v, self.pop_array(&mut next)?) {
+                    (_, None) => (),
+                    (0x53, Some(component)) if component.is_reference() => (),
+                    (v, Some(component)) if Self::array_component(v).contains(&component) => (),
+                    (_, Some(component)) => {
+                        return Err(reject(format!(
+                            "array of {component} stored into by the wrong instruction"
+                        )));
+                    }
+                }
+            }
+            0x57 => {
+                self.take_slots(&mut next, 1)?;
+            }
+            0x58 => {
+                self.take_slots(&mut next, 2)?;
+            }
+            0x59 => {
+                let v1 = self.take_slots(&mut next, 1)?;
+                self.push_slots(&mut next, &[&v1, &v1])?;
+            }
+            0x5a..=0x5e => {
+                // The top values are copied beneath the next ones
+                let (copied, skipped) = match op_code {
+                    0x5a => (1, 1),
+                    0x5b => (1, 2),
+                    0x5c => (2, 0),
+                    0x5d => (2, 1),
+                    _ => (2, 2),
+                };
+
+                let v1 = self.take_slots(&mut next, copied)?;
+                let v2 = self.take_slots(&mut next, skipped)?;
+                self.push_slots(&mut next, &[&v1, &v2, &v1])?;
+            }
+            0x5f => {
+                let v1 = self.take_slots(&mut next, 1)?;
+                let v2 = self.take_slots(&mut next, 1)?;
+                self.push_slots(&mut next, &[&v1, &v2])?;
+            }
+            v @ 0x60..=0x73 => {
+                let ty = &ARITHMETIC_TYPES[((v - 0x60) % 4) as usize];
+                self.pop(&mut next, ty)?;
+                self.pop(&mut next, ty)?;
+                self.push(&mut next, ty.clone())?;
+            }
+            v @ 0x74..=0x77 => {
+                let ty = &ARITHMETIC_TYPES[((v - 0x74) % 4) as usize];
+                self.pop(&mut next, ty)?;
+                self.push(&mut next, ty.clone())?;
+            }
+            v @ 0x78..=0x7d => {
+                // The shift distance is an int, for longs too
+                let ty = if v % 2 == 0 { T::Int } else { T::Long };
+                self.pop(&mut next, &T::Int)?;
+                self.pop(&mut next, &ty)?;
+                self.push(&mut next, ty)?;
+            }
+            v @ 0x7e..=0x83 => {
+                let ty = if v % 2 == 0 { T::Int } else { T::Long };
+                self.pop(&mut next, &ty)?;
+                self.pop(&mut next, &ty)?;
+                self.push(&mut next, ty)?;
+            }
+            0x84 => self.iinc(&next, self.u8_at(offset + 1)?.into())?,
+            v @ 0x85..=0x90 => {
+                let from = (v - 0x85) / 3;
+                let to = ARITHMETIC_TYPES
+                    .iter()
+                    .enumerate()
+                    .filter(|(i, _)| *i != from as usize)
+                    .nth(((v - 0x85) % 3) as usize)
+                    .map(|(_, ty)| ty.clone())
+                    .unwrap();
+
+                self.pop(&mut next, &ARITHMETIC_TYPES[from as usize])?;
+                self.push(&mut next, to)?;
+            }
+            0x91..=0x93 => {
+                self.pop(&mut next, &T::Int)?;
+                self.push(&mut next, T::Int)?;
+            }
+            v @ 0x94..=0x98 => {
+                let ty = match v {
+                    0x94 => T::Long,
+                    0x95 | 0x96 => T::Float,
+                    _ => T::Double,
+                };
+                self.pop(&mut next, &ty)?;
+                self.pop(&mut next, &ty)?;
+                self.push(&mut next, T::Int)?;
+            }
+            v @ 0x99..=0xa6 | v @ 0xc6 | v @ 0xc7 => {
+                match v {
+                    0x99..=0x9e => {
+                        self.pop(&mut next, &T::Int)?;
+                    }
+                    0x9f..=0xa4 => {
+                        self.pop(&mut next, &T::Int)?;
+                        self.pop(&mut next, &T::Int)?;
+                    }
+                    0xa5 | 0xa6 => {
+                        self.pop_reference(&mut next)?;
+                        self.pop_reference(&mut next)?;
+                    }
+                    _ => {
+                        self.pop_reference(&mut next)?;
+                    }
+                }
+
+                let delta = self.u16_at(offset + 1)? as i16;
+                self.check_branch(&next, offset, delta.into())?;
+            }
+            0xa7 => {
+                let delta = self.u16_at(offset + 1)? as i16;
+                self.check_branch(&next, offset, delta.into())?;
+
+                return Ok(None);
+            }
+            0xc8 => {
+                self.check_branch(&next, offset, self.i32_at(offset + 1)?)?;
+
+                return Ok(None);
+            }
+            0xa8 | 0xa9 | 0xc9 => {
+                return Err(reject(
+                    "jsr and ret are not allowed in methods verified by type checking",
+                ));
+            }
+            0xaa | 0xab => {
+                self.pop(&mut next, &T::Int)?;
+
+                let operands = Self::switch_operands(offset);
+                let default = self.i32_at(operands)?;
+
+                self.check_branch(&next, offset, default)?;
+
+                if op_code == 0xaa {
+                    let low = self.i32_at(operands + 4)? as i64;
+                    let high = self.i32_at(operands + 8)? as i64;
+
+                    for i in 0..=(high - low) as usize {
+                        let delta = self.i32_at(operands + 12 + 4 * i)?;
+                        self.check_branch(&next, offset, delta)?;
+                    }
+                } else {
+                    let npairs = self.i32_at(operands + 4)? as usize;
+                    let mut previous_key = None;
+
+                    for i in 0..npairs {
+                        let key = self.i32_at(operands + 8 + 8 * i)?;
+
+                        if previous_key.is_some_and(|previous| previous >= key) {
+                            return Err(reject("lookupswitch keys not sorted"));
+                        }
+
+                        previous_key = Some(key);
+
+                        let delta = self.i32_at(operands + 12 + 8 * i)?;
+                        self.check_branch(&next, offset, delta)?;
+                    }
+                }
+
+                return Ok(None);
+            }
+            v @ 0xac..=0xb0 => {
+                let Some(return_type) = self.method.ret_type() else {
+                    return Err(reject("value returned by a void method"));
+                };
+
+                let return_type = T::from_descriptor(return_type);
+
+                let is_matching = match v {
+                    0xb0 => matches!(return_type, T::Reference(_)),
+                    v => return_type == ARITHMETIC_TYPES[(v - 0xac) as usize],
+                };
+
+                if !is_matching {
+                    return Err(reject(format!(
+                        "wrong return instruction for a method returning {return_type}"
+                    )));
+                }
+
+                self.pop(&mut next, &return_type)?;
+
+                return Ok(None);
+            }
+            0xb1 => {
+                if self.method.ret_type().is_some() {
+                    return Err(reject("no value returned by a non-void method"));
+                }
+
+                if frame.this_uninit {
+                    return Err(reject(
+                        "constructor returning before the initialization of this",
+                    ));
+                }
+
+                return Ok(None);
+            }
+            v @ 0xb2..=0xb5 => {
+                let cp_index = self.u16_at(offset + 1)?;
+                let field = self
+                    .class
+                    .constant_pool
+                    .get_field_ref(cp_index)
+                    .ok_or_else(|| reject(format!("no field reference at {cp_index}")))?;
+
+                let ty = T::from_descriptor(&field.ty);
+                let owner = T::class(&field.class.name);
+
+                match v {
+                    0xb2 => self.push(&mut next, ty)?,
+                    0xb3 => {
+                        self.pop(&mut next, &ty)?;
+                    }
+                    0xb4 => {
+                        self.pop(&mut next, &owner)?;
+                        self.push(&mut next, ty)?;
+                    }
+                    _ => {
+                        self.pop(&mut next, &ty)?;
+
+                        // The fields declared by the class can be set before calling the
+                        // constructor of its superclass
+                        let receiver = self.pop_any(&mut next)?;
+                        let is_own_field = receiver == T::UninitializedThis
+                            && field.class.name == self.class.name
+                            && self.class.get_declared_field(&field.name).is_some();
+
+                        if !is_own_field && !self.is_assignable(&receiver, &owner)? {
+                            return Err(reject(format!(
+                                "expected {owner} on the operand stack, found {receiver}"
+                            )));
+                        }
+                    }
+                }
+            }
+            v @ 0xb6..=0xb9 => {
+                let cp_index = self.u16_at(offset + 1)?;
+                let constant_pool = &self.class.constant_pool;
+
+                let method_ref = match v {
+                    0xb6 => constant_pool
+                        .get_method_ref(cp_index)
+                        .map(|m| (m.class.name, m.name, m.ty)),
+                    0xb9 => constant_pool
+                        .get_interface_method_ref(cp_index)
+                        .map(|m| (m.class.name, m.name, m.ty)),
+                    _ => constant_pool
+                        .get_method_ref(cp_index)
+                        .map(|m| (m.class.name, m.name, m.ty))
+                        .or_else(|| {
+                            constant_pool
+                                .get_interface_method_ref(cp_index)
+                                .map(|m| (m.class.name, m.name, m.ty))
+                        }),
+                };
+
+                let Some((class_name, name, descriptor)) = method_ref else {
+                    return Err(reject(format!("no method reference at {cp_index}")));
+                };
+
+                let is_init = name.as_str() == "<init>";
+
+                if name.starts_with('<') && !(is_init && v == 0xb7) {
+                    return Err(reject(format!("invalid invocation of {name}")));
+                }
+
+                if v == 0xb9 {
+                    let slots = 1 + descriptor
+                        .parameter_types
+                        .iter()
+                        .map(|p| if p.is_two_slots() { 2 } else { 1 })
+                        .sum::<usize>();
+
+                    if self.u8_at(offset + 3)? as usize != slots || self.u8_at(offset + 4)? != 0 {
+                        return Err(reject("invalid operands of invokeinterface"));
+                    }
+                }
+
+                for parameter in descriptor.parameter_types.iter().rev() {
+                    self.pop(&mut next, &T::from_descriptor(parameter))?;
+                }
+
+                match v {
+                    0xb6 => {
+                        let receiver = type_of_class_name(&class_name)
+                            .map_err(|err| reject(format!("{err:#}")))?;
+                        self.pop(&mut next, &T::Reference(receiver))?;
+                    }
+                    0xb7 if is_init => {
+                        if descriptor.return_type.is_some() {
+                            return Err(reject("constructor returning a value"));
+                        }
+
+                        self.initialize(&mut next, &class_name)?;
+                    }
+                    0xb7 => {
+                        self.pop(&mut next, &T::class(&self.class.name))?;
+                    }
+                    0xb8 => (),
+                    _ => {
+                        self.pop(&mut next, &T::class(&class_name))?;
+                    }
+                }
+
+                if let Some(return_type) = &descriptor.return_type {
+                    self.push(&mut next, T::from_descriptor(return_type))?;
+                }
+            }
+            0xba => {
+                let cp_index = self.u16_at(offset + 1)?;
+                let dynamic_invoke = self
+                    .class
+                    .constant_pool
+                    .get_dynamic_invoke(cp_index)
+                    .ok_or_else(|| reject(format!("no dynamic call site at {cp_index}")))?;
+
+                if self.u16_at(offset + 3)? != 0 {
+                    return Err(reject("invalid operands of invokedynamic"));
+                }
+
+                for parameter in dynamic_invoke.ty.parameter_types.iter().rev() {
+                    self.pop(&mut next, &T::from_descriptor(parameter))?;
+                }
+
+                if let Some(return_type) = &dynamic_invoke.ty.return_type {
+                    self.push(&mut next, T::from_descriptor(return_type))?;
+                }
+            }
+            0xbb => {
+                if self.new_class_at(offset)?.starts_with('[') {
+                    return Err(reject("new of an array class"));
+                }
+
+                let created = T::Uninitialized(offset);
+
+                if next.stack.contains(&created) {
+                    return Err(reject(format!("{created} already on the operand stack")));
+                }
+
+                for local in next.locals.iter_mut().filter(|l| **l == created) {
+                    *local = T::Top;
+                }
+
+                self.push(&mut next, created)?;
+            }
+            0xbc => {
+                let component = match self.u8_at(offset + 1)? {
+                    4 => JvmTypeDescriptor::Boolean,
+                    5 => JvmTypeDescriptor::Char,
+                    6 => JvmTypeDescriptor::Float,
+                    7 => JvmTypeDescriptor::Double,
+                    8 => JvmTypeDescriptor::Byte,
+                    9 => JvmTypeDescriptor::Short,
+                    10 => JvmTypeDescriptor::Int,
+                    11 => JvmTypeDescriptor::Long,
+                    v => return Err(reject(format!("invalid newarray type {v}"))),
+                };
+
+                self.pop(&mut next, &T::Int)?;
+                self.push(
+                    &mut next,
+                    T::Reference(JvmTypeDescriptor::Array(Box::new(component))),
+                )?;
+            }
+            0xbd => {
+                let component = self.class_operand(offset)?;
+                let ty = JvmTypeDescriptor::Array(Box::new(component));
+
+                if Self::dimensions(&ty) > 255 {
+                    return Err(reject("array type with more than 255 dimensions"));
+                }
+
+                self.pop(&mut next, &T::Int)?;
+                self.push(&mut next, T::Reference(ty))?;
+            }
+            0xbe => {
+                self.pop_array(&mut next)?;
+                self.push(&mut next, T::Int)?;
+            }
+            0xbf => {
+                self.pop(&mut next, &T::class("java/lang/Throwable"))?;
+
+                return Ok(None);
+            }
+            0xc0 | 0xc1 => {
+                let ty = self.class_operand(offset)?;
+
+                self.pop(&mut next, &T::class("java/lang/Object"))?;
+
+                if op_code == 0xc0 {
+                    self.push(&mut next, T::Reference(ty))?;
+                } else {
+                    self.push(&mut next, T::Int)?;
+                }
+            }
+            0xc2 | 0xc3 => {
+                self.pop_reference(&mut next)?;
+            }
+            0xc4 => {
+                let index = self.u16_at(offset + 2)? as usize;
+
+                match self.u8_at(offset + 1)? {
+                    v @ 0x15..=0x19 => {
+                        self.load(&mut next, index, LOCAL_TYPES[(v - 0x15) as usize].clone())?
+                    }
+                    v @ 0x36..=0x3a => {
+                        self.store(&mut next, index, LOCAL_TYPES[(v - 0x36) as usize].clone())?
+                    }
+                    0x84 => self.iinc(&next, index)?,
+                    _ => {
+                        return Err(reject(
+                            "jsr and ret are not allowed in methods verified by type checking",
+                        ));
+                    }
+                }
+            }
+            0xc5 => {
+                let ty = self.class_operand(offset)?;
+                let dimensions = self.u8_at(offset + 3)? as usize;
+
+                if dimensions == 0 || Self::dimensions(&ty) < dimensions {
+                    return Err(reject(format!(
+                        "multianewarray of {dimensions} dimensions for {ty}"
+                    )));
+                }
+
+                for _ in 0..dimensions {
+                    self.pop(&mut next, &T::Int)?;
+                }
+
+                self.push(&mut next, T::Reference(ty))?;
+            }
+            v => return Err(reject(format!("unknown opcode 0x{v:02x}"))),
+        }
+
+        Ok(Some(next))
+    }
+
+    /// The type named by the class constant operand of an instruction
+    fn class_operand(&self, offset: usize) -> anyhow::Result<JvmTypeDescriptor> {
+        let name = self.new_class_at(offset)?;
+
+        type_of_class_name(&name).map_err(|err| reject(format!("{err:#}")))
+    }
+
+    fn dimensions(ty: &JvmTypeDescriptor) -> usize {
+        match ty {
+            JvmTypeDescriptor::Array(component) => 1 + Self::dimensions(component),
+            _ => 0,
+        }
+    }
+
+    /// The component types of the arrays handled by an array load or store instruction
+    fn array_component(op_code: u8) -> &'static [JvmTypeDescriptor] {
+        match op_code {
+            0x2e | 0x4f => &[JvmTypeDescriptor::Int],
+            0x2f | 0x50 => &[JvmTypeDescriptor::Long],
+            0x30 | 0x51 => &[JvmTypeDescriptor::Float],
+            0x31 | 0x52 => &[JvmTypeDescriptor::Double],
+            0x33 | 0x54 => &[JvmTypeDescriptor::Byte, JvmTypeDescriptor::Boolean],
+            0x34 | 0x55 => &[JvmTypeDescriptor::Char],
+            _ => &[JvmTypeDescriptor::Short],
+        }
+    }
+
+    /// Pops the receiver of a constructor, which is then initialized everywhere in the frame
+    fn initialize(&self, frame: &mut Frame, class_name: &str) -> anyhow::Result<()> {
+        let receiver = self.pop_any(frame)?;
+
+        let initialized = match &receiver {
+            VerificationType::UninitializedThis => {
+                let super_class = self.class.super_class.as_ref().map(|c| c.name.as_str());
+
+                if class_name != self.class.name.as_str() && Some(class_name) != super_class {
+                    return Err(reject(format!(
+                        "constructor of {class_name} invoked on this, which is not one of {} nor of its superclass",
+                        self.class.name
+                    )));
+                }
+
+                frame.this_uninit = false;
+
+                VerificationType::class(&self.class.name)
+            }
+            VerificationType::Uninitialized(new_offset) => {
+                let created_class = self.new_class_at(*new_offset)?;
+
+                if created_class != class_name {
+                    return Err(reject(format!(
+                        "constructor of {class_name} invoked on an instance of {created_class}"
+                    )));
+                }
+
+                VerificationType::class(&created_class)
+            }
+            v => {
+                return Err(reject(format!(
+                    "constructor invoked on {v}, which is not uninitialized"
+                )));
+            }
+        };
+
+        for ty in frame.locals.iter_mut().chain(frame.stack.iter_mut()) {
+            if *ty == receiver {
+                *ty = initialized.clone();
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::class::{
+        JvmUnit, parse_class_file,
+        tests::{ClassBytes, CodeBytes},
+    };
+    use crate::exec::exception::JavaException;
+
+    /// A class Test with a static method test(I)I, returning whether its argument is not 0
+    fn test_class(major_version: u16, code: &[u8], stack_map: &[&[u8]]) -> ClassBytes {
+        let mut class = ClassBytes::new("Test", major_version);
+
+        class.method(
+            0x0009,
+            "test",
+            "(I)I",
+            Some(CodeBytes {
+                max_stack: 1,
+                max_locals: 1,
+                code,
+                exception_table: &[],
+                stack_map,
+            }),
+        );
+
+        class
+    }
+
+    /// iload_0, ifeq +5, iconst_1, ireturn, iconst_0, ireturn
+    const IS_NOT_ZERO: &[u8] = &[0x1a, 0x99, 0x00, 0x05, 0x04, 0xac, 0x03, 0xac];
+
+    /// A same_frame at offset 6
+    const SAME_FRAME_AT_6: &[u8] = &[6];
+
+    /// A same_locals_1_stack_item_frame with an int at offset 6
+    const INT_ON_STACK_AT_6: &[u8] = &[64 + 6, 1];
+
+    fn verify(class: &ClassBytes) -> anyhow::Result<()> {
+        let env = JvmExecEnv::new();
+
+        for bytes in [
+            ClassBytes::new("java/lang/Object", 61).bytes(),
+            class.bytes(),
+        ] {
+            let class_file = parse_class_file("", &bytes)?;
+            env.add_unit(JvmUnit::from_class_file(class_file)?)?;
+        }
+
+        verify_class(&env, &env.get_class("Test").unwrap())
+    }
+
+    fn verify_error(class: &ClassBytes) -> String {
+        let err = verify(class).unwrap_err();
+
+        err.downcast_ref::<JavaException>()
+            .unwrap_or_else(|| panic!("not a java exception: {err:#}"))
+            .to_string()
+    }
+
+    #[test]
+    fn matching_stack_map_frame_is_accepted() {
+        verify(&test_class(61, IS_NOT_ZERO, &[SAME_FRAME_AT_6])).unwrap();
+    }
+
+    #[test]
+    fn stack_map_frame_mismatch_is_rejected() {
+        let class = test_class(61, IS_NOT_ZERO, &[INT_ON_STACK_AT_6]);
+
+        assert_eq!(
+            verify_error(&class),
+            "java.lang.VerifyError: Test.test(I)I @1: frame not assignable to the stack map \
+             frame at 6\n  \
+             current frame: locals: [int], stack: [int]\n  \
+             expected frame: locals: [int], stack: [int]"
+        );
+    }
+
+    #[test]
+    fn missing_stack_map_frame_is_rejected() {
+        let message = verify_error(&test_class(61, IS_NOT_ZERO, &[]));
+
+        assert!(
+            message.starts_with("java.lang.VerifyError: Test.test(I)I @1: no stack map frame at 6"),
+            "{message}"
+        );
+    }
+
+    #[test]
+    fn branch_inside_an_instruction_is_rejected() {
+        let mut code = IS_NOT_ZERO.to_vec();
+        // ifeq +2, in the middle of the ifeq
+        code[3] = 0x02;
+
+        let message = verify_error(&test_class(61, &code, &[SAME_FRAME_AT_6]));
+
+        assert!(
+            message.starts_with(
+                "java.lang.VerifyError: Test.test(I)I @1: branch target 3 is not the start of an \
+                 instruction\n  current frame: locals: [int], stack: [int]"
+            ),
+            "{message}"
+        );
+    }
+
+    #[test]
+    fn branch_outside_the_code_is_rejected() {
+        let mut code = IS_NOT_ZERO.to_vec();
+        // ifeq -2
+        code[2..4].copy_from_slice(&(-2i16).to_be_bytes());
+
+        let message = verify_error(&test_class(61, &code, &[SAME_FRAME_AT_6]));
+
+        assert!(
+            message.contains("@1: branch target -1 is not the start of an instruction"),
+            "{message}"
+        );
+    }
+
+    #[test]
+    fn class_files_older_than_version_50_are_not_verified() {
+        verify(&test_class(49, IS_NOT_ZERO, &[INT_ON_STACK_AT_6])).unwrap();
+        verify(&test_class(49, IS_NOT_ZERO, &[])).unwrap();
+    }
+
+    #[test]
+    fn version_50_class_files_fall_back_when_rejected() {
+        verify(&test_class(50, IS_NOT_ZERO, &[INT_ON_STACK_AT_6])).unwrap();
+        assert!(verify(&test_class(51, IS_NOT_ZERO, &[INT_ON_STACK_AT_6])).is_err());
+    }
+
+    #[test]
+    fn jsr_and_ret_are_rejected_when_type_checked() {
+        // jsr +4, iconst_0, ireturn, astore_0, ret 0
+        let code = [0xa8, 0x00, 0x04, 0x03, 0xac, 0x4b, 0xa9, 0x00];
+
+        verify(&test_class(49, &code, &[])).unwrap();
+        verify(&test_class(50, &code, &[])).unwrap();
+
+        assert_eq!(
+            verify_error(&test_class(51, &code, &[])),
+            "java.lang.VerifyError: Test.test(I)I @0: jsr and ret are not allowed in methods \
+             verified by type checking\n  current frame: locals: [int], stack: []"
+        );
+    }
+}
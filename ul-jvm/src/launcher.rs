@@ -0,0 +1,206 @@
+use std::collections::HashMap;
+
+use anyhow::bail;
+
+pub const USAGE: &str = "\
+Usage: java [options] <mainclass> [args...]
+           (to execute a class)
+   or  java [options] -jar <jarfile> [args...]
+           (to execute a jar file)
+
+ Arguments following the main class or -jar <jarfile> are passed as the arguments to main.
+
+ where options include:
+
+    -cp <class search path of directories and zip/jar files>
+    -classpath <class search path of directories and zip/jar files>
+    --class-path <class search path of directories and zip/jar files>
+                  A : separated list of directories, JAR archives
+                  and ZIP archives to search for class files.
+    -D<name>=<value>
+                  set a system property
+    -verbose:class
+                  enable verbose output of the loaded classes
+    --dump-class  write the parsed class file and unit of the main class
+                  as JSON in the current directory
+    -version      print product version and exit
+    -? -h -help --help
+                  print this help message and exit";
+
+/// What the launcher starts: a class from the class path, or the Main-Class of a jar
+#[derive(Debug, PartialEq)]
+pub enum LaunchTarget {
+    /// The binary name of the class (java/lang/Object)
+    Class(String),
+    Jar(String),
+}
+
+/// The command-line of the launcher, as accepted by java
+#[derive(Debug, PartialEq)]
+pub enum LaunchCommand {
+    Run(LaunchOptions),
+    PrintVersion,
+    PrintUsage,
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct LaunchOptions {
+    /// The class path given with -cp, -classpath or --class-path
+    pub class_path: Option<String>,
+    /// The system properties defined with -D
+    pub system_properties: HashMap<String, String>,
+    pub verbose_class: bool,
+    pub dump_class: bool,
+    pub target: Option<LaunchTarget>,
+    /// The arguments passed to main
+    pub args: Vec<String>,
+}
+
+impl LaunchCommand {
+    /// Parses the arguments of the launcher (without the program name). Options are only
+    /// accepted before the main class or the jar, everything after being passed to main.
+    pub fn parse(args: impl IntoIterator<Item = String>) -> anyhow::Result<Self> {
+        let mut args = args.into_iter();
+        let mut options = LaunchOptions::default();
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "-cp" | "-classpath" | "--class-path" => {
+                    let Some(class_path) = args.next() else {
+                        bail!("{arg} requires class path specification");
+                    };
+
+                    options.class_path = Some(class_path);
+                }
+                "-jar" => {
+                    let Some(jar) = args.next() else {
+                        bail!("-jar requires jar file specification");
+                    };
+
+                    options.target = Some(LaunchTarget::Jar(jar));
+                    break;
+                }
+                "-verbose" | "-verbose:class" => options.verbose_class = true,
+                "--dump-class" => options.dump_class = true,
+                "-version" | "--version" => return Ok(Self::PrintVersion),
+                "-?" | "-h" | "-help" | "--help" => return Ok(Self::PrintUsage),
+                _ if arg.starts_with("-D") => {
+                    let (name, value) = arg[2..].split_once('=').unwrap_or((&arg[2..], ""));
+
+                    if name.is_empty() {
+                        bail!("{arg} is not a valid system property definition");
+                    }
+
+                    options
+                        .system_properties
+                        .insert(name.to_string(), value.to_string());
+                }
+                _ if arg.starts_with('-') => bail!("Unrecognized option: {arg}"),
+                _ => {
+                    options.target = Some(LaunchTarget::Class(arg.replace('.', "/")));
+                    break;
+                }
+            }
+        }
+
+        options.args = args.collect();
+
+        Ok(Self::Run(options))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> anyhow::Result<LaunchCommand> {
+        LaunchCommand::parse(args.iter().map(|arg| arg.to_string()))
+    }
+
+    fn parse_options(args: &[&str]) -> LaunchOptions {
+        match parse(args).unwrap() {
+            LaunchCommand::Run(options) => options,
+            command => panic!("expected a run command, got {command:?}"),
+        }
+    }
+
+    #[test]
+    fn system_properties_with_and_without_value() {
+        let options = parse_options(&["-Da=1", "-Db", "-Dc=x=y", "-Dd=", "Main"]);
+
+        assert_eq!(options.system_properties.len(), 4);
+        assert_eq!(options.system_properties["a"], "1");
+        assert_eq!(options.system_properties["b"], "");
+        assert_eq!(options.system_properties["c"], "x=y");
+        assert_eq!(options.system_properties["d"], "");
+
+        assert!(parse(&["-D=1", "Main"]).is_err());
+        assert!(parse(&["-D", "Main"]).is_err());
+    }
+
+    #[test]
+    fn jar_requires_a_file() {
+        let error = parse(&["-cp", "lib", "-jar"]).unwrap_err();
+
+        assert_eq!(error.to_string(), "-jar requires jar file specification");
+        assert!(parse(&["-cp"]).is_err());
+    }
+
+    #[test]
+    fn jar_target_without_main_class() {
+        let options = parse_options(&["-jar", "app.jar", "a", "-version"]);
+
+        assert_eq!(
+            options.target,
+            Some(LaunchTarget::Jar("app.jar".to_string()))
+        );
+        assert_eq!(options.args, ["a", "-version"]);
+
+        // A jar without a Main-Class, or no target at all, is reported when launching
+        let options = parse_options(&["-verbose:class"]);
+
+        assert!(options.verbose_class);
+        assert_eq!(options.target, None);
+    }
+
+    #[test]
+    fn dump_class_option() {
+        assert!(parse_options(&["--dump-class", "Main"]).dump_class);
+        assert!(!parse_options(&["Main", "--dump-class"]).dump_class);
+    }
+
+    #[test]
+    fn args_after_main_class_are_untouched() {
+        let options = parse_options(&[
+            "-cp",
+            "classes",
+            "com.example.Main",
+            "-Dx=y",
+            "-cp",
+            "a.b",
+            "",
+            "--help",
+        ]);
+
+        assert_eq!(options.class_path.as_deref(), Some("classes"));
+        assert!(options.system_properties.is_empty());
+        assert_eq!(
+            options.target,
+            Some(LaunchTarget::Class("com/example/Main".to_string()))
+        );
+        assert_eq!(options.args, ["-Dx=y", "-cp", "a.b", "", "--help"]);
+    }
+
+    #[test]
+    fn version_usage_and_unknown_options() {
+        assert_eq!(
+            parse(&["-version", "Main"]).unwrap(),
+            LaunchCommand::PrintVersion
+        );
+        assert_eq!(parse(&["-?"]).unwrap(), LaunchCommand::PrintUsage);
+        assert_eq!(
+            parse(&["-Xfoo", "Main"]).unwrap_err().to_string(),
+            "Unrecognized option: -Xfoo"
+        );
+    }
+}
@@ -1,17 +1,24 @@
-use std::{io::stdout, path::Path};
-
-use anyhow::{Context, bail};
-use binrw::BinRead;
-use class::{JvmUnit, parser::ClassFile};
-use class_container::read_container;
-use either::Either;
-use exec::{JvmExecEnv, thread::JvmThread};
-use log::{debug, error, info, warn};
+use std::{collections::HashMap, io::stdout, path::Path, sync::Arc};
+
+use anyhow::Context;
+use class::{ClassFormatError, JvmUnit, parse_class_file};
+use class_container::ClassContainer;
+use class_path::ClassPath;
+use exec::{
+    JvmExecEnv, UnitLoader,
+    exception::{JavaException, ProgramExit},
+    runtime_type::RuntimeType,
+    thread::JvmThread,
+};
+use launcher::{LaunchCommand, LaunchTarget, USAGE};
+use log::{debug, error, info};
 use types::JvmTypeDescriptor;
 
 mod class;
 mod class_container;
+mod class_path;
 mod exec;
+mod launcher;
 mod native;
 mod types;
 
@@ -19,33 +26,135 @@ fn main() {
     env_logger::init();
 
     info!("uLambda's JVM version {}", env!("CARGO_PKG_VERSION"));
-    let mut jvm_exec_env = JvmExecEnv::new();
-
-    let first_unit =
-        load_unit("Main", &["test-classes".to_string()], true).expect("loading Main.class");
-
-    let mut done = jvm_exec_env.add_unit(first_unit, true);
-
-    while !done {
-        for class in jvm_exec_env.missing_units() {
-            let jvm_unit = load_unit(
-                &class,
-                &[
-                    ".".to_string(),
-                    "/usr/lib/jvm/jre/jmods/java.base.jmod".to_string(),
-                    "/usr/lib/jvm/default-java/jmods/java.base.jmod".to_string(),
-                ],
-                false,
-            )
-            .unwrap_or_else(|_| panic!("unable to load class file {class}"));
-
-            done = jvm_exec_env.add_unit(jvm_unit, false);
+
+    let options = match LaunchCommand::parse(std::env::args().skip(1)) {
+        Ok(LaunchCommand::Run(options)) => options,
+        Ok(LaunchCommand::PrintVersion) => {
+            eprintln!("ul-jvm version \"{}\"", env!("CARGO_PKG_VERSION"));
+            return;
+        }
+        Ok(LaunchCommand::PrintUsage) => {
+            println!("{USAGE}");
+            return;
+        }
+        Err(err) => {
+            eprintln!("{err}");
+            eprintln!("Error: Could not create the Java Virtual Machine.");
+            eprintln!("Error: A fatal exception has occurred. Program will exit.");
+            std::process::exit(1);
+        }
+    };
+
+    let (mut class_path, main_class) = match options.target {
+        Some(LaunchTarget::Jar(jar_path)) => {
+            let jar = match ClassContainer::new(Path::new(&jar_path)) {
+                Ok(v) => v,
+                Err(err) => {
+                    eprintln!("Error: Unable to access jarfile {jar_path}");
+                    debug!("unable to read {jar_path}: {err:#}");
+                    std::process::exit(1);
+                }
+            };
+
+            let Some(main_class) = jar.main_class() else {
+                eprintln!("no main manifest attribute, in {jar_path}");
+                std::process::exit(1);
+            };
+
+            // As with java, the class path only consists of the jar and its own Class-Path
+            let mut class_path = ClassPath::new();
+            class_path.push_container(jar);
+
+            (class_path, main_class)
+        }
+        Some(LaunchTarget::Class(main_class)) => {
+            let class_path = options
+                .class_path
+                .or_else(|| std::env::var("CLASSPATH").ok())
+                .unwrap_or_else(|| ".".to_string());
+
+            (ClassPath::parse(&class_path), main_class)
+        }
+        None => {
+            eprintln!("{USAGE}");
+            std::process::exit(1);
         }
+    };
+
+    let mut system_properties = HashMap::from([
+        ("java.class.path".to_string(), class_path.to_string()),
+        ("java.vm.name".to_string(), "ul-jvm".to_string()),
+        (
+            "java.vm.version".to_string(),
+            env!("CARGO_PKG_VERSION").to_string(),
+        ),
+        (
+            "file.separator".to_string(),
+            std::path::MAIN_SEPARATOR.to_string(),
+        ),
+        (
+            "path.separator".to_string(),
+            if cfg!(windows) { ";" } else { ":" }.to_string(),
+        ),
+        (
+            "line.separator".to_string(),
+            if cfg!(windows) { "\r\n" } else { "\n" }.to_string(),
+        ),
+    ]);
+
+    if let Ok(dir) = std::env::current_dir() {
+        system_properties.insert("user.dir".to_string(), dir.to_string_lossy().to_string());
+    }
+
+    system_properties.extend(options.system_properties);
+
+    class_path.push(Path::new("/usr/lib/jvm/jre/jmods/java.base.jmod"));
+    class_path.push(Path::new("/usr/lib/jvm/default-java/jmods/java.base.jmod"));
+
+    let loader = ClassPathLoader {
+        class_path,
+        verbose_class: options.verbose_class,
+    };
+
+    let first_unit = match loader.load_unit_with_dump(&main_class, options.dump_class) {
+        Ok(v) => v,
+        Err(err) => {
+            let main_class = main_class.replace('/', ".");
+
+            if let Some(format_error) = err.downcast_ref::<ClassFormatError>() {
+                eprintln!("Error: LinkageError occurred while loading main class {main_class}");
+                eprintln!("\tjava.lang.ClassFormatError: {format_error}");
+                std::process::exit(1);
+            }
+
+            eprintln!("Error: Could not find or load main class {main_class}");
+            eprintln!("Caused by: java.lang.ClassNotFoundException: {main_class}");
+            debug!("unable to load the main class: {err:?}");
+            std::process::exit(1);
+        }
+    };
+
+    let jvm_exec_env = JvmExecEnv::new()
+        .with_loader(loader)
+        .with_system_properties(system_properties);
+
+    let start_class_name = first_unit.this_class.name.clone();
+
+    if let Err(err) = jvm_exec_env.add_unit(first_unit) {
+        let Some(exception) = err.downcast_ref::<JavaException>() else {
+            panic!("loading the superclasses of the start class: {err:?}");
+        };
+
+        eprintln!(
+            "Error: LinkageError occurred while loading main class {}",
+            start_class_name.replace('/', ".")
+        );
+        eprintln!("\t{exception}");
+        std::process::exit(1);
     }
 
     let start_class = jvm_exec_env
-        .start_class
-        .as_ref()
+        .get_class(&start_class_name)
         .expect("no start class found");
 
     let main_method = start_class
@@ -58,13 +167,54 @@ fn main() {
                 return_type: None,
             },
         )
-        .expect("no main method in the specified class");
+        .unwrap_or_else(|| {
+            eprintln!(
+                "Error: Main method not found in class {}, please define the main method as:",
+                start_class.name.replace('/', ".")
+            );
+            eprintln!("   public static void main(String[] args)");
+            std::process::exit(1);
+        });
 
     let mut main_thread = JvmThread::new(start_class.clone(), &main_method);
 
+    let args = options
+        .args
+        .into_iter()
+        .map(|arg| RuntimeType::String(Arc::new(arg)))
+        .collect();
+    let args = jvm_exec_env.heap.new_array_with(
+        JvmTypeDescriptor::Class("java/lang/String".to_string()),
+        args,
+    );
+
+    main_thread
+        .store_to_local(0, RuntimeType::Array(args))
+        .expect("passing arguments to main");
+
     debug!("starting main thread (class: {})", start_class.name);
 
-    if let Err(err) = main_thread.run(&jvm_exec_env) {
+    // The main class is initialized before main is invoked (JVMS §5.2), the main thread (and
+    // its arguments) being suspended meanwhile
+    let suspension = main_thread.suspend(&jvm_exec_env);
+    let result = JvmThread::initialize_class(&jvm_exec_env, &start_class);
+    drop(suspension);
+
+    let result = result.and_then(|_| main_thread.run(&jvm_exec_env));
+
+    if let Err(err) = result {
+        if let Some(ProgramExit { status }) = err.downcast_ref::<ProgramExit>() {
+            debug!("main thread exited with status {status}");
+
+            std::process::exit(*status);
+        }
+
+        if let Some(exception) = err.downcast_ref::<JavaException>() {
+            eprintln!("Exception in thread \"main\" {exception}");
+
+            std::process::exit(1);
+        }
+
         error!("error on main thread: {err}");
 
         main_thread.dump_to(stdout()).unwrap();
@@ -73,62 +223,46 @@ fn main() {
     };
 
     debug!("main thread terminated");
+    debug!(
+        "classes referenced but never loaded: {:?}",
+        jvm_exec_env.differed_units()
+    );
 }
 
-pub fn load_unit(full_name: &str, class_path: &[String], dump: bool) -> anyhow::Result<JvmUnit> {
-    // TODO: cache units location
+/// Loads the units from a list of directories and containers (jars and jmods)
+struct ClassPathLoader {
+    class_path: ClassPath,
+    /// Whether to print the loaded classes, as with -verbose:class
+    verbose_class: bool,
+}
 
-    debug!("Looking up class file for {full_name} in {class_path:?}...");
-    let mut source = None;
+impl ClassPathLoader {
+    /// Loads a unit, writing its class file and unit as JSON if asked to
+    fn load_unit_with_dump(&self, full_name: &str, dump: bool) -> anyhow::Result<JvmUnit> {
+        let jvm_unit = load_unit(full_name, &self.class_path, dump)?;
 
-    for current_dir in class_path {
-        if current_dir.ends_with(".jar")
-            || current_dir.ends_with(".JAR")
-            || current_dir.ends_with(".jmod")
-            || current_dir.ends_with(".JMOD")
+        if self.verbose_class
+            && let Some(entry) = self.class_path.find(full_name)
         {
-            let jar_file = match read_container(current_dir) {
-                Ok(v) => v,
-                Err(e) => {
-                    warn!("unable to read JAR file {current_dir}: {e}. Skipping...");
-                    continue;
-                }
-            };
-
-            if jar_file.has_unit(full_name) {
-                source = Some(Either::Right(jar_file.read_class_file(full_name)?));
-                break;
-            }
+            println!("[Loaded {} from {entry}]", full_name.replace('/', "."));
         }
 
-        let current_dir = Path::new(current_dir);
-
-        // TODO: Handle when this is a dir
-        // current_dir.is_dir();
-
-        let current_path = current_dir.join(full_name).with_extension("class");
+        Ok(jvm_unit)
+    }
+}
 
-        if current_path.is_file() {
-            source = Some(Either::Left(
-                std::fs::OpenOptions::new()
-                    .read(true)
-                    .open(current_path)
-                    .context("opening class file")?,
-            ));
-            break;
-        }
+impl UnitLoader for ClassPathLoader {
+    fn load_unit(&self, name: &str) -> anyhow::Result<JvmUnit> {
+        self.load_unit_with_dump(name, false)
     }
+}
 
-    let Some(source) = source else {
-        bail!("no JVM unit in class path for {full_name}");
-    };
+pub fn load_unit(full_name: &str, class_path: &ClassPath, dump: bool) -> anyhow::Result<JvmUnit> {
+    debug!("Looking up class file for {full_name} in {class_path}...");
 
-    debug!("Found class file for {full_name}");
+    let source = class_path.read_class_file(full_name)?;
 
-    let parsed_class = match source {
-        Either::Left(mut v) => ClassFile::read(&mut v)?,
-        Either::Right(mut v) => ClassFile::read(&mut v)?,
-    };
+    let parsed_class = parse_class_file(full_name, source.get_ref())?;
 
     if dump {
         info!("Dumping parsed class file...");
@@ -1,4 +1,4 @@
-use std::str::FromStr;
+use std::{fmt::Display, str::FromStr};
 
 use anyhow::{Context, anyhow, bail};
 use serde::Serialize;
@@ -66,6 +66,51 @@ impl FromStr for JvmTypeDescriptor {
     }
 }
 
+impl Display for JvmTypeDescriptor {
+    /// Formats the type as a descriptor, the inverse of the FromStr implementation
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Byte => write!(f, "B"),
+            Self::Char => write!(f, "C"),
+            Self::Double => write!(f, "D"),
+            Self::Float => write!(f, "F"),
+            Self::Int => write!(f, "I"),
+            Self::Long => write!(f, "J"),
+            Self::Short => write!(f, "S"),
+            Self::Boolean => write!(f, "Z"),
+            Self::Array(component) => write!(f, "[{component}"),
+            Self::Class(name) => write!(f, "L{name};"),
+        }
+    }
+}
+
+impl JvmTypeDescriptor {
+    /// The type of the innermost components for arrays (int for int[][]), the type itself otherwise
+    pub fn element_type(&self) -> &Self {
+        match self {
+            Self::Array(component) => component.element_type(),
+            v => v,
+        }
+    }
+
+    pub fn is_two_slots(&self) -> bool {
+        matches!(self, Self::Long | Self::Double)
+    }
+
+    pub fn is_reference(&self) -> bool {
+        matches!(self, Self::Class(_) | Self::Array(_))
+    }
+
+    /// The name of the class of the values of a reference type, as returned by Class.getName
+    /// (java.lang.String, [I, [Ljava.lang.String;...)
+    pub fn binary_name(&self) -> String {
+        match self {
+            Self::Class(name) => name.replace('/', "."),
+            v => v.to_string().replace('/', "."),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, PartialEq, Hash)]
 pub struct JvmMethodDescriptor {
     pub parameter_types: Vec<JvmTypeDescriptor>,